@@ -0,0 +1,258 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::net::split_host_port;
+use super::{HostName, NetAddr};
+
+/// Kinds of addresses tried by [`AnyAddr`] parser.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum AddrKind {
+    /// host and port
+    HostPort,
+
+    /// IP address
+    Ip,
+
+    /// Tor onion address
+    Tor,
+
+    /// I2P address
+    I2p,
+
+    /// DNS name
+    Dns,
+
+    /// Nym address
+    Nym,
+}
+
+/// Error parsing [`AnyAddr`], listing all address kinds which were tried
+/// together with the reasons they were rejected.
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub struct AnyAddrError {
+    /// The string which was parsed.
+    pub addr: String,
+    /// Tried address kinds and reasons why the string doesn't match them.
+    pub attempts: Vec<(AddrKind, String)>,
+}
+
+impl Display for AnyAddrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unable to parse '{}' as a network address; tried ", self.addr)?;
+        for (no, (kind, reason)) in self.attempts.iter().enumerate() {
+            if no > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{kind} ({reason})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Address of any of the supported kinds, with or without a port. Used for
+/// parsing strings of unknown format, like configuration values mixing IP,
+/// Tor, I2P addresses and DNS names.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Display, From)]
+#[display(inner)]
+pub enum AnyAddr {
+    /// Host without a port.
+    #[from]
+    Host(HostName),
+
+    /// Host with a port.
+    #[from]
+    Net(NetAddr<HostName>),
+}
+
+impl AnyAddr {
+    /// Returns host part of the address.
+    pub fn host(&self) -> &HostName {
+        match self {
+            AnyAddr::Host(host) => host,
+            AnyAddr::Net(addr) => &addr.host,
+        }
+    }
+
+    /// Returns port, if present.
+    pub fn port(&self) -> Option<u16> {
+        match self {
+            AnyAddr::Host(_) => None,
+            AnyAddr::Net(addr) => Some(addr.port),
+        }
+    }
+}
+
+impl FromStr for AnyAddr {
+    type Err = AnyAddrError;
+
+    /// Detects the kind of the address from its shape: bare IPv6 addresses
+    /// and hosts followed by `:<port>` (with IPv6 hosts enclosed in square
+    /// brackets) are recognized first; then the host is parsed as an IP
+    /// address, Tor or I2P address, depending on its suffix, or as a DNS
+    /// name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ip_err = match IpAddr::from_str(s) {
+            Ok(ip) => return Ok(AnyAddr::Host(HostName::Ip(ip))),
+            Err(err) => err,
+        };
+        let (host, port) = split_host_port(s).map_err(|err| AnyAddrError {
+            addr: s.to_owned(),
+            attempts: vec![
+                (AddrKind::Ip, ip_err.to_string()),
+                (AddrKind::HostPort, err.to_string()),
+            ],
+        })?;
+        let mut attempts = vec![];
+        let host = parse_host(host, &mut attempts).ok_or_else(|| AnyAddrError {
+            addr: s.to_owned(),
+            attempts,
+        })?;
+        Ok(match port {
+            None => AnyAddr::Host(host),
+            Some(port) => AnyAddr::Net(NetAddr { host, port }),
+        })
+    }
+}
+
+fn parse_host(host: &str, attempts: &mut Vec<(AddrKind, String)>) -> Option<HostName> {
+    match IpAddr::from_str(host) {
+        Ok(ip) => return Some(HostName::Ip(ip)),
+        Err(err) => attempts.push((AddrKind::Ip, err.to_string())),
+    }
+    if host.ends_with(".onion") {
+        #[cfg(feature = "tor")]
+        match super::tor::OnionAddrV3::from_str(host) {
+            Ok(onion) => return Some(HostName::Tor(onion)),
+            Err(err) => attempts.push((AddrKind::Tor, err.to_string())),
+        }
+        #[cfg(not(feature = "tor"))]
+        attempts.push((AddrKind::Tor, "support for Tor is not enabled".to_owned()));
+        return None;
+    }
+    if host.ends_with(".i2p") {
+        #[cfg(feature = "i2p")]
+        match super::i2p::I2pAddr::from_str(host) {
+            Ok(i2p) => return Some(HostName::I2p(i2p)),
+            Err(err) => attempts.push((AddrKind::I2p, err.to_string())),
+        }
+        #[cfg(not(feature = "i2p"))]
+        attempts.push((AddrKind::I2p, "support for I2P is not enabled".to_owned()));
+        return None;
+    }
+    #[cfg(feature = "dns")]
+    {
+        Some(HostName::Dns(host.to_owned()))
+    }
+    #[cfg(not(feature = "dns"))]
+    {
+        attempts.push((AddrKind::Dns, "support for DNS names is not enabled".to_owned()));
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mixed() {
+        const ONION: &str = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+        const I2P: &str = "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p";
+        let mut cases = vec![
+            (String::from("1.2.3.4"), "1.2.3.4", None),
+            (String::from("1.2.3.4:80"), "1.2.3.4", Some(80)),
+            (String::from("::1"), "::1", None),
+            (String::from("[::1]"), "::1", None),
+            (String::from("[::1]:80"), "::1", Some(80)),
+        ];
+        if cfg!(feature = "tor") {
+            cases.push((String::from(ONION), ONION, None));
+            cases.push((format!("{ONION}:9735"), ONION, Some(9735)));
+        }
+        if cfg!(feature = "i2p") {
+            cases.push((String::from(I2P), I2P, None));
+            cases.push((format!("{I2P}:4567"), I2P, Some(4567)));
+        }
+        if cfg!(feature = "dns") {
+            cases.push((String::from("example.com"), "example.com", None));
+            cases.push((String::from("example.com:443"), "example.com", Some(443)));
+        }
+        for (s, host, port) in cases {
+            let addr = AnyAddr::from_str(&s).unwrap();
+            assert_eq!(addr.port(), port, "{s}");
+            assert_eq!(addr.host(), &HostName::from_str(host).unwrap(), "{s}");
+            assert_eq!(AnyAddr::from_str(&addr.to_string()), Ok(addr));
+        }
+    }
+
+    #[test]
+    fn kinds() {
+        assert!(matches!(AnyAddr::from_str("1.2.3.4").unwrap().host(), HostName::Ip(_)));
+        #[cfg(feature = "tor")]
+        assert!(matches!(
+            AnyAddr::from_str("2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:1")
+                .unwrap()
+                .host(),
+            HostName::Tor(_)
+        ));
+        #[cfg(feature = "i2p")]
+        assert!(matches!(
+            AnyAddr::from_str("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p")
+                .unwrap()
+                .host(),
+            HostName::I2p(_)
+        ));
+        #[cfg(feature = "dns")]
+        assert!(matches!(AnyAddr::from_str("example.com").unwrap().host(), HostName::Dns(_)));
+    }
+
+    #[test]
+    fn errors() {
+        let kinds = |s: &str| {
+            AnyAddr::from_str(s)
+                .unwrap_err()
+                .attempts
+                .into_iter()
+                .map(|(kind, _)| kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds("1.2.3.4:port"), vec![AddrKind::Ip, AddrKind::HostPort]);
+        assert_eq!(kinds("::1:port"), vec![AddrKind::Ip, AddrKind::HostPort]);
+        assert_eq!(kinds("[::1"), vec![AddrKind::Ip, AddrKind::HostPort]);
+        assert_eq!(kinds("invalid.onion:80"), vec![AddrKind::Ip, AddrKind::Tor]);
+        assert_eq!(kinds("invalid.i2p"), vec![AddrKind::Ip, AddrKind::I2p]);
+        #[cfg(not(feature = "dns"))]
+        assert_eq!(kinds("example.com"), vec![AddrKind::Ip, AddrKind::Dns]);
+
+        let err = AnyAddr::from_str("1.2.3.4:port").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unable to parse '1.2.3.4:port' as a network address; tried IP address (invalid IP \
+             address syntax), host and port (network address \"1.2.3.4:port\" has an invalid port \
+             number, which must be an integer in range 0..=65535.)"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! RFC 4648 base32 encoding (lowercase, no padding) used by mixnet address
+//! types.
+//!
+//! Encoding doesn't allocate. Address data contains key material (public keys
+//! of onion services and hashes or blinded keys of I2P destinations), so it is
+//! decoded with [`decode_ct`], which does not branch on the decoded data.
+
+const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Returns length of the base32 string (without padding) encoding `len`
+/// bytes.
+pub const fn encoded_len(len: usize) -> usize { (len * 8 + 4) / 5 }
+
+/// Encodes `data` into the provided buffer, returning the part of the buffer
+/// containing the encoded string.
+///
+/// # Panics
+///
+/// If the buffer is shorter than [`encoded_len`] of the data.
+pub fn encode_into<'buf>(data: &[u8], buf: &'buf mut [u8]) -> &'buf str {
+    let len = encoded_len(data.len());
+    let out = &mut buf[..len];
+    let mut acc = 0u16;
+    let mut bits = 0u8;
+    let mut pos = 0usize;
+    for byte in data {
+        acc = (acc << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out[pos] = ALPHABET[((acc >> bits) & 0x1F) as usize];
+            pos += 1;
+        }
+    }
+    if bits > 0 {
+        out[pos] = ALPHABET[((acc << (5 - bits)) & 0x1F) as usize];
+    }
+    core::str::from_utf8(out).expect("base32 alphabet is ASCII")
+}
+
+/// Checks that the string contains only characters of base32 alphabet (in any
+/// case), allowing to reject malformed input before the decoding.
+pub fn is_base32(s: &str) -> bool { invalid_position(s).is_none() }
+
+/// Returns byte position of the first character in the string which is not a
+/// part of base32 alphabet (in any case).
+pub fn invalid_position(s: &str) -> Option<usize> {
+    s.bytes().position(|c| decode_char(c).is_none())
+}
+
+/// Decodes a single base32 character (in any case), returning `None` for
+/// characters outside of the alphabet.
+pub fn decode_char(c: u8) -> Option<u8> {
+    match c {
+        b'a'..=b'z' => Some(c - b'a'),
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'2'..=b'7' => Some(c - b'2' + 26),
+        _ => None,
+    }
+}
+
+/// Decodes a single base32 character (in any case) in constant time, returning
+/// -1 for characters outside of the alphabet.
+fn decode_char_ct(c: u8) -> i16 {
+    let c = c as i16;
+    let mut val: i16 = -1;
+    // A-Z: 0x41..=0x5A -> 0..=25
+    val += (((0x40 - c) & (c - 0x5B)) >> 8) & (c - 0x40);
+    // a-z: 0x61..=0x7A -> 0..=25
+    val += (((0x60 - c) & (c - 0x7B)) >> 8) & (c - 0x60);
+    // 2-7: 0x32..=0x37 -> 26..=31
+    val += (((0x31 - c) & (c - 0x38)) >> 8) & (c - 0x17);
+    val
+}
+
+/// Decodes base32 string (in any case, without padding) without
+/// data-dependent branches or memory accesses: the whole input is always
+/// processed and the validity of all characters is checked only after the
+/// decoding is complete. The execution time depends only on the input length.
+///
+/// Bits of the last character which do not fit into a whole byte are ignored.
+///
+/// # Returns
+///
+/// Decoded data or `None` if the string contains characters outside of the
+/// base32 alphabet.
+pub fn decode_ct(s: &str) -> Option<Vec<u8>> {
+    let mut out = vec![0u8; s.len() * 5 / 8];
+    let mut invalid = 0i16;
+    let mut acc = 0u16;
+    let mut bits = 0u8;
+    let mut pos = 0usize;
+    for c in s.bytes() {
+        let val = decode_char_ct(c);
+        invalid |= val;
+        acc = (acc << 5) | (val as u16 & 0x1F);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out[pos] = (acc >> bits) as u8;
+            pos += 1;
+        }
+    }
+    if invalid < 0 {
+        out.fill(0);
+        return None;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use ::base32::Alphabet;
+
+    use super::*;
+
+    const ALPHABET_RFC4648: Alphabet = Alphabet::RFC4648 { padding: false };
+
+    #[test]
+    fn rfc4648_vectors() {
+        let mut buf = [0u8; 16];
+        for (data, expected) in [
+            (&b""[..], ""),
+            (b"f", "my"),
+            (b"fo", "mzxq"),
+            (b"foo", "mzxw6"),
+            (b"foob", "mzxw6yq"),
+            (b"fooba", "mzxw6ytb"),
+            (b"foobar", "mzxw6ytboi"),
+        ] {
+            assert_eq!(encode_into(data, &mut buf), expected);
+        }
+    }
+
+    #[test]
+    fn decode_ct_agrees() {
+        let mut buf = [0u8; 64];
+        for data in [
+            &b""[..],
+            b"f",
+            b"fo",
+            b"foo",
+            b"foob",
+            b"fooba",
+            b"foobar",
+            &[0xFFu8; 35],
+            &[0x00u8; 32],
+        ] {
+            let encoded = encode_into(data, &mut buf).to_owned();
+            assert_eq!(decode_ct(&encoded).as_deref(), Some(data));
+            assert_eq!(
+                decode_ct(&encoded),
+                ::base32::decode(ALPHABET_RFC4648, &encoded),
+                "{encoded}"
+            );
+            let upper = encoded.to_uppercase();
+            assert_eq!(decode_ct(&upper), ::base32::decode(ALPHABET_RFC4648, &upper));
+        }
+    }
+
+    #[test]
+    fn decode_char_agrees() {
+        for c in 0..=u8::MAX {
+            assert_eq!(decode_char(c).map(i16::from).unwrap_or(-1), decode_char_ct(c), "{c}");
+        }
+        assert!(is_base32("mzxw6ytboi"));
+        assert!(is_base32("MZXW6YTBOI"));
+        assert!(is_base32(""));
+        assert!(!is_base32("mzxw6ytb=="));
+        assert_eq!(invalid_position("mzxw6ytb=="), Some(8));
+        assert_eq!(invalid_position("\u{e9}"), Some(0));
+        assert_eq!(invalid_position("mzxw6"), None);
+    }
+
+    #[test]
+    fn decode_ct_rejects() {
+        for s in [
+            "mzxw6ytb0i",
+            "mzxw6ytb1",
+            "mzxw6ytb8",
+            "mzxw6ytb9",
+            "mz w6",
+            "mz-w6",
+            "mzxw6ytbo\u{e9}",
+        ] {
+            assert_eq!(decode_ct(s), None, "{s}");
+            assert_eq!(::base32::decode(ALPHABET_RFC4648, s), None, "{s}");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bech32 encoding of 32-byte mixnet address identifiers, providing a
+//! typo-resistant alternative to base32.
+
+use ::bech32::{FromBase32, ToBase32, Variant};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Bech32Error {
+    /// invalid Bech32 human-readable part `{0}`.
+    InvalidHrp(String),
+
+    /// Bech32 string has human-readable part `{found}` instead of
+    /// `{expected}`.
+    HrpMismatch { expected: String, found: String },
+
+    /// invalid Bech32 string; {0}.
+    InvalidEncoding(String),
+
+    /// Bech32m encoding is used instead of Bech32.
+    InvalidVariant,
+
+    /// Bech32 string encodes {0} bytes instead of 32.
+    InvalidLen(usize),
+}
+
+pub(super) fn encode(hrp: &str, data: &[u8; 32]) -> Result<String, Bech32Error> {
+    ::bech32::encode(hrp, data.to_base32(), Variant::Bech32)
+        .map_err(|_| Bech32Error::InvalidHrp(hrp.to_owned()))
+}
+
+pub(super) fn decode(s: &str, hrp: &str) -> Result<[u8; 32], Bech32Error> {
+    let (found, data, variant) =
+        ::bech32::decode(s).map_err(|err| Bech32Error::InvalidEncoding(err.to_string()))?;
+    if found != hrp.to_lowercase() {
+        return Err(Bech32Error::HrpMismatch {
+            expected: hrp.to_owned(),
+            found,
+        });
+    }
+    if variant != Variant::Bech32 {
+        return Err(Bech32Error::InvalidVariant);
+    }
+    let data = Vec::<u8>::from_base32(&data)
+        .map_err(|err| Bech32Error::InvalidEncoding(err.to_string()))?;
+    <[u8; 32]>::try_from(data).map_err(|data| Bech32Error::InvalidLen(data.len()))
+}
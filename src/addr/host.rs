@@ -22,7 +22,7 @@
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
-use super::{AddrParseError, Host};
+use super::{AddrKind, AddrParseError, Host};
 use crate::addr::Localhost;
 
 /// An Internet host name which can be resolved by standard OS means (and thus
@@ -46,6 +46,37 @@ pub enum InetHost {
 #[cfg(feature = "dns")]
 impl Host for InetHost {}
 
+#[cfg(feature = "dns")]
+impl From<[u8; 4]> for InetHost {
+    fn from(octets: [u8; 4]) -> Self { Self::Ip(octets.into()) }
+}
+
+#[cfg(feature = "dns")]
+impl From<[u8; 16]> for InetHost {
+    fn from(octets: [u8; 16]) -> Self { Self::Ip(octets.into()) }
+}
+
+#[cfg(feature = "dns")]
+impl InetHost {
+    /// Returns normalized string representation of the host: compressed form
+    /// of IPv6 addresses and lowercase DNS names.
+    pub fn to_canonical(&self) -> String {
+        match self {
+            InetHost::Ip(ip) => ip.to_string(),
+            InetHost::Dns(dns) => dns.to_ascii_lowercase(),
+        }
+    }
+
+    /// Collapses IPv4-mapped IPv6 address (like `::ffff:1.2.3.4`) into its
+    /// IPv4 form; see [`HostName::normalize`].
+    pub fn normalize(self) -> Self {
+        match self {
+            InetHost::Ip(ip) => InetHost::Ip(normalize_ip(ip)),
+            host => host,
+        }
+    }
+}
+
 #[cfg(feature = "dns")]
 impl Localhost for InetHost {
     fn localhost() -> Self { Self::Ip(Localhost::localhost()) }
@@ -58,10 +89,57 @@ impl FromStr for InetHost {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match IpAddr::from_str(s) {
             Ok(addr) => Ok(Self::Ip(addr)),
-            // TODO: Check charset and format of a DNS name
-            Err(_) => Ok(Self::Dns(s.to_owned())),
+            // TODO: Check format of a DNS name
+            Err(_) => {
+                check_dns_name(s)?;
+                Ok(Self::Dns(s.to_owned()))
+            }
+        }
+    }
+}
+
+/// Errors of DNS name validation. The errors don't contain the name itself,
+/// such that names advertised by remote peers can't inject data into logs.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+#[cfg(feature = "dns")]
+pub enum DnsNameError {
+    /// DNS name is empty.
+    Empty,
+
+    /// DNS name contains control character at position {0}.
+    ControlChar(usize),
+
+    /// DNS name contains whitespace at position {0}.
+    Whitespace(usize),
+
+    /// DNS name contains non-ASCII character at position {0}; internationalized
+    /// names must be punycoded.
+    NonAscii(usize),
+}
+
+/// Checks that the DNS name is non-empty and contains only printable ASCII
+/// characters other than whitespace, rejecting names which can be used for
+/// log injection or homograph attacks. Internationalized names are accepted
+/// only in their punycode (`xn--`) form. Positions in the errors are byte
+/// offsets in the string.
+#[cfg(feature = "dns")]
+pub fn check_dns_name(s: &str) -> Result<(), DnsNameError> {
+    if s.is_empty() {
+        return Err(DnsNameError::Empty);
+    }
+    for (position, c) in s.char_indices() {
+        if c.is_control() {
+            return Err(DnsNameError::ControlChar(position));
+        }
+        if c.is_whitespace() {
+            return Err(DnsNameError::Whitespace(position));
+        }
+        if !c.is_ascii() {
+            return Err(DnsNameError::NonAscii(position));
         }
     }
+    Ok(())
 }
 
 /// A host name covers multiple types which are not necessarily resolved by an
@@ -96,6 +174,208 @@ pub enum HostName {
 
 impl Host for HostName {}
 
+impl From<[u8; 4]> for HostName {
+    fn from(octets: [u8; 4]) -> Self { Self::Ip(octets.into()) }
+}
+
+impl From<[u8; 16]> for HostName {
+    fn from(octets: [u8; 16]) -> Self { Self::Ip(octets.into()) }
+}
+
+impl HostName {
+    /// Detects whether the host belongs to one of anonymous overlay networks
+    /// (Tor, I2P or Nym), i.e. can't be used to reveal the location of the
+    /// peer.
+    pub fn is_anonymous(&self) -> bool {
+        match self {
+            HostName::Ip(_) => false,
+            #[cfg(feature = "dns")]
+            HostName::Dns(_) => false,
+            #[cfg(feature = "tor")]
+            HostName::Tor(_) => true,
+            #[cfg(feature = "i2p")]
+            HostName::I2p(_) => true,
+            #[cfg(feature = "nym")]
+            HostName::Nym(_) => true,
+        }
+    }
+
+    /// Returns normalized string representation of the host, which is
+    /// accepted by [`HostName::from_str`]: compressed form of IPv6 addresses,
+    /// lowercase DNS names and overlay network addresses with their suffixes.
+    pub fn to_canonical(&self) -> String {
+        match self {
+            HostName::Ip(ip) => ip.to_string(),
+            #[cfg(feature = "dns")]
+            HostName::Dns(dns) => dns.to_ascii_lowercase(),
+            #[cfg(feature = "tor")]
+            HostName::Tor(onion) => onion.to_canonical(),
+            #[cfg(feature = "i2p")]
+            HostName::I2p(i2p) => i2p.to_canonical(),
+            #[cfg(feature = "nym")]
+            HostName::Nym(nym) => nym.to_string(),
+        }
+    }
+
+    /// Collapses IPv4-mapped IPv6 address (like `::ffff:1.2.3.4`) into its
+    /// IPv4 form, such that the same logical host isn't stored twice; other
+    /// hosts are returned unmodified.
+    ///
+    /// Normalization is opt-in: parsing and displaying hosts always preserves
+    /// their exact form, which may be required on the wire.
+    pub fn normalize(self) -> Self {
+        match self {
+            HostName::Ip(ip) => HostName::Ip(normalize_ip(ip)),
+            #[allow(unreachable_patterns)]
+            host => host,
+        }
+    }
+
+    /// Returns kind of the host address.
+    pub fn kind(&self) -> AddrKind {
+        match self {
+            HostName::Ip(_) => AddrKind::Ip,
+            #[cfg(feature = "dns")]
+            HostName::Dns(_) => AddrKind::Dns,
+            #[cfg(feature = "tor")]
+            HostName::Tor(_) => AddrKind::Tor,
+            #[cfg(feature = "i2p")]
+            HostName::I2p(_) => AddrKind::I2p,
+            #[cfg(feature = "nym")]
+            HostName::Nym(_) => AddrKind::Nym,
+        }
+    }
+
+    /// Returns name of the address scheme of the host, as listed by
+    /// [`supported_schemes`].
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            HostName::Ip(IpAddr::V4(_)) => "ip4",
+            HostName::Ip(IpAddr::V6(_)) => "ip6",
+            #[cfg(feature = "dns")]
+            HostName::Dns(_) => "dns",
+            #[cfg(feature = "tor")]
+            HostName::Tor(_) => "onion3",
+            #[cfg(feature = "i2p")]
+            HostName::I2p(_) => "i2p",
+            #[cfg(feature = "nym")]
+            HostName::Nym(_) => "nym",
+        }
+    }
+
+    /// Classifies the host for diagnostic output; see [`AddrInfo`].
+    pub fn describe(&self) -> AddrInfo {
+        let (routable, private) = match self {
+            HostName::Ip(ip) => (!is_local_ip(ip), is_private_ip(ip)),
+            #[allow(unreachable_patterns)]
+            _ => (true, false),
+        };
+        AddrInfo {
+            kind: self.kind(),
+            routable,
+            anonymous: self.is_anonymous(),
+            private,
+            canonical: self.to_canonical(),
+        }
+    }
+
+    /// Checks whether both hosts belong to the same network, i.e. are of the
+    /// same type. IP addresses are considered to be in the same network if
+    /// both are either globally routable or local (loopback, private,
+    /// link-local, unique local or unspecified); IPv4 and IPv6 addresses may
+    /// be in the same network.
+    pub fn same_network(&self, other: &HostName) -> bool {
+        match (self, other) {
+            (HostName::Ip(a), HostName::Ip(b)) => is_local_ip(a) == is_local_ip(b),
+            #[cfg(feature = "dns")]
+            (HostName::Dns(_), HostName::Dns(_)) => true,
+            #[cfg(feature = "tor")]
+            (HostName::Tor(_), HostName::Tor(_)) => true,
+            #[cfg(feature = "i2p")]
+            (HostName::I2p(_), HostName::I2p(_)) => true,
+            #[cfg(feature = "nym")]
+            (HostName::Nym(_), HostName::Nym(_)) => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// Returns names of the address schemes of all hosts supported with the
+/// enabled crate features, as returned by [`HostName::scheme`], for
+/// generating help and validating scheme prefixes.
+pub fn supported_schemes() -> &'static [&'static str] {
+    &[
+        "ip4",
+        "ip6",
+        #[cfg(feature = "tor")]
+        "onion3",
+        #[cfg(feature = "i2p")]
+        "i2p",
+        #[cfg(feature = "dns")]
+        "dns",
+        #[cfg(feature = "nym")]
+        "nym",
+    ]
+}
+
+/// Classification of a host produced by [`HostName::describe`], intended for
+/// status and debug output.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct AddrInfo {
+    /// Kind of the host address.
+    pub kind: AddrKind,
+    /// Whether the host is reachable from outside of the local machine or
+    /// network. IP addresses are routable unless they are loopback, private,
+    /// link-local, unique local or unspecified; other hosts are routable
+    /// within their networks.
+    pub routable: bool,
+    /// Whether the host belongs to one of anonymous overlay networks; see
+    /// [`HostName::is_anonymous`].
+    pub anonymous: bool,
+    /// Whether the host is an IP address from a private range (RFC 1918 IPv4
+    /// or unique local IPv6 address).
+    pub private: bool,
+    /// Canonical form of the host; see [`HostName::to_canonical`].
+    pub canonical: String,
+}
+
+/// Converts IPv4-mapped IPv6 address into IPv4 address.
+fn normalize_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        ip => ip,
+    }
+}
+
+/// Detects IP addresses which are not globally routable.
+fn is_local_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => {
+            let segment = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local addresses (fc00::/7)
+                || segment & 0xfe00 == 0xfc00
+                // Unicast link-local addresses (fe80::/10)
+                || segment & 0xffc0 == 0xfe80
+                || ip.to_ipv4_mapped().map(|ip| is_local_ip(&IpAddr::V4(ip))).unwrap_or_default()
+        }
+    }
+}
+
+/// Detects IP addresses from private ranges: RFC 1918 IPv4 addresses and
+/// unique local IPv6 addresses (fc00::/7).
+fn is_private_ip(ip: &IpAddr) -> bool {
+    match normalize_ip(*ip) {
+        IpAddr::V4(ip) => ip.is_private(),
+        IpAddr::V6(ip) => ip.segments()[0] & 0xfe00 == 0xfc00,
+    }
+}
+
 impl Localhost for HostName {
     fn localhost() -> Self { Self::Ip(Localhost::localhost()) }
 }
@@ -123,9 +403,14 @@ impl FromStr for HostName {
                 .map(Self::Tor)
                 .map_err(AddrParseError::from);
         }
-        // TODO: Support Num and I2P
+        #[cfg(feature = "i2p")]
+        if s.ends_with(super::i2p::I2P_LEGACY_SUFFIX) {
+            return super::i2p::I2pAddr::from_str(s).map(Self::I2p).map_err(AddrParseError::from);
+        }
+        // TODO: Support Nym
         #[cfg(feature = "dns")]
         {
+            check_dns_name(s)?;
             Ok(Self::Dns(s.to_owned()))
         }
         #[cfg(not(feature = "dns"))]
@@ -134,3 +419,219 @@ impl FromStr for HostName {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+
+    #[quickcheck]
+    fn prop_ip_display_from_str(ip: IpAddr) {
+        let host = HostName::Ip(ip);
+        assert_eq!(HostName::from_str(&host.to_string()).unwrap(), host);
+    }
+
+    #[test]
+    fn from_array() {
+        assert_eq!(HostName::from([1, 2, 3, 4]), HostName::from_str("1.2.3.4").unwrap());
+        let mut ipv6 = [0u8; 16];
+        ipv6[15] = 1;
+        assert_eq!(HostName::from(ipv6), HostName::from_str("::1").unwrap());
+        #[cfg(feature = "dns")]
+        {
+            assert_eq!(InetHost::from([1, 2, 3, 4]), InetHost::from_str("1.2.3.4").unwrap());
+            assert_eq!(InetHost::from(ipv6), InetHost::from_str("::1").unwrap());
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let mut cases = vec![
+            ("1.2.3.4", "1.2.3.4"),
+            ("0:0:0:0:0:0:0:1", "::1"),
+            ("2001:DB8:0:0:1:0:0:1", "2001:db8::1:0:0:1"),
+        ];
+        if cfg!(feature = "dns") {
+            cases.push(("Example.COM", "example.com"));
+        }
+        if cfg!(feature = "tor") {
+            cases.push((
+                "2GZYXA5IHM7NSGGFXNU52RCK2VV4RVMDLKIU3ZZUI5DU4XYCLEN53WID.onion",
+                "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion",
+            ));
+        }
+        if cfg!(feature = "i2p") {
+            cases.push((
+                "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.i2p",
+                "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p",
+            ));
+        }
+        for (s, expected) in cases {
+            let canonical = HostName::from_str(s).unwrap().to_canonical();
+            assert_eq!(canonical, expected);
+            assert_eq!(HostName::from_str(&canonical).unwrap().to_canonical(), canonical);
+        }
+        #[cfg(feature = "dns")]
+        assert_eq!(InetHost::from_str("Example.COM").unwrap().to_canonical(), "example.com");
+    }
+
+    #[test]
+    fn same_network_ip() {
+        let host = |s: &str| HostName::from_str(s).unwrap();
+        assert!(host("1.2.3.4").same_network(&host("8.8.8.8")));
+        assert!(host("1.2.3.4").same_network(&host("2001:db8::1")));
+        assert!(host("192.168.0.1").same_network(&host("10.0.0.1")));
+        assert!(host("127.0.0.1").same_network(&host("::1")));
+        assert!(host("fd00::1").same_network(&host("fe80::1")));
+        assert!(host("::ffff:10.0.0.1").same_network(&host("172.16.0.1")));
+        assert!(!host("1.2.3.4").same_network(&host("192.168.0.1")));
+        assert!(!host("2001:db8::1").same_network(&host("fd00::1")));
+        assert!(!host("169.254.0.1").same_network(&host("::ffff:1.2.3.4")));
+    }
+
+    #[test]
+    #[cfg(feature = "dns")]
+    fn dns_name_validation() {
+        for name in ["example.com", "xn--bcher-kva.example", "localhost", "a-b_c.example."] {
+            assert_eq!(check_dns_name(name), Ok(()));
+            assert_eq!(HostName::from_str(name).unwrap(), HostName::Dns(name.to_owned()));
+            assert_eq!(InetHost::from_str(name).unwrap(), InetHost::Dns(name.to_owned()));
+        }
+        assert_eq!(check_dns_name(""), Err(DnsNameError::Empty));
+        assert_eq!(check_dns_name("example.com\n"), Err(DnsNameError::ControlChar(11)));
+        assert_eq!(check_dns_name("exa\x1bmple.com"), Err(DnsNameError::ControlChar(3)));
+        assert_eq!(check_dns_name("example com"), Err(DnsNameError::Whitespace(7)));
+        assert_eq!(check_dns_name("example\u{a0}com"), Err(DnsNameError::Whitespace(7)));
+        assert_eq!(check_dns_name("b\u{fc}cher.example"), Err(DnsNameError::NonAscii(1)));
+        // Homograph with Cyrillic small letter a instead of the Latin one
+        assert_eq!(check_dns_name("ex\u{430}mple.com"), Err(DnsNameError::NonAscii(2)));
+        assert!(matches!(
+            HostName::from_str("example.com\r\nINJECTED"),
+            Err(AddrParseError::Dns(DnsNameError::ControlChar(11)))
+        ));
+        assert!(matches!(
+            InetHost::from_str("example com"),
+            Err(AddrParseError::Dns(DnsNameError::Whitespace(7)))
+        ));
+    }
+
+    #[test]
+    fn normalize() {
+        let host = |s: &str| HostName::from_str(s).unwrap();
+        let mapped = host("::ffff:1.2.3.4");
+        assert_eq!(mapped.to_string(), "::ffff:1.2.3.4");
+        assert_eq!(mapped.normalize(), host("1.2.3.4"));
+        for s in ["1.2.3.4", "2001:db8::1", "::1", "::1.2.3.4", "64:ff9b::102:304"] {
+            assert_eq!(host(s).normalize(), host(s));
+        }
+
+        #[cfg(feature = "dns")]
+        {
+            let inet = |s: &str| InetHost::from_str(s).unwrap();
+            assert_eq!(inet("::ffff:1.2.3.4").normalize(), inet("1.2.3.4"));
+            assert_eq!(inet("2001:db8::1").normalize(), inet("2001:db8::1"));
+            assert_eq!(inet("example.com").normalize(), inet("example.com"));
+        }
+    }
+
+    #[test]
+    fn supported_schemes() {
+        let parse = |s: &str| HostName::from_str(s).unwrap();
+        let hosts = [
+            parse("1.2.3.4"),
+            parse("::1"),
+            #[cfg(feature = "tor")]
+            parse("2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion"),
+            #[cfg(feature = "i2p")]
+            parse("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p"),
+            #[cfg(feature = "dns")]
+            parse("example.com"),
+            #[cfg(feature = "nym")]
+            HostName::Nym(super::super::nym::NymAddr::from([7u8; 32])),
+        ];
+        for host in &hosts {
+            assert!(super::supported_schemes().contains(&host.scheme()), "{}", host.scheme());
+        }
+        let schemes = hosts.iter().map(HostName::scheme).collect::<Vec<_>>();
+        assert_eq!(schemes, super::supported_schemes());
+
+        #[cfg(not(feature = "tor"))]
+        assert!(HostName::from_str(
+            "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion"
+        )
+        .map(|host| host.scheme() != "onion3")
+        .unwrap_or(true));
+        #[cfg(not(feature = "dns"))]
+        assert!(HostName::from_str("example.com").is_err());
+    }
+
+    #[test]
+    fn describe() {
+        let info = HostName::from_str("2001:DB8::1").unwrap().describe();
+        assert_eq!(info, AddrInfo {
+            kind: AddrKind::Ip,
+            routable: true,
+            anonymous: false,
+            private: false,
+            canonical: "2001:db8::1".to_owned(),
+        });
+        let info = HostName::from_str("192.168.1.1").unwrap().describe();
+        assert!(!info.routable);
+        assert!(info.private);
+        let info = HostName::from_str("127.0.0.1").unwrap().describe();
+        assert!(!info.routable);
+        assert!(!info.private);
+        assert!(HostName::from_str("fd00::1").unwrap().describe().private);
+
+        #[cfg(feature = "dns")]
+        assert_eq!(HostName::from_str("Example.COM").unwrap().describe(), AddrInfo {
+            kind: AddrKind::Dns,
+            routable: true,
+            anonymous: false,
+            private: false,
+            canonical: "example.com".to_owned(),
+        });
+        #[cfg(feature = "tor")]
+        {
+            let onion = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+            assert_eq!(HostName::from_str(onion).unwrap().describe(), AddrInfo {
+                kind: AddrKind::Tor,
+                routable: true,
+                anonymous: true,
+                private: false,
+                canonical: onion.to_owned(),
+            });
+        }
+        #[cfg(feature = "i2p")]
+        {
+            let i2p = "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p";
+            assert_eq!(HostName::from_str(i2p).unwrap().describe(), AddrInfo {
+                kind: AddrKind::I2p,
+                routable: true,
+                anonymous: true,
+                private: false,
+                canonical: i2p.to_owned(),
+            });
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "dns", feature = "tor", feature = "i2p"))]
+    fn same_network_variants() {
+        let ip = HostName::from_str("1.2.3.4").unwrap();
+        let dns = HostName::from_str("example.com").unwrap();
+        let tor =
+            HostName::from_str("2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion")
+                .unwrap();
+        let i2p =
+            HostName::from_str("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p")
+                .unwrap();
+        let hosts = [ip, dns, tor, i2p];
+        for (i, a) in hosts.iter().enumerate() {
+            for (j, b) in hosts.iter().enumerate() {
+                assert_eq!(a.same_network(b), i == j, "{a} vs {b}");
+            }
+        }
+    }
+}
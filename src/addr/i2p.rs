@@ -19,19 +19,673 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::array::TryFromSliceError;
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
+use super::base32::{self, is_base32};
+use super::InputSnippet;
+
+pub const I2P_HASH_LEN: usize = 32;
+/// Length of the base32-encoded part of the I2P address (without `.b32.i2p`
+/// suffix).
+pub const I2P_BASE32_LEN: usize = super::base32::encoded_len(I2P_HASH_LEN);
+/// Length of the data encoded in the extended I2P address: flags byte,
+/// signature type (2 bytes) and blinded public key of the destination.
+pub const I2P_EXTENDED_LEN: usize = 35;
+/// Length of the base32-encoded part of the extended I2P address (without
+/// `.b32.i2p` suffix).
+pub const I2P_EXTENDED_BASE32_LEN: usize = super::base32::encoded_len(I2P_EXTENDED_LEN);
+/// Suffix of the I2P base32 addresses.
+pub const I2P_SUFFIX: &str = ".b32.i2p";
+/// Legacy suffix of the I2P base32 addresses without `.b32` part.
+pub const I2P_LEGACY_SUFFIX: &str = ".i2p";
+
+/// Kind of the I2P base32 address, distinguished by the length of the encoded
+/// data.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum I2pAddrKind {
+    /// Address encoding 32-byte SHA-256 hash of the destination, 52 base32
+    /// characters.
+    #[display("hash")]
+    Hash,
+
+    /// Extended address used by destinations with encrypted lease sets,
+    /// encoding 35 bytes in 56 base32 characters.
+    #[display("extended")]
+    Extended,
+}
+
+impl I2pAddrKind {
+    /// Returns length of the data encoded in the address of this kind.
+    pub const fn raw_len(self) -> usize {
+        match self {
+            I2pAddrKind::Hash => I2P_HASH_LEN,
+            I2pAddrKind::Extended => I2P_EXTENDED_LEN,
+        }
+    }
+
+    /// Returns length of the base32 part of the address of this kind.
+    pub const fn base32_len(self) -> usize { super::base32::encoded_len(self.raw_len()) }
+}
+
+/// I2P base32 address, either of [`I2pAddrKind::Hash`] or
+/// [`I2pAddrKind::Extended`] kind.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct I2pAddr {
+    kind: I2pAddrKind,
+    // Data beyond the length of the kind are always zeros
+    data: [u8; I2P_EXTENDED_LEN],
+}
+
+impl I2pAddr {
+    /// Checks whether the string would be parsed by [`I2pAddr::from_str`],
+    /// verifying only the suffix, length, base32 alphabet and unused trailing
+    /// bits without decoding the address. Intended for live validation of user input.
+    pub fn is_valid(s: &str) -> bool {
+        let Some(stripped) = strip_suffix(s) else {
+            return false;
+        };
+        (stripped.len() == I2P_BASE32_LEN || stripped.len() == I2P_EXTENDED_BASE32_LEN)
+            && is_base32(stripped)
+            && is_canonical(stripped)
+    }
+
+    /// Returns kind of the address.
+    pub fn kind(&self) -> I2pAddrKind { self.kind }
+
+    /// Returns data encoded in the address: destination hash for
+    /// [`I2pAddrKind::Hash`] or 35 bytes for [`I2pAddrKind::Extended`].
+    pub fn as_bytes(&self) -> &[u8] { &self.data[..self.kind.raw_len()] }
+
+    /// Returns the destination routing hash (SHA-256 of the destination) as
+    /// used by Java I2P `net.i2p.data.Hash`, or `None` for the extended
+    /// addresses, which encode a blinded key instead of the hash.
+    ///
+    /// The base32 address encodes the hash bytes in their natural order, and
+    /// Java I2P uses the same order, thus no byte reordering is required: the
+    /// returned array can be passed to `new Hash(byte[])` as is.
+    pub fn routing_hash(&self) -> Option<[u8; I2P_HASH_LEN]> {
+        match self.kind {
+            I2pAddrKind::Hash => {
+                let mut hash = [0u8; I2P_HASH_LEN];
+                hash.copy_from_slice(self.as_bytes());
+                Some(hash)
+            }
+            I2pAddrKind::Extended => None,
+        }
+    }
+
+    /// Checks whether the address corresponds to the destination, i.e. encodes
+    /// its hash, comparing the hashes in constant time. Extended addresses
+    /// encode a blinded key and never match.
+    pub fn matches_destination(&self, dest: &I2pDestination) -> bool {
+        let Some(hash) = self.routing_hash() else {
+            return false;
+        };
+        crate::crypto::ed25519::ct_contains(&hash, [&dest.hash()[..]])
+    }
+
+    /// Returns normalized string representation of the address: lowercase
+    /// base32 hash followed by `.b32.i2p` suffix, which is accepted by
+    /// [`I2pAddr::from_str`].
+    pub fn to_canonical(&self) -> String { self.to_string() }
+
+    /// Returns string representation of the address with the hash part in
+    /// uppercase base32, as used by some of I2P tools. The suffix remains
+    /// lowercase.
+    pub fn to_string_upper(&self) -> String {
+        let mut s = self.to_string();
+        s[..self.kind.base32_len()].make_ascii_uppercase();
+        s
+    }
+
+    /// Encodes the address hash as Bech32 string with the provided
+    /// human-readable part. Extended addresses can't be Bech32-encoded.
+    #[cfg(feature = "bech32")]
+    pub fn to_bech32(&self, hrp: &str) -> Result<String, super::Bech32Error> {
+        let hash = <[u8; I2P_HASH_LEN]>::try_from(self.as_bytes())
+            .map_err(|_| super::Bech32Error::InvalidLen(self.kind.raw_len()))?;
+        super::bech32::encode(hrp, &hash)
+    }
+
+    /// Decodes the address from Bech32 string produced by
+    /// [`I2pAddr::to_bech32`], checking its checksum and human-readable part.
+    #[cfg(feature = "bech32")]
+    pub fn from_bech32(s: &str, hrp: &str) -> Result<Self, super::Bech32Error> {
+        super::bech32::decode(s, hrp).map(Self::from)
+    }
+}
+
+impl From<[u8; I2P_HASH_LEN]> for I2pAddr {
+    fn from(hash: [u8; I2P_HASH_LEN]) -> Self {
+        let mut data = [0u8; I2P_EXTENDED_LEN];
+        data[..I2P_HASH_LEN].copy_from_slice(&hash);
+        Self {
+            kind: I2pAddrKind::Hash,
+            data,
+        }
+    }
+}
+
+impl From<[u8; I2P_EXTENDED_LEN]> for I2pAddr {
+    fn from(data: [u8; I2P_EXTENDED_LEN]) -> Self {
+        Self {
+            kind: I2pAddrKind::Extended,
+            data,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for I2pAddr {
+    type Error = TryFromSliceError;
+
+    /// Constructs address of the kind matching the data length.
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() == I2P_EXTENDED_LEN {
+            <[u8; I2P_EXTENDED_LEN]>::try_from(data).map(Self::from)
+        } else {
+            <[u8; I2P_HASH_LEN]>::try_from(data).map(Self::from)
+        }
+    }
+}
+
+/// Length of the I2P destination without the certificate payload: 256-byte
+/// encryption public key, 128-byte signing public key and 3-byte certificate
+/// header.
+pub const I2P_DESTINATION_MIN_LEN: usize = 387;
+
+/// Full I2P destination in its binary form, the SHA-256 hash of which is
+/// encoded in the [`I2pAddr`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct I2pDestination(Vec<u8>);
+
+impl I2pDestination {
+    /// Length of the area of the destination keeping encryption public key.
+    pub const ENC_KEY_AREA_LEN: usize = 256;
+    /// Length of the area of the destination keeping signing public key.
+    pub const SIG_KEY_AREA_LEN: usize = 128;
+
+    /// Assembles destination from the signing and encryption public keys and
+    /// the certificate (type byte, 2-byte big-endian length and the payload,
+    /// like a key certificate specifying the key types) in the I2P wire
+    /// layout: the encryption key is placed at the start of its 256-byte area
+    /// and the signing key at the end of its 128-byte area, with zero padding
+    /// between them, followed by the certificate.
+    ///
+    /// # Errors
+    ///
+    /// If the keys don't fit into their areas or the certificate length
+    /// doesn't match its header.
+    pub fn new(sig_key: &[u8], enc_key: &[u8], cert: &[u8]) -> Result<Self, I2pAddrError> {
+        if enc_key.len() > Self::ENC_KEY_AREA_LEN {
+            return Err(I2pAddrError::DestinationKeyTooLong(enc_key.len()));
+        }
+        if sig_key.len() > Self::SIG_KEY_AREA_LEN {
+            return Err(I2pAddrError::DestinationKeyTooLong(sig_key.len()));
+        }
+        let keys_len = Self::ENC_KEY_AREA_LEN + Self::SIG_KEY_AREA_LEN;
+        let mut data = vec![0u8; keys_len];
+        data[..enc_key.len()].copy_from_slice(enc_key);
+        data[keys_len - sig_key.len()..].copy_from_slice(sig_key);
+        data.extend_from_slice(cert);
+        Self::try_from(data)
+    }
+
+    /// Returns binary representation of the destination.
+    pub fn as_bytes(&self) -> &[u8] { &self.0 }
+
+    /// Computes the destination hash (SHA-256 of the binary destination).
+    pub fn hash(&self) -> [u8; I2P_HASH_LEN] {
+        use sha2::Digest;
+        sha2::Sha256::digest(&self.0).into()
+    }
+
+    /// Constructs I2P address of [`I2pAddrKind::Hash`] kind for the
+    /// destination.
+    pub fn to_addr(&self) -> I2pAddr { I2pAddr::from(self.hash()) }
+}
+
+impl TryFrom<Vec<u8>> for I2pDestination {
+    type Error = I2pAddrError;
+
+    /// Constructs destination from its binary form, checking that the length
+    /// of the data matches the length declared in the certificate header.
+    fn try_from(data: Vec<u8>) -> Result<Self, Self::Error> {
+        if data.len() < I2P_DESTINATION_MIN_LEN {
+            return Err(I2pAddrError::InvalidDestination(data.len()));
+        }
+        let cert_len = u16::from_be_bytes([data[385], data[386]]) as usize;
+        if data.len() != I2P_DESTINATION_MIN_LEN + cert_len {
+            return Err(I2pAddrError::InvalidDestination(data.len()));
+        }
+        Ok(Self(data))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct I2pAddr([u8; 32]);
+#[display(doc_comments)]
+pub enum I2pAddrError {
+    /// I2P address {0} doesn't end with `.b32.i2p` or `.i2p` suffix.
+    NoSuffix(InputSnippet),
+
+    /// invalid base32 in I2P address {input}: at position {position}.
+    InvalidBase32 {
+        input: InputSnippet,
+        position: usize,
+    },
+
+    /// I2P address {0} has an invalid length.
+    InvalidLen(InputSnippet),
+
+    /// I2P address {0} has a non-canonical base32 encoding with non-zero
+    /// trailing bits.
+    NonCanonical(InputSnippet),
+
+    /// binary I2P destination of {0} bytes has invalid length.
+    InvalidDestination(usize),
+
+    /// public key of {0} bytes doesn't fit into the I2P destination.
+    DestinationKeyTooLong(usize),
+}
+
+/// Checks that the bits of the last base32 character which don't fit into
+/// a whole byte are zero, such that each address has a single string
+/// representation (up to the letter case). Expects a string containing only
+/// base32 characters.
+fn is_canonical(s: &str) -> bool {
+    let unused_bits = s.len() * 5 % 8;
+    let Some(last) = s.bytes().last() else {
+        return true;
+    };
+    let value = super::base32::decode_char(last).unwrap_or_default();
+    value & ((1 << unused_bits) - 1) == 0
+}
+
+/// Strips one of the accepted address suffixes, returning `None` if the string
+/// has some other suffix.
+fn strip_suffix(s: &str) -> Option<&str> {
+    match s.strip_suffix(I2P_SUFFIX) {
+        Some(stripped) => Some(stripped),
+        None => match s.strip_suffix(I2P_LEGACY_SUFFIX) {
+            Some(stripped) => Some(stripped),
+            None if s.contains('.') => None,
+            None => Some(s),
+        },
+    }
+}
 
 impl FromStr for I2pAddr {
-    type Err = ();
+    type Err = I2pAddrError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> { todo!() }
+    /// Parses I2P base32 address in one of the following forms:
+    /// - canonical `<hash>.b32.i2p`;
+    /// - legacy `<hash>.i2p`;
+    /// - bare `<hash>` without a suffix.
+    ///
+    /// Here `<hash>` is a base32 encoding in any case of either the destination
+    /// hash (52 characters) or extended address data (56 characters); the
+    /// address kind is detected from the length. Suffixes are case-sensitive,
+    /// and no other suffixes are accepted. Encodings with non-zero trailing bits
+    /// of the last character, which would decode into the same data as the
+    /// canonical one, are rejected. Regardless of the parsed form, the
+    /// address is displayed in the canonical form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stripped = strip_suffix(s).ok_or_else(|| I2pAddrError::NoSuffix(s.into()))?;
+        if stripped.len() != I2P_BASE32_LEN && stripped.len() != I2P_EXTENDED_BASE32_LEN {
+            return Err(I2pAddrError::InvalidLen(s.into()));
+        }
+        if let Some(position) = super::base32::invalid_position(stripped) {
+            return Err(I2pAddrError::InvalidBase32 {
+                input: s.into(),
+                position,
+            });
+        }
+        if !is_canonical(stripped) {
+            return Err(I2pAddrError::NonCanonical(s.into()));
+        }
+        let data = base32::decode_ct(stripped).expect("base32 alphabet is checked");
+        I2pAddr::try_from(data.as_slice()).map_err(|_| I2pAddrError::InvalidLen(s.into()))
+    }
 }
 
 impl Display for I2pAddr {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { todo!() }
+    /// Writes lowercase base32 encoding of the address data, matching the
+    /// address kind, followed by `.b32.i2p` suffix. With the alternate flag
+    /// (`{:#}`) the suffix is omitted.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; I2P_EXTENDED_BASE32_LEN];
+        f.write_str(super::base32::encode_into(self.as_bytes(), &mut buf))?;
+        if !f.alternate() {
+            f.write_str(I2P_SUFFIX)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<I2pAddr> for String {
+    fn from(other: I2pAddr) -> Self { other.to_string() }
+}
+
+impl TryFrom<String> for I2pAddr {
+    type Error = I2pAddrError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { Self::from_str(&value) }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+
+    const I2P: &str = "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p";
+    const I2P_EXTENDED: &str = "aaaaaqqbaibqibiga4eascqlbqgq4dyqcejbgfavcylrqgi2dmob2hq7.b32.i2p";
+
+    #[quickcheck]
+    fn prop_display_from_str(addr: I2pAddr) {
+        assert_eq!(I2pAddr::from_str(&addr.to_string()), Ok(addr));
+    }
+
+    #[test]
+    fn display_from_str() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        assert_eq!(addr.to_string(), I2P);
+        assert_eq!(I2pAddr::from_str(&addr.to_string()), Ok(addr));
+    }
+
+    #[test]
+    fn is_valid() {
+        let check = |s: &str| {
+            assert_eq!(I2pAddr::is_valid(s), I2pAddr::from_str(s).is_ok(), "{s}");
+            I2pAddr::is_valid(s)
+        };
+        for s in [I2P, I2P_EXTENDED] {
+            assert!(check(s));
+            assert!(check(&s.to_uppercase().replace(".B32.I2P", I2P_SUFFIX)));
+            assert!(check(s.trim_end_matches(I2P_SUFFIX)));
+            assert!(check(&s.replace(I2P_SUFFIX, I2P_LEGACY_SUFFIX)));
+            assert!(!check(&s.to_uppercase()));
+            assert!(!check(&s.replace(I2P_SUFFIX, ".b32.i2p.")));
+            assert!(!check(&s.replace(I2P_SUFFIX, ".onion")));
+            assert!(!check(&s[1..]));
+            assert!(!check(&format!("a{s}")));
+            for pos in [0, 10, s.len() - I2P_SUFFIX.len() - 1] {
+                for c in ['0', '1', '8', '9', '=', '-', ' ', '.', '\u{e9}', 'Z', '2'] {
+                    let mut mutated = s.to_owned();
+                    mutated.replace_range(pos..pos + 1, &c.to_string());
+                    check(&mutated);
+                }
+            }
+        }
+        for s in ["", ".i2p", ".b32.i2p", "a.b32.i2p"] {
+            assert!(!check(s));
+        }
+    }
+
+    #[test]
+    fn kinds() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        assert_eq!(addr.kind(), I2pAddrKind::Hash);
+        assert_eq!(addr.as_bytes().len(), I2P_HASH_LEN);
+
+        let extended = I2pAddr::from_str(I2P_EXTENDED).unwrap();
+        assert_eq!(extended.kind(), I2pAddrKind::Extended);
+        assert_eq!(extended.as_bytes().len(), I2P_EXTENDED_LEN);
+        assert_eq!(extended.to_string(), I2P_EXTENDED);
+        assert_eq!(extended.to_canonical(), I2P_EXTENDED);
+        assert_eq!(I2pAddr::from_str(&extended.to_string_upper()), Ok(extended));
+        assert_eq!(I2pAddr::try_from(extended.as_bytes()).unwrap(), extended);
+        assert_ne!(extended, addr);
+
+        let hash = I2P_EXTENDED.trim_end_matches(I2P_SUFFIX);
+        assert_eq!(format!("{extended:#}"), hash);
+        assert_eq!(I2pAddr::from_str(hash), Ok(extended));
+        assert_eq!(I2pAddr::from_str(&hash[1..]), Err(I2pAddrError::InvalidLen(hash[1..].into())));
+    }
+
+    #[test]
+    fn canonical() {
+        let hash = I2P.trim_end_matches(I2P_SUFFIX);
+        for s in [I2P.to_owned(), format!("{hash}.i2p"), hash.to_uppercase()] {
+            let canonical = I2pAddr::from_str(&s).unwrap().to_canonical();
+            assert_eq!(canonical, I2P);
+            assert_eq!(I2pAddr::from_str(&canonical).unwrap().to_canonical(), canonical);
+        }
+    }
+
+    #[test]
+    fn routing_hash() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        let hash = addr.routing_hash().unwrap();
+        assert_eq!(
+            amplify::hex::ToHex::to_hex(&hash[..]),
+            "a0ce38ce2224d2cecaf9929388f73379259c0c27e0debdbd7ca4cd085b55e25a"
+        );
+        assert_eq!(I2pAddr::from(hash), addr);
+        assert_eq!(I2pAddr::from_str(I2P_EXTENDED).unwrap().routing_hash(), None);
+    }
+
+    #[test]
+    fn matches_destination() {
+        // Destination with key certificate of Ed25519 signature type
+        let mut data: Vec<u8> = (0..384).map(|i| i as u8).collect();
+        data.extend([5, 0, 4, 0, 7, 0, 0]);
+        let dest = I2pDestination::try_from(data.clone()).unwrap();
+        let addr =
+            I2pAddr::from_str("64orrdhmp3s3ry44ddb5uw35qx5qtgot6gcntnrqziq5d4vtmzfa.b32.i2p")
+                .unwrap();
+        assert_eq!(dest.to_addr(), addr);
+        assert!(addr.matches_destination(&dest));
+
+        let mismatched = I2pAddr::from_str(I2P).unwrap();
+        assert!(!mismatched.matches_destination(&dest));
+        let mut spoofed = data.clone();
+        spoofed[0] ^= 1;
+        assert!(!addr.matches_destination(&I2pDestination::try_from(spoofed).unwrap()));
+        assert!(!I2pAddr::from_str(I2P_EXTENDED).unwrap().matches_destination(&dest));
+
+        assert_eq!(
+            I2pDestination::try_from(data[..386].to_vec()),
+            Err(I2pAddrError::InvalidDestination(386))
+        );
+        data.push(0);
+        assert_eq!(I2pDestination::try_from(data), Err(I2pAddrError::InvalidDestination(392)));
+    }
+
+    #[test]
+    fn build_destination() {
+        // Key certificate for Ed25519 signing and X25519 encryption keys
+        let cert = [5, 0, 4, 0, 7, 0, 4];
+        let enc_key: Vec<u8> = (0..32).collect();
+        let sig_key: Vec<u8> = (100..132).collect();
+        let dest = I2pDestination::new(&sig_key, &enc_key, &cert).unwrap();
+        let bytes = dest.as_bytes();
+        assert_eq!(bytes.len(), 391);
+        assert_eq!(bytes[..32], enc_key);
+        assert!(bytes[32..352].iter().all(|b| *b == 0));
+        assert_eq!(bytes[352..384], sig_key);
+        assert_eq!(bytes[384..], cert);
+        // Hash computed with an independent implementation
+        assert_eq!(
+            dest.to_addr().to_string(),
+            "rjwprdvghcrfy32jwz7owjxpq5q75tx3akirdkj6uouhevhvl6mq.b32.i2p"
+        );
+        assert!(dest.to_addr().matches_destination(&dest));
+
+        assert_eq!(
+            I2pDestination::new(&[0; 129], &enc_key, &cert),
+            Err(I2pAddrError::DestinationKeyTooLong(129))
+        );
+        assert_eq!(
+            I2pDestination::new(&sig_key, &[0; 257], &cert),
+            Err(I2pAddrError::DestinationKeyTooLong(257))
+        );
+        assert_eq!(
+            I2pDestination::new(&sig_key, &enc_key, &cert[..6]),
+            Err(I2pAddrError::InvalidDestination(390))
+        );
+    }
+
+    #[test]
+    fn from_array() {
+        let addr = I2pAddr::from([0xA0; I2P_HASH_LEN]);
+        assert_eq!(I2pAddr::from_str(&addr.to_string()), Ok(addr));
+        assert_eq!(I2pAddr::try_from(&[0xA0; I2P_HASH_LEN][..]).unwrap(), addr);
+        assert!(I2pAddr::try_from(&[0xA0; 31][..]).is_err());
+    }
+
+    #[test]
+    fn display_alternate() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        assert_eq!(format!("{addr:#}"), I2P.trim_end_matches(I2P_SUFFIX));
+    }
+
+    #[test]
+    fn display_upper() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        let upper = addr.to_string_upper();
+        assert_eq!(upper, "UDHDRTRCETJM5SXZSKJYR5ZTPESZYDBH4DPL3PL4UTGQQW2V4JNA.b32.i2p");
+        assert_eq!(I2pAddr::from_str(&upper), Ok(addr));
+    }
+
+    #[test]
+    fn from_str_suffixes() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        let hash = I2P.trim_end_matches(I2P_SUFFIX);
+        for s in [I2P.to_owned(), format!("{hash}.i2p"), hash.to_owned()] {
+            let parsed = I2pAddr::from_str(&s).unwrap();
+            assert_eq!(parsed, addr);
+            assert_eq!(parsed.to_string(), I2P);
+        }
+    }
+
+    #[test]
+    fn from_str_errors() {
+        assert_eq!(
+            I2pAddr::from_str("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.onion"),
+            Err(I2pAddrError::NoSuffix(
+                "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.onion".into()
+            ))
+        );
+        assert_eq!(
+            I2pAddr::from_str("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.B32.I2P"),
+            Err(I2pAddrError::NoSuffix(
+                "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.B32.I2P".into()
+            ))
+        );
+        assert_eq!(
+            I2pAddr::from_str("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jn"),
+            Err(I2pAddrError::InvalidLen(
+                "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jn".into()
+            ))
+        );
+        assert_eq!(
+            I2pAddr::from_str("udhdrtrc.b32.i2p"),
+            Err(I2pAddrError::InvalidLen("udhdrtrc.b32.i2p".into()))
+        );
+        assert_eq!(
+            I2pAddr::from_str("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4j0a.b32.i2p"),
+            Err(I2pAddrError::InvalidBase32 {
+                input: InputSnippet::new(
+                    "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4j0a.b32.i2p"
+                ),
+                position: 50,
+            })
+        );
+    }
+
+    #[test]
+    fn error_snippet() {
+        let err = I2pAddr::from_str(&I2P.replace("5sx", "5s-")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid base32 in I2P address \"udhdrtrcetjm5s-zskjyr5zt...\": at position 14."
+        );
+        let I2pAddrError::InvalidBase32 { input, .. } = err else {
+            panic!("unexpected error {err}");
+        };
+        assert!(input.is_truncated());
+        assert_eq!(input.as_str().chars().count(), InputSnippet::MAX_LEN);
+
+        // All errors carrying the input truncate it
+        let long = "x".repeat(4096);
+        let non_canonical = I2P.replace("4jna.", "4jnb.");
+        for (s, err) in [
+            (format!("{long}.onion"), I2pAddr::from_str(&format!("{long}.onion")).unwrap_err()),
+            (format!("{long}.b32.i2p"), I2pAddr::from_str(&format!("{long}.b32.i2p")).unwrap_err()),
+            (non_canonical.clone(), I2pAddr::from_str(&non_canonical).unwrap_err()),
+        ] {
+            let (I2pAddrError::NoSuffix(input)
+            | I2pAddrError::InvalidLen(input)
+            | I2pAddrError::NonCanonical(input)) = &err
+            else {
+                panic!("unexpected error {err}");
+            };
+            assert_eq!(input, &InputSnippet::new(&s));
+            assert!(input.is_truncated());
+            assert!(err.to_string().len() < 128, "{err}");
+        }
+
+        let short = "udhd\u{e9}.i2p";
+        assert_eq!(InputSnippet::new(short).to_string(), format!("\"{short}\""));
+        assert!(!InputSnippet::new(short).is_truncated());
+        let unicode = "\u{e9}".repeat(InputSnippet::MAX_LEN + 1);
+        let snippet = InputSnippet::new(&unicode);
+        assert!(snippet.is_truncated());
+        assert_eq!(snippet.as_str(), &unicode[..unicode.len() - 2]);
+    }
+
+    #[test]
+    fn non_canonical() {
+        let hash = I2P.trim_end_matches(I2P_SUFFIX);
+        assert!(hash.ends_with('a'));
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        // The last character encodes 1 bit of data and 4 unused bits, thus
+        // these encodings decode into the same hash
+        for c in ['b', 'p', 'B'] {
+            let s = format!("{}{c}.b32.i2p", &hash[..hash.len() - 1]);
+            assert_eq!(base32::decode_ct(&s[..I2P_BASE32_LEN]).as_deref(), Some(addr.as_bytes()));
+            assert_eq!(I2pAddr::from_str(&s), Err(I2pAddrError::NonCanonical(s.as_str().into())));
+            assert!(!I2pAddr::is_valid(&s));
+        }
+        let s = format!("{}q.b32.i2p", &hash[..hash.len() - 1]);
+        let other = I2pAddr::from_str(&s).unwrap();
+        assert_ne!(other, addr);
+        assert_eq!(other.to_string(), s);
+        let s = format!("{}7.b32.i2p", &hash[..hash.len() - 1]);
+        assert_eq!(I2pAddr::from_str(&s), Err(I2pAddrError::NonCanonical(s.as_str().into())));
+
+        // Extended addresses have no unused bits
+        assert_eq!(I2P_EXTENDED_BASE32_LEN * 5 % 8, 0);
+        assert!(I2pAddr::from_str(&I2P_EXTENDED.replace("q7.", "q6.")).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "bech32")]
+    fn bech32() {
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        let encoded = addr.to_bech32("addr").unwrap();
+        assert!(encoded.starts_with("addr1"));
+        assert_eq!(I2pAddr::from_bech32(&encoded, "addr"), Ok(addr));
+        assert_eq!(I2pAddr::from_bech32(&encoded.to_uppercase(), "addr"), Ok(addr));
+        assert!(I2pAddr::from_bech32(&encoded, "other").is_err());
+        assert!(I2pAddr::from_str(I2P_EXTENDED).unwrap().to_bech32("addr").is_err());
+
+        let mut corrupted = encoded.into_bytes();
+        let last = corrupted.last_mut().unwrap();
+        *last = if *last == b'q' { b'p' } else { b'q' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert!(matches!(
+            I2pAddr::from_bech32(&corrupted, "addr"),
+            Err(crate::addr::Bech32Error::InvalidEncoding(_))
+        ));
+    }
 }
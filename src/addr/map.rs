@@ -0,0 +1,216 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use super::tor::{OnionAddrError, OnionAddrV3};
+
+/// Errors parsing onion address to IP alias mappings.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum AddrMapError {
+    /// mapping '{0}' must have `<onion>=<ip>` form.
+    NoSeparator(String),
+
+    /// invalid onion address in mapping; {0}
+    #[from]
+    Onion(OnionAddrError),
+
+    /// invalid IPv4 alias '{0}' in mapping.
+    InvalidIp(String),
+}
+
+/// Binding of an onion address to a local alias IPv4 address, parsed from
+/// `<onion>=<ip>` configuration lines.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display("{onion}={ip}")]
+pub struct AddrMapping {
+    pub onion: OnionAddrV3,
+    pub ip: Ipv4Addr,
+}
+
+impl FromStr for AddrMapping {
+    type Err = AddrMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (onion, ip) =
+            s.split_once('=').ok_or_else(|| AddrMapError::NoSeparator(s.to_owned()))?;
+        let onion = OnionAddrV3::from_str(onion.trim())?;
+        let ip = Ipv4Addr::from_str(ip.trim())
+            .map_err(|_| AddrMapError::InvalidIp(ip.trim().to_owned()))?;
+        Ok(AddrMapping { onion, ip })
+    }
+}
+
+/// Bidirectional map between onion addresses and local alias IPv4 addresses
+/// (like Tor `MapAddress` and transparent proxy setups). Each onion address
+/// has at most one alias, and each alias is bound to at most one onion
+/// address.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct AddrMap {
+    forward: HashMap<OnionAddrV3, Ipv4Addr>,
+    reverse: HashMap<Ipv4Addr, OnionAddrV3>,
+}
+
+impl AddrMap {
+    /// Constructs an empty map.
+    pub fn new() -> Self { Self::default() }
+
+    /// Binds the onion address to the alias, replacing previous bindings of
+    /// both the onion address and the alias.
+    pub fn insert(&mut self, mapping: AddrMapping) {
+        self.remove_onion(&mapping.onion);
+        self.remove_ip(&mapping.ip);
+        self.forward.insert(mapping.onion, mapping.ip);
+        self.reverse.insert(mapping.ip, mapping.onion);
+    }
+
+    /// Returns alias IP of the onion address.
+    pub fn resolve_onion(&self, onion: &OnionAddrV3) -> Option<Ipv4Addr> {
+        self.forward.get(onion).copied()
+    }
+
+    /// Returns onion address bound to the alias IP.
+    pub fn resolve_ip(&self, ip: &Ipv4Addr) -> Option<OnionAddrV3> { self.reverse.get(ip).copied() }
+
+    /// Removes binding of the onion address, returning its alias.
+    pub fn remove_onion(&mut self, onion: &OnionAddrV3) -> Option<Ipv4Addr> {
+        let ip = self.forward.remove(onion)?;
+        self.reverse.remove(&ip);
+        Some(ip)
+    }
+
+    /// Removes binding of the alias IP, returning the onion address it was
+    /// bound to.
+    pub fn remove_ip(&mut self, ip: &Ipv4Addr) -> Option<OnionAddrV3> {
+        let onion = self.reverse.remove(ip)?;
+        self.forward.remove(&onion);
+        Some(onion)
+    }
+
+    /// Returns number of bindings.
+    pub fn len(&self) -> usize { self.forward.len() }
+
+    /// Checks whether there are no bindings.
+    pub fn is_empty(&self) -> bool { self.forward.is_empty() }
+
+    /// Iterates over the bindings in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = AddrMapping> + '_ {
+        self.forward.iter().map(|(onion, ip)| AddrMapping {
+            onion: *onion,
+            ip: *ip,
+        })
+    }
+}
+
+impl FromStr for AddrMap {
+    type Err = AddrMapError;
+
+    /// Parses mappings in `<onion>=<ip>` form, one per line. Empty lines and
+    /// lines starting with `#` are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = AddrMap::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            map.insert(AddrMapping::from_str(line)?);
+        }
+        Ok(map)
+    }
+}
+
+impl FromIterator<AddrMapping> for AddrMap {
+    fn from_iter<T: IntoIterator<Item = AddrMapping>>(iter: T) -> Self {
+        let mut map = AddrMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl Extend<AddrMapping> for AddrMap {
+    fn extend<T: IntoIterator<Item = AddrMapping>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|mapping| self.insert(mapping));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ONION: &str = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+
+    #[test]
+    fn lookups() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        let other = OnionAddrV3::from([7u8; 32]);
+        let map = AddrMap::from_str(&format!(
+            "# aliases\n{ONION}=127.192.0.1\n\n  {other} = 127.192.0.2  \n"
+        ))
+        .unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.resolve_onion(&onion), Some(Ipv4Addr::new(127, 192, 0, 1)));
+        assert_eq!(map.resolve_ip(&Ipv4Addr::new(127, 192, 0, 2)), Some(other));
+        assert_eq!(map.resolve_ip(&Ipv4Addr::new(127, 192, 0, 3)), None);
+
+        let mapping = AddrMapping::from_str(&format!("{ONION}=127.192.0.1")).unwrap();
+        assert_eq!(mapping.to_string(), format!("{ONION}=127.192.0.1"));
+    }
+
+    #[test]
+    fn rebinding() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        let other = OnionAddrV3::from([7u8; 32]);
+        let ip = Ipv4Addr::new(10, 0, 0, 1);
+        let mut map = AddrMap::new();
+        map.insert(AddrMapping { onion, ip });
+        map.insert(AddrMapping { onion: other, ip });
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.resolve_onion(&onion), None);
+        assert_eq!(map.resolve_ip(&ip), Some(other));
+
+        map.insert(AddrMapping {
+            onion: other,
+            ip: Ipv4Addr::new(10, 0, 0, 2),
+        });
+        assert_eq!(map.resolve_ip(&ip), None);
+        assert_eq!(map.remove_onion(&other), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            AddrMap::from_str(&format!("{ONION} 127.0.0.1")),
+            Err(AddrMapError::NoSeparator(format!("{ONION} 127.0.0.1")))
+        );
+        assert_eq!(
+            AddrMapping::from_str(&format!("{ONION}=::1")),
+            Err(AddrMapError::InvalidIp("::1".to_owned()))
+        );
+        assert!(matches!(
+            AddrMapping::from_str("example.com=127.0.0.1"),
+            Err(AddrMapError::Onion(OnionAddrError::NoSuffix(_)))
+        ));
+    }
+}
@@ -21,51 +21,105 @@
 
 //! Cyphernet node address types
 
+#[cfg(feature = "std")]
+mod any;
+#[cfg(any(feature = "tor", feature = "i2p"))]
+mod base32;
+#[cfg(all(feature = "bech32", any(feature = "tor", feature = "i2p")))]
+mod bech32;
+#[cfg(feature = "std")]
 mod host;
+#[cfg(feature = "tor")]
+mod map;
 #[cfg(feature = "i2p")]
 pub mod i2p;
+#[cfg(feature = "std")]
+mod multi;
+#[cfg(feature = "std")]
 mod net;
 #[cfg(feature = "nym")]
 pub mod nym;
+#[cfg(feature = "std")]
 mod p2p;
+#[cfg(feature = "std")]
 mod proxied;
+#[cfg(feature = "std")]
+mod set;
+#[cfg(feature = "std")]
+mod socks5;
 #[cfg(feature = "tor")]
 pub mod tor;
+#[cfg(feature = "std")]
+mod url;
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use core::fmt::{self, Display, Formatter};
 
-pub use host::HostName;
+#[cfg(feature = "std")]
+pub use any::{AddrKind, AnyAddr, AnyAddrError};
+#[cfg(feature = "std")]
+pub use host::{supported_schemes, AddrInfo, HostName};
 #[cfg(feature = "dns")]
-pub use host::InetHost;
-pub use net::{NetAddr, PartialAddr};
-pub use p2p::{PeerAddr, PeerAddrParseError};
+pub use host::{DnsNameError, InetHost};
+#[cfg(feature = "tor")]
+pub use map::{AddrMap, AddrMapError, AddrMapping};
+#[cfg(feature = "std")]
+pub use multi::{DialPolicy, MultiAddr};
+#[cfg(feature = "std")]
+pub use net::{LocalAddr, NetAddr, NetAddrError, PartialAddr, RemoteAddr, UnresolvedSocketAddr};
+#[cfg(feature = "std")]
+pub use p2p::{PeerAddr, PeerAddrCodecError, PeerAddrParseError};
+#[cfg(feature = "std")]
 pub use proxied::{ProxiedAddr, ProxiedHost};
+#[cfg(feature = "std")]
+pub use set::{AddrSet, Canonical};
+#[cfg(feature = "tokio")]
+pub use socks5::{connect_via_socks5_async, Socks5Auth};
+#[cfg(feature = "std")]
+pub use socks5::{PrivacyPolicy, Socks5Dialer, SocksError};
+#[cfg(feature = "std")]
+pub use url::{PeerUrl, PeerUrlError};
+
+#[cfg(all(feature = "bech32", any(feature = "tor", feature = "i2p")))]
+pub use self::bech32::Bech32Error;
 
 /// Marker trait for all types of host names
 pub trait Host {}
 
+#[cfg(feature = "std")]
 impl Host for std::net::IpAddr {}
 
+#[cfg(feature = "std")]
 impl Host for std::net::Ipv4Addr {}
 
+#[cfg(feature = "std")]
 impl Host for std::net::Ipv6Addr {}
 
+#[cfg(feature = "std")]
 impl Host for std::net::SocketAddr {}
 
+#[cfg(feature = "std")]
 impl Host for std::net::SocketAddrV4 {}
 
+#[cfg(feature = "std")]
 impl Host for std::net::SocketAddrV6 {}
 
 pub trait Localhost: Host {
     fn localhost() -> Self;
 }
 
+#[cfg(feature = "std")]
 impl Localhost for std::net::IpAddr {
     fn localhost() -> Self { std::net::Ipv4Addr::LOCALHOST.into() }
 }
 
+#[cfg(feature = "std")]
 impl Localhost for std::net::Ipv4Addr {
     fn localhost() -> Self { std::net::Ipv4Addr::LOCALHOST }
 }
 
+#[cfg(feature = "std")]
 impl Localhost for std::net::Ipv6Addr {
     fn localhost() -> Self { std::net::Ipv6Addr::LOCALHOST }
 }
@@ -74,6 +128,7 @@ pub trait Addr: Host {
     fn port(&self) -> u16;
 }
 
+#[cfg(feature = "std")]
 impl Addr for std::net::SocketAddr {
     fn port(&self) -> u16 {
         match self {
@@ -83,10 +138,12 @@ impl Addr for std::net::SocketAddr {
     }
 }
 
+#[cfg(feature = "std")]
 impl Addr for std::net::SocketAddrV4 {
     fn port(&self) -> u16 { std::net::SocketAddrV4::port(self) }
 }
 
+#[cfg(feature = "std")]
 impl Addr for std::net::SocketAddrV6 {
     fn port(&self) -> u16 { std::net::SocketAddrV6::port(self) }
 }
@@ -124,22 +181,68 @@ impl PortOr for std::net::Ipv6Addr {
 ///
 /// The trait is required since the socket address has to be constructed from a
 /// type reference without cloning.
+#[cfg(feature = "std")]
 pub trait ToSocketAddr {
     fn to_socket_addr(&self) -> std::net::SocketAddr;
 }
 
+#[cfg(feature = "std")]
 impl ToSocketAddr for std::net::SocketAddr {
     fn to_socket_addr(&self) -> std::net::SocketAddr { *self }
 }
 
+#[cfg(feature = "std")]
 impl ToSocketAddr for std::net::SocketAddrV4 {
     fn to_socket_addr(&self) -> std::net::SocketAddr { std::net::SocketAddr::V4(*self) }
 }
 
+#[cfg(feature = "std")]
 impl ToSocketAddr for std::net::SocketAddrV6 {
     fn to_socket_addr(&self) -> std::net::SocketAddr { std::net::SocketAddr::V6(*self) }
 }
 
+/// Fragment of an invalid input, limited to the first
+/// [`InputSnippet::MAX_LEN`] characters, which is carried by the address
+/// parsing errors. Displayed in quotes, with an ellipsis if truncated.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InputSnippet {
+    snippet: String,
+    truncated: bool,
+}
+
+impl InputSnippet {
+    /// Maximal number of characters of the input kept in the snippet.
+    pub const MAX_LEN: usize = 24;
+
+    /// Constructs snippet from the first [`InputSnippet::MAX_LEN`] characters
+    /// of the input.
+    pub fn new(input: &str) -> Self {
+        let end = input.char_indices().nth(Self::MAX_LEN).map(|(pos, _)| pos);
+        InputSnippet {
+            snippet: input[..end.unwrap_or(input.len())].to_owned(),
+            truncated: end.is_some(),
+        }
+    }
+
+    /// Returns the kept part of the input.
+    pub fn as_str(&self) -> &str { &self.snippet }
+
+    /// Detects whether the input was longer than the snippet.
+    pub fn is_truncated(&self) -> bool { self.truncated }
+}
+
+impl From<&str> for InputSnippet {
+    fn from(input: &str) -> Self { InputSnippet::new(input) }
+}
+
+impl Display for InputSnippet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}{}\"", self.snippet, if self.truncated { "..." } else { "" })
+    }
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, Display, Error, From)]
 #[display(doc_comments)]
 pub enum AddrParseError {
@@ -149,6 +252,18 @@ pub enum AddrParseError {
     /// invalid Tor ONION address
     Tor(tor::OnionAddrError),
 
+    #[from]
+    #[cfg(feature = "i2p")]
+    #[display(inner)]
+    /// invalid I2P address
+    I2p(i2p::I2pAddrError),
+
+    #[from]
+    #[cfg(feature = "dns")]
+    #[display(inner)]
+    /// invalid DNS name
+    Dns(host::DnsNameError),
+
     #[from]
     #[display(inner)]
     /// invalid IP or socket address
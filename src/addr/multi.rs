@@ -0,0 +1,163 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use super::{HostName, NetAddr, NetAddrError};
+
+/// Order in which the addresses of a multi-address peer are tried when
+/// connecting to it.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum DialPolicy {
+    /// Addresses from anonymous overlay networks (Tor, I2P, Nym) are tried
+    /// before clearnet addresses.
+    #[default]
+    AnonymousFirst,
+
+    /// Clearnet addresses, which have lower latency, are tried before the
+    /// addresses from anonymous overlay networks.
+    FastestFirst,
+}
+
+/// Ordered list of network addresses under which the same peer can be
+/// reached, for instance both over Tor and clearnet (dual-stack peers).
+///
+/// The order of the addresses is preserved and represents the preference of
+/// the peer advertising them.
+#[derive(Wrapper, Clone, PartialEq, Eq, Hash, Debug, Default, From)]
+#[wrapper(Deref)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct MultiAddr(Vec<NetAddr<HostName>>);
+
+impl MultiAddr {
+    /// Constructs multi-address from a list of addresses, preserving their
+    /// order.
+    pub fn new(addrs: impl IntoIterator<Item = NetAddr<HostName>>) -> Self {
+        Self(addrs.into_iter().collect())
+    }
+
+    /// Adds a new address to the end of the list.
+    pub fn push(&mut self, addr: NetAddr<HostName>) { self.0.push(addr) }
+
+    /// Selects the first address matching the connection policy. If
+    /// `anonymous_only` is set, only addresses from anonymous overlay networks
+    /// (Tor, I2P, Nym) are considered; otherwise the first address from the
+    /// list is returned.
+    pub fn preferred(&self, anonymous_only: bool) -> Option<&NetAddr<HostName>> {
+        self.0.iter().find(|addr| !anonymous_only || addr.host.is_anonymous())
+    }
+
+    /// Returns all addresses in the order they should be tried according to
+    /// the dial `policy`. Addresses within the same class (anonymous or
+    /// clearnet) keep the order provided by the peer.
+    pub fn dial_order(&self, policy: DialPolicy) -> impl Iterator<Item = &NetAddr<HostName>> {
+        let mut addrs = self.0.iter().collect::<Vec<_>>();
+        addrs.sort_by_key(|addr| match policy {
+            DialPolicy::AnonymousFirst => !addr.host.is_anonymous(),
+            DialPolicy::FastestFirst => addr.host.is_anonymous(),
+        });
+        addrs.into_iter()
+    }
+}
+
+impl Display for MultiAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut iter = self.0.iter();
+        if let Some(addr) = iter.next() {
+            Display::fmt(addr, f)?;
+        }
+        for addr in iter {
+            f.write_str(",")?;
+            Display::fmt(addr, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MultiAddr {
+    type Err = NetAddrError;
+
+    /// Parses comma-separated list of addresses. Whitespaces around each of
+    /// the addresses are ignored. An empty (or whitespace-only) string is
+    /// parsed as an empty list, matching its [`Display`] form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        s.split(',').map(str::trim).map(NetAddr::from_str).collect::<Result<_, _>>().map(Self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_display() {
+        let s = "1.2.3.4:8333,5.6.7.8:8080";
+        let multi = MultiAddr::from_str("1.2.3.4:8333, 5.6.7.8:8080").unwrap();
+        assert_eq!(multi.len(), 2);
+        assert_eq!(multi.to_string(), s);
+        assert_eq!(MultiAddr::from_str(s).unwrap(), multi);
+
+        let empty = MultiAddr::default();
+        assert_eq!(empty.to_string(), "");
+        assert_eq!(MultiAddr::from_str(&empty.to_string()).unwrap(), empty);
+        assert!(MultiAddr::from_str("1.2.3.4:8333,").is_err());
+    }
+
+    #[test]
+    fn preferred_clearnet_only() {
+        let multi = MultiAddr::from_str("1.2.3.4:8333,5.6.7.8:8333").unwrap();
+        assert_eq!(multi.preferred(false), Some(&NetAddr::from_str("1.2.3.4:8333").unwrap()));
+        assert_eq!(multi.preferred(true), None);
+        assert_eq!(MultiAddr::default().preferred(false), None);
+    }
+
+    #[test]
+    #[cfg(feature = "tor")]
+    fn preferred_dual_stack() {
+        let onion = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735";
+        let multi = MultiAddr::from_str(&format!("1.2.3.4:8333,{onion}")).unwrap();
+        assert_eq!(multi.preferred(false), Some(&NetAddr::from_str("1.2.3.4:8333").unwrap()));
+        assert_eq!(multi.preferred(true), Some(&NetAddr::from_str(onion).unwrap()));
+    }
+
+    #[test]
+    fn dial_order_clearnet_only() {
+        let multi = MultiAddr::from_str("1.2.3.4:8333,5.6.7.8:8333").unwrap();
+        for policy in [DialPolicy::AnonymousFirst, DialPolicy::FastestFirst] {
+            assert!(multi.dial_order(policy).eq(multi.iter()));
+        }
+        assert_eq!(MultiAddr::default().dial_order(DialPolicy::default()).next(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "tor")]
+    fn dial_order_dual_stack() {
+        let onion = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735";
+        let multi = MultiAddr::from_str(&format!("1.2.3.4:8333,{onion},5.6.7.8:8333")).unwrap();
+        let order = |policy| multi.dial_order(policy).map(NetAddr::to_string).collect::<Vec<_>>();
+        assert_eq!(order(DialPolicy::AnonymousFirst), [onion, "1.2.3.4:8333", "5.6.7.8:8333"]);
+        assert_eq!(order(DialPolicy::FastestFirst), ["1.2.3.4:8333", "5.6.7.8:8333", onion]);
+    }
+}
@@ -26,7 +26,91 @@ use std::{fmt, io, vec};
 
 #[cfg(feature = "dns")]
 use super::InetHost;
-use super::{Addr, AddrParseError, Host, Localhost, ToSocketAddr};
+use super::{Addr, AddrParseError, Host, HostName, InputSnippet, Localhost, ToSocketAddr};
+
+/// Errors parsing network address string.
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum NetAddrError {
+    /// network address {0} lacks port number.
+    PortAbsent(InputSnippet),
+
+    /// network address {0} has an invalid port number, which must be an integer
+    /// in range 0..=65535.
+    InvalidPort(InputSnippet),
+
+    /// network address {0} has unbalanced or misplaced square brackets around
+    /// the host.
+    InvalidBrackets(InputSnippet),
+
+    /// IPv6 host in network address {0} must be enclosed in square brackets.
+    UnbracketedIpv6(InputSnippet),
+
+    /// {0} is not a valid URL scheme.
+    InvalidScheme(InputSnippet),
+
+    /// network address can't be represented as URL; {0}
+    #[cfg(feature = "url")]
+    #[from]
+    InvalidUrl(::url::ParseError),
+
+    /// network address has malformed host; {0}
+    #[from]
+    InvalidHost(AddrParseError),
+}
+
+/// Splits network address string into the host and optional port parts. IPv6
+/// hosts must be enclosed into square brackets, which are removed from the
+/// returned host.
+pub(super) fn split_host_port(s: &str) -> Result<(&str, Option<u16>), NetAddrError> {
+    let parse_port =
+        |port: &str| u16::from_str(port).map_err(|_| NetAddrError::InvalidPort(s.into()));
+    if let Some(rest) = s.strip_prefix('[') {
+        let (host, rest) =
+            rest.split_once(']').ok_or_else(|| NetAddrError::InvalidBrackets(s.into()))?;
+        if host.contains(['[', ']']) {
+            return Err(NetAddrError::InvalidBrackets(s.into()));
+        }
+        return match rest {
+            "" => Ok((host, None)),
+            _ => match rest.strip_prefix(':') {
+                Some(port) => Ok((host, Some(parse_port(port)?))),
+                None => Err(NetAddrError::InvalidBrackets(s.into())),
+            },
+        };
+    }
+    if s.contains(['[', ']']) {
+        return Err(NetAddrError::InvalidBrackets(s.into()));
+    }
+    match s.rsplit_once(':') {
+        None => Ok((s, None)),
+        Some((host, _)) if host.contains(':') => Err(NetAddrError::UnbracketedIpv6(s.into())),
+        Some((host, port)) => Ok((host, Some(parse_port(port)?))),
+    }
+}
+
+/// Writes host, enclosing it into square brackets if it contains colons (i.e.
+/// is an IPv6 address).
+fn fmt_host(host: &impl Display, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut colons = ColonDetector(false);
+    fmt::write(&mut colons, format_args!("{host}"))?;
+    if colons.0 {
+        write!(f, "[{host}]")
+    } else {
+        write!(f, "{host}")
+    }
+}
+
+/// Writer discarding the formatted output, which only records whether it
+/// contains colons, such that [`fmt_host`] doesn't allocate.
+struct ColonDetector(bool);
+
+impl fmt::Write for ColonDetector {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 |= s.contains(':');
+        Ok(())
+    }
+}
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -35,6 +119,21 @@ pub struct NetAddr<H: Host> {
     pub port: u16,
 }
 
+impl<H: Host> NetAddr<H> {
+    /// Returns port of the address.
+    pub fn port(&self) -> u16 { self.port }
+
+    /// Returns the same host with the port replaced by `port`.
+    pub fn with_port(self, port: u16) -> Self { NetAddr { port, ..self } }
+
+    /// Checks whether both addresses have the same host, ignoring their ports.
+    /// Useful for merging multiple ports advertised for the same host.
+    pub fn same_host(&self, other: &Self) -> bool
+    where H: PartialEq {
+        self.host == other.host
+    }
+}
+
 impl<H: Localhost> NetAddr<H> {
     pub fn localhost(port: u16) -> Self {
         Self {
@@ -46,6 +145,90 @@ impl<H: Localhost> NetAddr<H> {
 
 impl<H: Host> Host for NetAddr<H> {}
 
+impl NetAddr<HostName> {
+    /// Returns normalized string representation of the address, which uses
+    /// [`HostName::to_canonical`] for the host part and is accepted by
+    /// [`NetAddr::from_str`].
+    pub fn to_canonical(&self) -> String {
+        let host = self.host.to_canonical();
+        if host.contains(':') {
+            format!("[{host}]:{}", self.port)
+        } else {
+            format!("{host}:{}", self.port)
+        }
+    }
+
+    /// Constructs URL with the given `scheme`, the address as URL authority
+    /// and an empty path, like `http://<onion>.onion:80/`. IPv6 hosts are
+    /// enclosed in square brackets. The port is omitted from the URL
+    /// serialization if it is the default one for the scheme; use
+    /// [`url::Url::port_or_known_default`] to read it back.
+    ///
+    /// # Errors
+    ///
+    /// [`NetAddrError::InvalidScheme`] if the scheme doesn't match RFC 3986
+    /// `ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )` syntax, or
+    /// [`NetAddrError::InvalidUrl`] if the host is not accepted by the URL
+    /// parser.
+    #[cfg(feature = "url")]
+    pub fn to_url(&self, scheme: &str) -> Result<::url::Url, NetAddrError> {
+        let mut chars = scheme.chars();
+        let valid = chars.next().map(|c| c.is_ascii_alphabetic()).unwrap_or_default()
+            && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        if !valid {
+            return Err(NetAddrError::InvalidScheme(scheme.into()));
+        }
+        let url = format!("{}://{}/", scheme.to_ascii_lowercase(), self.to_canonical());
+        ::url::Url::parse(&url).map_err(NetAddrError::from)
+    }
+
+    /// Parses `host:port` string like [`NetAddr::from_str`], but additionally
+    /// accepts onion hosts without the `.onion` suffix, which is implied by
+    /// the context in some configuration formats. A host of
+    /// [`super::tor::ONION_V3_BASE32_LEN`] base32 characters is parsed as a V3
+    /// onion address, including verification of its checksum, rather than as
+    /// a DNS name.
+    #[cfg(feature = "tor")]
+    pub fn from_str_assume_onion(s: &str) -> Result<Self, NetAddrError> {
+        use super::tor::{OnionAddrV3, ONION_V3_BASE32_LEN};
+
+        match split_host_port(s)? {
+            (_, None) => Err(NetAddrError::PortAbsent(s.into())),
+            (host, Some(port))
+                if host.len() == ONION_V3_BASE32_LEN && super::base32::is_base32(host) =>
+            {
+                let onion = OnionAddrV3::from_str(&format!("{host}.onion"))
+                    .map_err(AddrParseError::from)?;
+                Ok(NetAddr {
+                    host: HostName::Tor(onion),
+                    port,
+                })
+            }
+            _ => Self::from_str(s),
+        }
+    }
+
+    /// Parses a list of addresses separated by commas and/or whitespaces,
+    /// like the bootstrap node lists provided by operators. Empty entries are
+    /// ignored.
+    ///
+    /// # Returns
+    ///
+    /// Successfully parsed addresses and the entries which failed to parse, in
+    /// the order of their appearance.
+    pub fn parse_bootstrap(s: &str) -> (Vec<Self>, Vec<String>) {
+        let mut addrs = vec![];
+        let mut failed = vec![];
+        for entry in s.split(|c: char| c == ',' || c.is_whitespace()).filter(|e| !e.is_empty()) {
+            match Self::from_str(entry) {
+                Ok(addr) => addrs.push(addr),
+                Err(_) => failed.push(entry.to_owned()),
+            }
+        }
+        (addrs, failed)
+    }
+}
+
 impl<H: Host> Addr for NetAddr<H> {
     fn port(&self) -> u16 { self.port }
 }
@@ -54,7 +237,7 @@ impl<H: Host> Display for NetAddr<H>
 where H: Display
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Display::fmt(&self.host, f)?;
+        fmt_host(&self.host, f)?;
         write!(f, ":{}", self.port)
     }
 }
@@ -64,19 +247,31 @@ where
     H: FromStr,
     AddrParseError: From<H::Err>,
 {
-    type Err = AddrParseError;
+    type Err = NetAddrError;
 
+    /// Parses `host:port` string; IPv6 hosts must be enclosed into square
+    /// brackets, like in `[::1]:8080`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.rsplit_once(':') {
-            None => Err(AddrParseError::PortAbsent),
-            Some((host, port)) => Ok(NetAddr {
-                host: H::from_str(host)?,
-                port: u16::from_str(port).map_err(|_| AddrParseError::InvalidPort)?,
+        match split_host_port(s)? {
+            (_, None) => Err(NetAddrError::PortAbsent(s.into())),
+            (host, Some(port)) => Ok(NetAddr {
+                host: H::from_str(host).map_err(AddrParseError::from)?,
+                port,
             }),
         }
     }
 }
 
+impl<H: Host> TryFrom<&str> for NetAddr<H>
+where
+    H: FromStr,
+    AddrParseError: From<H::Err>,
+{
+    type Error = NetAddrError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> { Self::from_str(s) }
+}
+
 impl<H: Host> From<SocketAddr> for NetAddr<H>
 where H: From<IpAddr>
 {
@@ -145,6 +340,111 @@ impl ToSocketAddrs for NetAddr<InetHost> {
     }
 }
 
+/// Socket address which keeps the original host name and is never resolved
+/// into an IP address implicitly.
+///
+/// Unlike [`NetAddr<HostName>`], [`ToSocketAddrs`] is implemented for any host
+/// name, but succeeds only for IP addresses: DNS names and overlay network
+/// addresses (Tor, I2P, Nym) return [`io::ErrorKind::Unsupported`] error, such
+/// that connecting via `std::net` can't leak them to the OS resolver and they
+/// have to be connected to through a proxy.
+#[derive(Wrapper, Clone, PartialEq, Eq, Hash, Debug, Display, From)]
+#[wrapper(Deref)]
+#[display(inner)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct UnresolvedSocketAddr(NetAddr<HostName>);
+
+impl UnresolvedSocketAddr {
+    /// Constructs socket address from a host name and port.
+    pub fn new(host: HostName, port: u16) -> Self { Self(NetAddr { host, port }) }
+}
+
+impl Host for UnresolvedSocketAddr {}
+
+impl Addr for UnresolvedSocketAddr {
+    fn port(&self) -> u16 { self.0.port }
+}
+
+impl FromStr for UnresolvedSocketAddr {
+    type Err = NetAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { NetAddr::from_str(s).map(Self) }
+}
+
+impl ToSocketAddrs for UnresolvedSocketAddr {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        match self.0.host {
+            HostName::Ip(ip) => Ok(vec![SocketAddr::new(ip, self.0.port)].into_iter()),
+            #[allow(unreachable_patterns)]
+            ref host => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("host {host} must not be resolved and requires a proxy connection"),
+            )),
+        }
+    }
+}
+
+/// Address of the local node (like the address it listens on or advertises to
+/// the peers), distinguishing it from the [`RemoteAddr`] of a peer in function
+/// signatures.
+///
+/// The two types can't be used interchangeably; a conversion requires going
+/// through the underlying [`NetAddr`] explicitly:
+///
+/// ```compile_fail
+/// # use cyphernet::addr::{LocalAddr, RemoteAddr};
+/// fn connect(local: LocalAddr, remote: RemoteAddr) {}
+///
+/// let local: LocalAddr = "127.0.0.1:8080".parse().unwrap();
+/// let remote: RemoteAddr = "10.0.0.1:8080".parse().unwrap();
+/// connect(remote, local);
+/// ```
+///
+/// ```
+/// # use cyphernet::addr::{LocalAddr, RemoteAddr};
+/// # fn connect(local: LocalAddr, remote: RemoteAddr) {}
+/// let local: LocalAddr = "127.0.0.1:8080".parse().unwrap();
+/// let remote: RemoteAddr = "10.0.0.1:8080".parse().unwrap();
+/// connect(local, remote);
+/// ```
+#[derive(Wrapper, Clone, PartialEq, Eq, Hash, Debug, Display, From)]
+#[wrapper(Deref)]
+#[display(inner)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct LocalAddr(NetAddr<HostName>);
+
+impl LocalAddr {
+    /// Constructs local address from a host name and port.
+    pub fn new(host: HostName, port: u16) -> Self { Self(NetAddr { host, port }) }
+}
+
+impl FromStr for LocalAddr {
+    type Err = NetAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { NetAddr::from_str(s).map(Self) }
+}
+
+/// Address of a remote peer, distinguishing it from the [`LocalAddr`] of the
+/// node in function signatures.
+#[derive(Wrapper, Clone, PartialEq, Eq, Hash, Debug, Display, From)]
+#[wrapper(Deref)]
+#[display(inner)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct RemoteAddr(NetAddr<HostName>);
+
+impl RemoteAddr {
+    /// Constructs remote address from a host name and port.
+    pub fn new(host: HostName, port: u16) -> Self { Self(NetAddr { host, port }) }
+}
+
+impl FromStr for RemoteAddr {
+    type Err = NetAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { NetAddr::from_str(s).map(Self) }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PartialAddr<H: Host, const DEFAULT_PORT: u16> {
@@ -191,7 +491,7 @@ impl<H: Host, const DEFAULT_PORT: u16> Display for PartialAddr<H, DEFAULT_PORT>
 where H: Display
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Display::fmt(&self.host, f)?;
+        fmt_host(&self.host, f)?;
         if let Some(port) = self.port {
             write!(f, ":{}", port)?;
         }
@@ -204,19 +504,321 @@ where
     H: FromStr,
     AddrParseError: From<H::Err>,
 {
-    type Err = AddrParseError;
+    type Err = NetAddrError;
 
+    /// Parses either `host:port` or a bare `host` string; IPv6 hosts must be
+    /// enclosed into square brackets, like in `[::1]` or `[::1]:8080`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some((host, port)) = s.rsplit_once(':') {
-            Ok(PartialAddr {
-                host: H::from_str(host)?,
-                port: Some(u16::from_str(port).map_err(|_| AddrParseError::InvalidPort)?),
-            })
-        } else {
-            Ok(PartialAddr {
-                host: H::from_str(s)?,
-                port: None,
+        let (host, port) = split_host_port(s)?;
+        Ok(PartialAddr {
+            host: H::from_str(host).map_err(AddrParseError::from)?,
+            port,
+        })
+    }
+}
+
+impl<H: Host, const DEFAULT_PORT: u16> TryFrom<&str> for PartialAddr<H, DEFAULT_PORT>
+where
+    H: FromStr,
+    AddrParseError: From<H::Err>,
+{
+    type Error = NetAddrError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> { Self::from_str(s) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "tor")]
+    fn assume_onion() {
+        const ONION: &str = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid";
+
+        let addr = NetAddr::<HostName>::from_str_assume_onion(&format!("{ONION}:80")).unwrap();
+        assert_eq!(addr, NetAddr::from_str(&format!("{ONION}.onion:80")).unwrap());
+        assert_eq!(
+            NetAddr::<HostName>::from_str_assume_onion(&format!("{ONION}.onion:80")).unwrap(),
+            addr
+        );
+        assert_eq!(
+            NetAddr::<HostName>::from_str_assume_onion("1.2.3.4:80").unwrap(),
+            NetAddr::from_str("1.2.3.4:80").unwrap()
+        );
+        assert!(matches!(
+            NetAddr::<HostName>::from_str_assume_onion(ONION),
+            Err(NetAddrError::PortAbsent(_))
+        ));
+        // Checksum is verified
+        let corrupted = format!("{}a{}:80", &ONION[..10], &ONION[11..]);
+        assert!(matches!(
+            NetAddr::<HostName>::from_str_assume_onion(&corrupted),
+            Err(NetAddrError::InvalidHost(AddrParseError::Tor(_)))
+        ));
+
+        // Strict parser doesn't treat the shorthand as an onion address
+        let strict = NetAddr::<HostName>::from_str(&format!("{ONION}:80"));
+        assert!(!matches!(
+            strict,
+            Ok(NetAddr {
+                host: HostName::Tor(_),
+                ..
             })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "url")]
+    fn url() {
+        use ::url::Url;
+
+        fn check(addr: &NetAddr<HostName>, scheme: &str, expected: &str) {
+            let url = addr.to_url(scheme).unwrap();
+            assert_eq!(url.as_str(), expected);
+            let parsed = Url::parse(url.as_str()).unwrap();
+            assert_eq!(parsed, url);
+            assert_eq!(parsed.port_or_known_default(), Some(addr.port));
+            let authority = format!("{}:{}", parsed.host_str().unwrap(), addr.port);
+            assert_eq!(&NetAddr::<HostName>::from_str(&authority).unwrap(), addr);
+        }
+
+        let addr = NetAddr::<HostName>::from_str("1.2.3.4:80").unwrap();
+        check(&addr, "http", "http://1.2.3.4/");
+        check(&addr, "HTTPS", "https://1.2.3.4:80/");
+        check(&addr, "git+ssh", "git+ssh://1.2.3.4:80/");
+        let addr = NetAddr::<HostName>::from_str("[::1]:8080").unwrap();
+        check(&addr, "ws", "ws://[::1]:8080/");
+
+        #[cfg(feature = "tor")]
+        {
+            let addr = NetAddr::<HostName>::from_str(
+                "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:8080",
+            )
+            .unwrap();
+            check(
+                &addr,
+                "http",
+                "http://2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:8080/",
+            );
+        }
+        #[cfg(feature = "i2p")]
+        {
+            let addr = NetAddr::<HostName>::from_str(
+                "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p:0",
+            )
+            .unwrap();
+            check(
+                &addr,
+                "http",
+                "http://udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p:0/",
+            );
+        }
+
+        for scheme in ["", "1http", "ht tp", "http:", "h\u{e9}"] {
+            assert!(matches!(addr.to_url(scheme), Err(NetAddrError::InvalidScheme(_))), "{scheme}");
         }
+        let scheme = format!("http{}:", "s".repeat(4096));
+        let Err(NetAddrError::InvalidScheme(input)) = addr.to_url(&scheme) else {
+            panic!("invalid scheme accepted");
+        };
+        assert_eq!(input, InputSnippet::new(&scheme));
+        assert!(input.is_truncated());
+    }
+
+    #[test]
+    fn same_host() {
+        let addr = NetAddr::<HostName>::from_str("1.2.3.4:8333").unwrap();
+        assert!(addr.same_host(&addr));
+        assert!(addr.same_host(&addr.clone().with_port(9735)));
+        assert!(!addr.same_host(&NetAddr::from_str("1.2.3.5:8333").unwrap()));
+        assert!(!addr.same_host(&NetAddr::from_str("[::ffff:1.2.3.4]:8333").unwrap()));
+
+        #[cfg(feature = "tor")]
+        {
+            let onion = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+            let addr = NetAddr::<HostName>::from_str(&format!("{onion}:9735")).unwrap();
+            assert!(addr.same_host(&NetAddr::from_str(&format!("{onion}:80")).unwrap()));
+            assert!(!addr.same_host(&NetAddr::from_str("1.2.3.4:9735").unwrap()));
+        }
+    }
+
+    #[test]
+    fn port() {
+        let addr = NetAddr::<HostName>::from_str("[2001:db8::1]:8333").unwrap();
+        assert_eq!(addr.port(), 8333);
+        let addr = addr.with_port(9735);
+        assert_eq!(addr.port(), 9735);
+        assert_eq!(addr.host, HostName::Ip("2001:db8::1".parse().unwrap()));
+        assert_eq!(addr.to_string(), "[2001:db8::1]:9735");
+
+        #[cfg(feature = "tor")]
+        {
+            let addr = NetAddr::<HostName>::from_str(
+                "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735",
+            )
+            .unwrap();
+            assert_eq!(addr.port(), 9735);
+            let host = addr.host.clone();
+            let addr = addr.with_port(0);
+            assert_eq!(addr.port(), 0);
+            assert_eq!(addr.host, host);
+        }
+    }
+
+    #[test]
+    fn bootstrap() {
+        let (addrs, failed) = NetAddr::<HostName>::parse_bootstrap(
+            " 1.2.3.4:8333, [::1]:9735\t10.0.0.1:0,,\n  127.0.0.1 1.2.3.4:port, ",
+        );
+        assert_eq!(addrs, vec![
+            NetAddr {
+                host: HostName::Ip(Ipv4Addr::new(1, 2, 3, 4).into()),
+                port: 8333
+            },
+            NetAddr {
+                host: HostName::Ip(Ipv6Addr::LOCALHOST.into()),
+                port: 9735
+            },
+            NetAddr {
+                host: HostName::Ip(Ipv4Addr::new(10, 0, 0, 1).into()),
+                port: 0
+            },
+        ]);
+        assert_eq!(failed, vec!["127.0.0.1".to_owned(), "1.2.3.4:port".to_owned()]);
+
+        #[cfg(all(feature = "tor", feature = "i2p"))]
+        {
+            let (addrs, failed) = NetAddr::<HostName>::parse_bootstrap(
+                "1.2.3.4:8333, \
+                 2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735  \
+                 udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p:0,abc.onion:1",
+            );
+            assert_eq!(addrs.len(), 3);
+            assert!(matches!(addrs[1].host, HostName::Tor(_)));
+            assert!(matches!(addrs[2].host, HostName::I2p(_)));
+            assert_eq!(failed, vec!["abc.onion:1".to_owned()]);
+        }
+
+        assert_eq!(NetAddr::<HostName>::parse_bootstrap(" ,\n"), (vec![], vec![]));
+    }
+
+    #[test]
+    fn unresolved() {
+        let addr = UnresolvedSocketAddr::from_str("[::1]:80").unwrap();
+        assert_eq!(addr.to_socket_addrs().unwrap().collect::<Vec<_>>(), vec![SocketAddr::from((
+            Ipv6Addr::LOCALHOST,
+            80
+        ))]);
+        #[cfg(feature = "tor")]
+        {
+            let addr = UnresolvedSocketAddr::from_str(
+                "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735",
+            )
+            .unwrap();
+            assert_eq!(addr.to_socket_addrs().unwrap_err().kind(), io::ErrorKind::Unsupported);
+            assert!(std::net::TcpStream::connect(&addr).is_err());
+        }
+        #[cfg(feature = "dns")]
+        {
+            let addr = UnresolvedSocketAddr::from_str("localhost:80").unwrap();
+            assert_eq!(addr.to_socket_addrs().unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        for (s, expected) in [("[0:0:0:0:0:0:0:1]:80", "[::1]:80"), ("1.2.3.4:80", "1.2.3.4:80")] {
+            let canonical = NetAddr::<HostName>::from_str(s).unwrap().to_canonical();
+            assert_eq!(canonical, expected);
+            assert_eq!(
+                NetAddr::<HostName>::from_str(&canonical).unwrap().to_canonical(),
+                canonical
+            );
+        }
+    }
+
+    #[test]
+    fn parse_ip() {
+        let addr = NetAddr::<IpAddr>::try_from("[::1]:80").unwrap();
+        assert_eq!(addr, NetAddr::from(SocketAddr::from((Ipv6Addr::LOCALHOST, 80))));
+        assert_eq!(addr.to_string(), "[::1]:80");
+
+        let addr = NetAddr::<HostName>::try_from("1.2.3.4:80").unwrap();
+        assert_eq!(addr, NetAddr::from(SocketAddr::from(([1, 2, 3, 4], 80))));
+        assert_eq!(addr.to_string(), "1.2.3.4:80");
+    }
+
+    #[test]
+    #[cfg(feature = "tor")]
+    fn parse_onion() {
+        let onion = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735";
+        let addr = NetAddr::<HostName>::try_from(onion).unwrap();
+        assert_eq!(addr.port, 9735);
+        assert!(addr.host.is_anonymous());
+        assert_eq!(addr.to_string(), onion);
+
+        assert!(matches!(
+            NetAddr::<HostName>::try_from("abc.onion:9735"),
+            Err(NetAddrError::InvalidHost(AddrParseError::Tor(_)))
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            NetAddr::<IpAddr>::try_from("1.2.3.4:65536"),
+            Err(NetAddrError::InvalidPort(_))
+        ));
+        assert!(matches!(NetAddr::<IpAddr>::try_from("1.2.3.4"), Err(NetAddrError::PortAbsent(_))));
+        assert!(matches!(NetAddr::<IpAddr>::try_from("[::1]"), Err(NetAddrError::PortAbsent(_))));
+        assert!(matches!(
+            NetAddr::<IpAddr>::try_from("::1:80"),
+            Err(NetAddrError::UnbracketedIpv6(_))
+        ));
+        assert!(matches!(
+            NetAddr::<IpAddr>::try_from("[::1:80"),
+            Err(NetAddrError::InvalidBrackets(_))
+        ));
+        assert!(matches!(
+            NetAddr::<IpAddr>::try_from("[::1]80"),
+            Err(NetAddrError::InvalidBrackets(_))
+        ));
+        assert!(matches!(
+            NetAddr::<IpAddr>::try_from("1.2.3:80"),
+            Err(NetAddrError::InvalidHost(AddrParseError::InvalidSocketAddr(_)))
+        ));
+
+        // All errors carrying the input truncate it
+        let long = "1".repeat(4096);
+        for s in [
+            format!("1.2.3.4:{long}"),
+            format!("1.2.3.4{long}"),
+            format!("[::1{long}"),
+            format!("::1:{long}"),
+        ] {
+            let err = NetAddr::<IpAddr>::try_from(s.as_str()).unwrap_err();
+            let (NetAddrError::InvalidPort(input)
+            | NetAddrError::PortAbsent(input)
+            | NetAddrError::InvalidBrackets(input)
+            | NetAddrError::UnbracketedIpv6(input)) = &err
+            else {
+                panic!("unexpected error {err}");
+            };
+            assert_eq!(input, &InputSnippet::new(&s));
+            assert!(input.is_truncated());
+            assert!(err.to_string().len() < 128, "{err}");
+        }
+    }
+
+    #[test]
+    fn parse_partial() {
+        let addr = PartialAddr::<IpAddr, 8080>::try_from("[::1]").unwrap();
+        assert_eq!(addr.port, None);
+        assert_eq!(NetAddr::from(addr.clone()).to_string(), "[::1]:8080");
+        assert_eq!(addr.to_string(), "[::1]");
+
+        let addr = PartialAddr::<IpAddr, 8080>::try_from("1.2.3.4:80").unwrap();
+        assert_eq!(NetAddr::from(addr).to_string(), "1.2.3.4:80");
     }
 }
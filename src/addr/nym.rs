@@ -19,13 +19,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::fmt::{self, Display, Formatter};
-use std::str::FromStr;
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NymAddr([u8; 32]);
 
+impl From<[u8; 32]> for NymAddr {
+    fn from(data: [u8; 32]) -> Self { Self(data) }
+}
+
 impl FromStr for NymAddr {
     type Err = ();
 
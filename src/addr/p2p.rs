@@ -22,11 +22,11 @@
 use std::borrow::Borrow;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::io;
-use std::net::{self, ToSocketAddrs};
+use std::net::{self, IpAddr, ToSocketAddrs};
 use std::str::FromStr;
 
 use super::{Addr, AddrParseError};
-use crate::addr::{Host, NetAddr, PartialAddr, ToSocketAddr};
+use crate::addr::{Host, HostName, NetAddr, NetAddrError, PartialAddr, ToSocketAddr};
 use crate::crypto::EcPk;
 
 #[derive(Debug, Display, Error, From)]
@@ -41,6 +41,10 @@ where
     #[display(inner)]
     Addr(AddrParseError),
 
+    #[from]
+    #[display(inner)]
+    NetAddr(NetAddrError),
+
     /// invalid peer key. Details: {0}
     Key(<Id as FromStr>::Err),
 
@@ -49,6 +53,55 @@ where
     InvalidFormat,
 }
 
+/// Errors of the binary encoding of peer addresses.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum PeerAddrCodecError {
+    /// peer address data end unexpectedly; at least {0} more bytes are required.
+    UnexpectedEnd(usize),
+
+    /// peer address data contain {0} unexpected trailing bytes.
+    TrailingData(usize),
+
+    /// unknown or unsupported peer host address type {0:#04x}.
+    UnknownAddrType(u8),
+
+    /// peer key can't be decoded from the provided data.
+    InvalidKey,
+
+    /// DNS name in peer address is not a valid UTF-8 string or contains
+    /// characters not allowed in DNS names.
+    InvalidDns,
+
+    /// length of {0} bytes exceeds 255 bytes limit of the binary encoding.
+    TooLong(usize),
+
+    /// peer host type can't be represented in the binary encoding.
+    UnsupportedHost,
+}
+
+/// Reads binary-encoded peer address data with bounds checking.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PeerAddrCodecError> {
+        if self.0.len() < len {
+            return Err(PeerAddrCodecError::UnexpectedEnd(len - self.0.len()));
+        }
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(data)
+    }
+
+    fn take_array<const LEN: usize>(&mut self) -> Result<[u8; LEN], PeerAddrCodecError> {
+        let mut array = [0u8; LEN];
+        array.copy_from_slice(self.take(LEN)?);
+        Ok(array)
+    }
+
+    fn take_u8(&mut self) -> Result<u8, PeerAddrCodecError> { Ok(self.take(1)?[0]) }
+}
+
 #[derive(Getters, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 #[getter(as_copy)]
 pub struct PeerAddr<Id: EcPk, A: Addr> {
@@ -127,6 +180,156 @@ impl<Id: EcPk, A: Addr> PeerAddr<Id, A> {
     }
 }
 
+impl HostName {
+    const ADDR_IPV4: u8 = 1;
+    const ADDR_IPV6: u8 = 2;
+    #[cfg(feature = "tor")]
+    const ADDR_TOR: u8 = 3;
+    #[cfg(feature = "i2p")]
+    const ADDR_I2P: u8 = 4;
+    #[cfg(feature = "i2p")]
+    const ADDR_I2P_EXTENDED: u8 = 5;
+    #[cfg(feature = "dns")]
+    const ADDR_DNS: u8 = 6;
+
+    /// Encodes host into the compact binary form used by [`PeerAddr::encode`]:
+    /// host address type byte (1 for IPv4, 2 for IPv6, 3 for Tor v3 onion
+    /// public key, 4 for I2P hash, 5 for extended I2P address and 6 for DNS
+    /// name), followed by the host address payload (DNS name is prefixed with
+    /// its length byte).
+    pub fn encode(&self) -> Result<Vec<u8>, PeerAddrCodecError> {
+        let mut data = Vec::with_capacity(1 + 16);
+        self.encode_into(&mut data)?;
+        Ok(data)
+    }
+
+    fn encode_into(&self, data: &mut Vec<u8>) -> Result<(), PeerAddrCodecError> {
+        match self {
+            HostName::Ip(IpAddr::V4(ip)) => {
+                data.push(Self::ADDR_IPV4);
+                data.extend_from_slice(&ip.octets());
+            }
+            HostName::Ip(IpAddr::V6(ip)) => {
+                data.push(Self::ADDR_IPV6);
+                data.extend_from_slice(&ip.octets());
+            }
+            #[cfg(feature = "tor")]
+            HostName::Tor(onion) => {
+                data.push(Self::ADDR_TOR);
+                data.extend_from_slice(onion.into_public_key().as_slice());
+            }
+            #[cfg(feature = "i2p")]
+            HostName::I2p(i2p) => {
+                data.push(match i2p.kind() {
+                    super::i2p::I2pAddrKind::Hash => Self::ADDR_I2P,
+                    super::i2p::I2pAddrKind::Extended => Self::ADDR_I2P_EXTENDED,
+                });
+                data.extend_from_slice(i2p.as_bytes());
+            }
+            #[cfg(feature = "dns")]
+            HostName::Dns(dns) => {
+                let len =
+                    u8::try_from(dns.len()).map_err(|_| PeerAddrCodecError::TooLong(dns.len()))?;
+                data.push(Self::ADDR_DNS);
+                data.push(len);
+                data.extend_from_slice(dns.as_bytes());
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(PeerAddrCodecError::UnsupportedHost),
+        }
+        Ok(())
+    }
+
+    /// Decodes host from the binary form produced by [`HostName::encode`].
+    /// The data must contain exactly one encoded host, without trailing bytes.
+    pub fn decode(data: &[u8]) -> Result<Self, PeerAddrCodecError> {
+        let (host, rest) = Self::decode_prefix(data)?;
+        if !rest.is_empty() {
+            return Err(PeerAddrCodecError::TrailingData(rest.len()));
+        }
+        Ok(host)
+    }
+
+    /// Decodes host from the beginning of the binary data, returning the host
+    /// and the remaining unconsumed data, which allows to decode hosts from a
+    /// larger message.
+    pub fn decode_prefix(data: &[u8]) -> Result<(Self, &[u8]), PeerAddrCodecError> {
+        let mut cursor = Cursor(data);
+        let host = Self::decode_from(&mut cursor)?;
+        Ok((host, cursor.0))
+    }
+
+    fn decode_from(cursor: &mut Cursor) -> Result<Self, PeerAddrCodecError> {
+        Ok(match cursor.take_u8()? {
+            Self::ADDR_IPV4 => HostName::from(cursor.take_array::<4>()?),
+            Self::ADDR_IPV6 => HostName::from(cursor.take_array::<16>()?),
+            #[cfg(feature = "tor")]
+            Self::ADDR_TOR => {
+                HostName::Tor(super::tor::OnionAddrV3::from(cursor.take_array::<32>()?))
+            }
+            #[cfg(feature = "i2p")]
+            Self::ADDR_I2P => HostName::I2p(super::i2p::I2pAddr::from(
+                cursor.take_array::<{ super::i2p::I2P_HASH_LEN }>()?,
+            )),
+            #[cfg(feature = "i2p")]
+            Self::ADDR_I2P_EXTENDED => HostName::I2p(super::i2p::I2pAddr::from(
+                cursor.take_array::<{ super::i2p::I2P_EXTENDED_LEN }>()?,
+            )),
+            #[cfg(feature = "dns")]
+            Self::ADDR_DNS => {
+                let len = cursor.take_u8()?;
+                let dns = std::str::from_utf8(cursor.take(len as usize)?)
+                    .map_err(|_| PeerAddrCodecError::InvalidDns)?;
+                super::host::check_dns_name(dns).map_err(|_| PeerAddrCodecError::InvalidDns)?;
+                HostName::Dns(dns.to_owned())
+            }
+            unknown => return Err(PeerAddrCodecError::UnknownAddrType(unknown)),
+        })
+    }
+}
+
+impl<Id: EcPk> PeerAddr<Id, NetAddr<HostName>>
+where
+    Id: AsRef<[u8]>,
+    for<'a> Id: TryFrom<&'a [u8]>,
+{
+    /// Encodes peer address into a compact binary form for the use in gossip
+    /// messages, consisting of:
+    /// - key length byte and the key bytes;
+    /// - host in the form produced by [`HostName::encode`];
+    /// - big-endian 2-byte port number.
+    pub fn encode(&self) -> Result<Vec<u8>, PeerAddrCodecError> {
+        let key = self.id.as_ref();
+        let key_len =
+            u8::try_from(key.len()).map_err(|_| PeerAddrCodecError::TooLong(key.len()))?;
+        let mut data = Vec::with_capacity(1 + key.len() + 1 + 16 + 2);
+        data.push(key_len);
+        data.extend_from_slice(key);
+        self.addr.host.encode_into(&mut data)?;
+        data.extend_from_slice(&self.addr.port.to_be_bytes());
+        Ok(data)
+    }
+
+    /// Decodes peer address from the binary form produced by
+    /// [`PeerAddr::encode`]. The data must contain exactly one encoded
+    /// address, without trailing bytes.
+    pub fn decode(data: &[u8]) -> Result<Self, PeerAddrCodecError> {
+        let mut cursor = Cursor(data);
+        let key_len = cursor.take_u8()?;
+        let id = Id::try_from(cursor.take(key_len as usize)?)
+            .map_err(|_| PeerAddrCodecError::InvalidKey)?;
+        let host = HostName::decode_from(&mut cursor)?;
+        let port = u16::from_be_bytes(cursor.take_array()?);
+        if !cursor.0.is_empty() {
+            return Err(PeerAddrCodecError::TrailingData(cursor.0.len()));
+        }
+        Ok(PeerAddr {
+            id,
+            addr: NetAddr { host, port },
+        })
+    }
+}
+
 impl<Id: EcPk, A> ToSocketAddrs for PeerAddr<Id, A>
 where A: Addr + ToSocketAddrs
 {
@@ -134,3 +337,109 @@ where A: Addr + ToSocketAddrs
 
     fn to_socket_addrs(&self) -> io::Result<A::Iter> { self.addr.to_socket_addrs() }
 }
+
+#[cfg(test)]
+#[cfg(feature = "ed25519")]
+mod test {
+    use super::*;
+    use crate::crypto::ed25519::PublicKey;
+
+    type Peer = PeerAddr<PublicKey, NetAddr<HostName>>;
+
+    fn peer(addr: &str) -> Peer {
+        PeerAddr::new(PublicKey::from([0xA5; 32]), NetAddr::from_str(addr).unwrap())
+    }
+
+    #[test]
+    fn codec_round_trip() {
+        let mut addrs = vec!["1.2.3.4:9735", "[2001:db8::1]:80"];
+        if cfg!(feature = "tor") {
+            addrs.push("2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:9735");
+        }
+        if cfg!(feature = "i2p") {
+            addrs.push("udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p:1");
+            addrs.push("aaaaaqqbaibqibiga4eascqlbqgq4dyqcejbgfavcylrqgi2dmob2hq7.b32.i2p:2");
+        }
+        if cfg!(feature = "dns") {
+            addrs.push("example.com:443");
+        }
+        for addr in addrs {
+            let peer = peer(addr);
+            let data = peer.encode().unwrap();
+            assert_eq!(Peer::decode(&data), Ok(peer), "{addr}");
+        }
+    }
+
+    #[test]
+    fn codec_layout() {
+        let data = peer("1.2.3.4:9735").encode().unwrap();
+        let mut expected = vec![32u8];
+        expected.extend([0xA5; 32]);
+        expected.extend([1, 1, 2, 3, 4, 0x26, 0x07]);
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn host_decode_prefix() {
+        let first = HostName::from([1, 2, 3, 4]);
+        let second = HostName::Ip(std::net::Ipv6Addr::LOCALHOST.into());
+        let mut data = first.encode().unwrap();
+        data.extend(second.encode().unwrap());
+        data.extend_from_slice(b"tail");
+
+        let (host, rest) = HostName::decode_prefix(&data).unwrap();
+        assert_eq!(host, first);
+        let (host, rest) = HostName::decode_prefix(rest).unwrap();
+        assert_eq!(host, second);
+        assert_eq!(rest, b"tail");
+        assert_eq!(HostName::decode_prefix(rest), Err(PeerAddrCodecError::UnknownAddrType(b't')));
+
+        assert_eq!(HostName::decode(&first.encode().unwrap()).unwrap(), first);
+        assert_eq!(HostName::decode(&data), Err(PeerAddrCodecError::TrailingData(21)));
+        assert_eq!(HostName::decode_prefix(&data[..3]), Err(PeerAddrCodecError::UnexpectedEnd(2)));
+
+        #[cfg(feature = "tor")]
+        {
+            let onion = HostName::Tor(super::super::tor::OnionAddrV3::from([7u8; 32]));
+            let mut data = onion.encode().unwrap();
+            data.extend(first.encode().unwrap());
+            let (host, rest) = HostName::decode_prefix(&data).unwrap();
+            assert_eq!(host, onion);
+            assert_eq!(HostName::decode(rest).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn codec_truncated() {
+        let data = peer("[2001:db8::1]:80").encode().unwrap();
+        for len in 0..data.len() {
+            assert!(
+                matches!(Peer::decode(&data[..len]), Err(PeerAddrCodecError::UnexpectedEnd(_))),
+                "{len}"
+            );
+        }
+        assert_eq!(Peer::decode(&data[..40]), Err(PeerAddrCodecError::UnexpectedEnd(10)));
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert_eq!(Peer::decode(&trailing), Err(PeerAddrCodecError::TrailingData(1)));
+
+        let mut unknown = data.clone();
+        unknown[33] = 0xFE;
+        assert_eq!(Peer::decode(&unknown), Err(PeerAddrCodecError::UnknownAddrType(0xFE)));
+
+        let mut short_key = data;
+        short_key[0] = 31;
+        assert_eq!(Peer::decode(&short_key), Err(PeerAddrCodecError::InvalidKey));
+    }
+
+    #[test]
+    #[cfg(feature = "dns")]
+    fn codec_invalid_dns() {
+        for name in [&b"example.com\n"[..], b"", b"exa mple.com", b"\xFF.com"] {
+            let mut data = vec![HostName::ADDR_DNS, name.len() as u8];
+            data.extend_from_slice(name);
+            assert_eq!(HostName::decode(&data), Err(PeerAddrCodecError::InvalidDns), "{name:?}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{hash_set, HashSet};
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use super::{Host, HostName, NetAddr};
+
+/// Address types which may represent the same address by different values
+/// (like DNS names differing only in case), and can be converted into the
+/// canonical one.
+pub trait Canonical {
+    /// Converts the address into the canonical form, in which equal addresses
+    /// are represented by equal values.
+    fn into_canonical(self) -> Self;
+}
+
+impl Canonical for IpAddr {
+    fn into_canonical(self) -> Self { self }
+}
+
+impl Canonical for SocketAddr {
+    fn into_canonical(self) -> Self { self }
+}
+
+#[cfg(feature = "tor")]
+impl Canonical for super::tor::OnionAddrV3 {
+    fn into_canonical(self) -> Self { self }
+}
+
+#[cfg(feature = "i2p")]
+impl Canonical for super::i2p::I2pAddr {
+    fn into_canonical(self) -> Self { self }
+}
+
+#[cfg(feature = "dns")]
+impl Canonical for super::InetHost {
+    fn into_canonical(self) -> Self {
+        match self {
+            super::InetHost::Dns(mut dns) => {
+                dns.make_ascii_lowercase();
+                super::InetHost::Dns(dns)
+            }
+            ip => ip,
+        }
+    }
+}
+
+impl Canonical for HostName {
+    fn into_canonical(self) -> Self {
+        match self {
+            #[cfg(feature = "dns")]
+            HostName::Dns(mut dns) => {
+                dns.make_ascii_lowercase();
+                HostName::Dns(dns)
+            }
+            host => host,
+        }
+    }
+}
+
+impl<H: Host + Canonical> Canonical for NetAddr<H> {
+    fn into_canonical(self) -> Self {
+        NetAddr {
+            host: self.host.into_canonical(),
+            port: self.port,
+        }
+    }
+}
+
+/// Set of addresses deduplicating them by their canonical form.
+///
+/// Since addresses are stored parsed and converted with
+/// [`Canonical::into_canonical`], different string renderings of the same
+/// address (like uppercase base32, I2P address with or without a suffix or
+/// DNS names differing in case) result in a single entry.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AddrSet<A: Hash + Eq = HostName>(HashSet<A>);
+
+impl<A: Hash + Eq> Default for AddrSet<A> {
+    fn default() -> Self { Self(HashSet::new()) }
+}
+
+impl<A: Hash + Eq + Canonical> AddrSet<A> {
+    /// Constructs an empty set.
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds the address to the set, returning whether it wasn't present.
+    pub fn insert(&mut self, addr: A) -> bool { self.0.insert(addr.into_canonical()) }
+
+    /// Parses the address from a string and adds it to the set, returning
+    /// whether it wasn't present.
+    pub fn insert_str(&mut self, s: &str) -> Result<bool, A::Err>
+    where A: FromStr {
+        A::from_str(s).map(|addr| self.insert(addr))
+    }
+
+    /// Checks whether the set contains the address.
+    pub fn contains(&self, addr: &A) -> bool
+    where A: Clone {
+        self.0.contains(&addr.clone().into_canonical())
+    }
+
+    /// Removes the address from the set, returning whether it was present.
+    pub fn remove(&mut self, addr: &A) -> bool
+    where A: Clone {
+        self.0.remove(&addr.clone().into_canonical())
+    }
+
+    /// Returns number of unique addresses in the set.
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Detects whether the set contains no addresses.
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Iterates over the addresses in an arbitrary order.
+    pub fn iter(&self) -> hash_set::Iter<'_, A> { self.0.iter() }
+}
+
+impl<A: Hash + Eq + Canonical> FromIterator<A> for AddrSet<A> {
+    fn from_iter<T: IntoIterator<Item = A>>(iter: T) -> Self {
+        Self(iter.into_iter().map(A::into_canonical).collect())
+    }
+}
+
+impl<A: Hash + Eq + Canonical> Extend<A> for AddrSet<A> {
+    fn extend<T: IntoIterator<Item = A>>(&mut self, iter: T) {
+        self.0.extend(iter.into_iter().map(A::into_canonical))
+    }
+}
+
+impl<A: Hash + Eq> IntoIterator for AddrSet<A> {
+    type Item = A;
+    type IntoIter = hash_set::IntoIter<A>;
+
+    fn into_iter(self) -> Self::IntoIter { self.0.into_iter() }
+}
+
+impl<'a, A: Hash + Eq> IntoIterator for &'a AddrSet<A> {
+    type Item = &'a A;
+    type IntoIter = hash_set::Iter<'a, A>;
+
+    fn into_iter(self) -> Self::IntoIter { self.0.iter() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "i2p")]
+    fn i2p_formattings() {
+        use crate::addr::i2p::I2pAddr;
+
+        const I2P: &str = "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p";
+        let mut set = AddrSet::<I2pAddr>::new();
+        assert!(set.insert_str(I2P).unwrap());
+        assert!(!set.insert_str(&I2P.to_uppercase().replace(".B32.I2P", ".b32.i2p")).unwrap());
+        assert!(!set.insert_str(I2P.trim_end_matches(".b32.i2p")).unwrap());
+        assert_eq!(set.len(), 1);
+        let addr = I2pAddr::from_str(I2P).unwrap();
+        assert!(set.contains(&addr));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![&addr]);
+    }
+
+    #[test]
+    fn hosts() {
+        let mut set = AddrSet::<HostName>::new();
+        assert!(set.insert_str("::1").unwrap());
+        assert!(!set.insert_str("0:0:0:0:0:0:0:1").unwrap());
+        assert!(set.insert_str("127.0.0.1").unwrap());
+        assert_eq!(set.len(), 2);
+        assert!(set.remove(&HostName::from([127, 0, 0, 1])));
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), vec![HostName::from_str("::1").unwrap()]);
+    }
+
+    #[test]
+    #[cfg(feature = "dns")]
+    fn dns_case() {
+        let mut set = AddrSet::<NetAddr<HostName>>::new();
+        assert!(set.insert_str("Example.COM:80").unwrap());
+        assert!(!set.insert_str("example.com:80").unwrap());
+        assert!(set.insert_str("example.com:443").unwrap());
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&NetAddr::from_str("EXAMPLE.com:443").unwrap()));
+        assert!(set.iter().all(|addr| addr.to_string().starts_with("example.com:")));
+
+        let set = ["Example.COM", "example.com", "EXAMPLE.COM"]
+            .into_iter()
+            .map(|s| HostName::from_str(s).unwrap())
+            .collect::<AddrSet>();
+        assert_eq!(set.len(), 1);
+    }
+}
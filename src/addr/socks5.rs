@@ -0,0 +1,669 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal SOCKS5 client (RFC 1928) for connecting to the remote hosts through
+//! a proxy without authentication, like Tor or I2P SOCKS ports.
+//!
+//! [`Socks5Dialer`] is blocking and accepts any [`Read`] + [`Write`] stream to
+//! the proxy; with `tokio` feature, [`connect_via_socks5_async`] performs the
+//! same handshake over tokio TCP stream.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+
+use super::{HostName, NetAddr, UnresolvedSocketAddr};
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_NONE: u8 = 0x00;
+#[cfg(feature = "tokio")]
+const AUTH_PASSWORD: u8 = 0x02;
+#[cfg(feature = "tokio")]
+const AUTH_PASSWORD_VERSION: u8 = 0x01;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Errors happening during the SOCKS5 connection.
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SocksError {
+    /// I/O error communicating with the proxy: {0}
+    #[from]
+    Io(io::Error),
+
+    /// proxy has responded with invalid SOCKS version {0}.
+    InvalidVersion(u8),
+
+    /// proxy doesn't support connections without authentication.
+    NoAcceptableAuth,
+
+    /// host name of {0} bytes is too long to be sent to the proxy.
+    HostTooLong(usize),
+
+    /// user name or password of {0} bytes is too long to be sent to the proxy.
+    CredentialsTooLong(usize),
+
+    /// proxy has responded with invalid authentication sub-negotiation version
+    /// {0}.
+    InvalidAuthVersion(u8),
+
+    /// proxy has rejected the user name and password.
+    AuthFailed,
+
+    /// proxy has responded with unknown address type {0}.
+    UnknownAddrType(u8),
+
+    /// general SOCKS server failure.
+    GeneralFailure,
+
+    /// connection is not allowed by the proxy ruleset.
+    NotAllowed,
+
+    /// network is unreachable.
+    NetworkUnreachable,
+
+    /// host is unreachable.
+    HostUnreachable,
+
+    /// connection refused by the remote host.
+    ConnectionRefused,
+
+    /// TTL expired.
+    TtlExpired,
+
+    /// proxy doesn't support CONNECT command.
+    CommandUnsupported,
+
+    /// proxy doesn't support the address type used for the target host.
+    AddressTypeUnsupported,
+
+    /// proxy has responded with unknown reply code {0:#04x}.
+    UnknownReply(u8),
+
+    /// connection to {0} must go through the proxy under the strict privacy
+    /// policy.
+    ProxyRequired(HostName),
+}
+
+impl SocksError {
+    fn with_reply(code: u8) -> Self {
+        match code {
+            0x01 => SocksError::GeneralFailure,
+            0x02 => SocksError::NotAllowed,
+            0x03 => SocksError::NetworkUnreachable,
+            0x04 => SocksError::HostUnreachable,
+            0x05 => SocksError::ConnectionRefused,
+            0x06 => SocksError::TtlExpired,
+            0x07 => SocksError::CommandUnsupported,
+            0x08 => SocksError::AddressTypeUnsupported,
+            code => SocksError::UnknownReply(code),
+        }
+    }
+}
+
+/// Form in which the target host is sent to the proxy.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum TargetForm {
+    /// IP addresses are sent as IPv4 or IPv6 address types; other hosts as a
+    /// domain name.
+    Native,
+    /// IP addresses are sent in their textual form as a domain name.
+    IpAsDomain,
+}
+
+/// Policy for connecting to the hosts without a proxy.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum PrivacyPolicy {
+    /// DNS names are resolved by the local OS when connected to directly.
+    #[default]
+    Permissive,
+
+    /// Only IP addresses may be connected to directly; any attempt to resolve
+    /// or directly connect to a non-IP host is an error, such that a code path
+    /// which forgot to use the proxy can't deanonymize the node.
+    Strict,
+}
+
+impl PrivacyPolicy {
+    /// Checks whether `host` is allowed to be connected to without a proxy.
+    pub fn check_direct(self, host: &HostName) -> Result<(), SocksError> {
+        if self == PrivacyPolicy::Strict && !matches!(host, HostName::Ip(_)) {
+            return Err(SocksError::ProxyRequired(host.clone()));
+        }
+        Ok(())
+    }
+}
+
+/// SOCKS5 dialer connecting to the remote hosts through a proxy.
+///
+/// Non-IP hosts (DNS names, Tor and I2P addresses) are always sent to the
+/// proxy as domain names, such that they are resolved by the proxy and never
+/// by the local OS.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Socks5Dialer {
+    retry_alternate_atyp: bool,
+    privacy: PrivacyPolicy,
+}
+
+impl Socks5Dialer {
+    /// Constructs dialer with the default settings.
+    pub fn new() -> Self { Self::default() }
+
+    /// Sets whether an IP target rejected by the proxy with
+    /// [`SocksError::AddressTypeUnsupported`] should be retried over a new
+    /// proxy connection, sending the IP address in its textual form as a domain
+    /// name.
+    pub fn with_alternate_atyp(mut self, retry: bool) -> Self {
+        self.retry_alternate_atyp = retry;
+        self
+    }
+
+    /// Sets the policy applied to the connections made without a proxy with
+    /// [`Socks5Dialer::dial_direct`].
+    pub fn with_privacy_policy(mut self, policy: PrivacyPolicy) -> Self {
+        self.privacy = policy;
+        self
+    }
+
+    /// Returns the policy applied to the connections made without a proxy.
+    pub fn privacy_policy(&self) -> PrivacyPolicy { self.privacy }
+
+    /// Connects to the `target` through the SOCKS5 proxy listening at `proxy`.
+    pub fn dial(
+        &self,
+        proxy: impl ToSocketAddrs,
+        target: &NetAddr<HostName>,
+    ) -> Result<TcpStream, SocksError> {
+        let proxy = proxy.to_socket_addrs()?.collect::<Vec<_>>();
+        self.dial_with(|| TcpStream::connect(&proxy[..]), target)
+    }
+
+    /// Connects to the `target` directly, without a proxy.
+    ///
+    /// Under [`PrivacyPolicy::Strict`] fails with [`SocksError::ProxyRequired`]
+    /// for any non-IP host before doing any name resolution. Under
+    /// [`PrivacyPolicy::Permissive`] DNS names are resolved by the local OS,
+    /// while overlay network addresses still can't be connected to.
+    pub fn dial_direct(&self, target: &NetAddr<HostName>) -> Result<TcpStream, SocksError> {
+        self.privacy.check_direct(&target.host)?;
+        let stream = match &target.host {
+            #[cfg(feature = "dns")]
+            HostName::Dns(dns) => TcpStream::connect((dns.as_str(), target.port))?,
+            host => TcpStream::connect(UnresolvedSocketAddr::new(host.clone(), target.port))?,
+        };
+        Ok(stream)
+    }
+
+    /// Connects to the `target` through a SOCKS5 proxy using new streams to the
+    /// proxy provided by the `connect` function, which is called again if the
+    /// request has to be retried.
+    pub fn dial_with<S: Read + Write>(
+        &self,
+        mut connect: impl FnMut() -> io::Result<S>,
+        target: &NetAddr<HostName>,
+    ) -> Result<S, SocksError> {
+        let mut stream = connect()?;
+        match handshake(&mut stream, target, TargetForm::Native) {
+            Ok(()) => Ok(stream),
+            Err(SocksError::AddressTypeUnsupported)
+                if self.retry_alternate_atyp && matches!(target.host, HostName::Ip(_)) =>
+            {
+                // The proxy closes the connection after a failure reply
+                let mut stream = connect()?;
+                handshake(&mut stream, target, TargetForm::IpAsDomain)?;
+                Ok(stream)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Encodes the greeting offering the single authentication `method`.
+fn encode_greeting(method: u8) -> [u8; 3] { [SOCKS_VERSION, 1, method] }
+
+/// Checks the proxy reply to the greeting, which must select the offered
+/// authentication `method`.
+fn check_method_reply(reply: [u8; 2], method: u8) -> Result<(), SocksError> {
+    match reply {
+        [SOCKS_VERSION, m] if m == method => Ok(()),
+        [SOCKS_VERSION, _] => Err(SocksError::NoAcceptableAuth),
+        [version, _] => Err(SocksError::InvalidVersion(version)),
+    }
+}
+
+/// Encodes the user name and password sub-negotiation request (RFC 1929).
+#[cfg(feature = "tokio")]
+fn encode_credentials(username: &str, password: &str) -> Result<Vec<u8>, SocksError> {
+    let mut data = vec![AUTH_PASSWORD_VERSION];
+    for field in [username, password] {
+        let len =
+            u8::try_from(field.len()).map_err(|_| SocksError::CredentialsTooLong(field.len()))?;
+        data.push(len);
+        data.extend_from_slice(field.as_bytes());
+    }
+    Ok(data)
+}
+
+/// Checks the proxy reply to the user name and password sub-negotiation.
+#[cfg(feature = "tokio")]
+fn check_auth_reply(reply: [u8; 2]) -> Result<(), SocksError> {
+    match reply {
+        [AUTH_PASSWORD_VERSION, 0x00] => Ok(()),
+        [AUTH_PASSWORD_VERSION, _] => Err(SocksError::AuthFailed),
+        [version, _] => Err(SocksError::InvalidAuthVersion(version)),
+    }
+}
+
+fn encode_target(target: &NetAddr<HostName>, form: TargetForm) -> Result<Vec<u8>, SocksError> {
+    let mut data = vec![SOCKS_VERSION, CMD_CONNECT, 0x00];
+    let domain = match (&target.host, form) {
+        (HostName::Ip(IpAddr::V4(ip)), TargetForm::Native) => {
+            data.push(ATYP_IPV4);
+            data.extend_from_slice(&ip.octets());
+            None
+        }
+        (HostName::Ip(IpAddr::V6(ip)), TargetForm::Native) => {
+            data.push(ATYP_IPV6);
+            data.extend_from_slice(&ip.octets());
+            None
+        }
+        (host, _) => Some(host.to_string()),
+    };
+    if let Some(domain) = domain {
+        let len = u8::try_from(domain.len()).map_err(|_| SocksError::HostTooLong(domain.len()))?;
+        data.push(ATYP_DOMAIN);
+        data.push(len);
+        data.extend_from_slice(domain.as_bytes());
+    }
+    data.extend_from_slice(&target.port.to_be_bytes());
+    Ok(data)
+}
+
+/// Authentication method used with the SOCKS5 proxy by
+/// [`connect_via_socks5_async`].
+#[cfg(feature = "tokio")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Socks5Auth {
+    /// No authentication.
+    #[default]
+    None,
+
+    /// User name and password authentication (RFC 1929). Tor uses the
+    /// credentials for stream isolation.
+    Password { username: String, password: String },
+}
+
+/// Connects to the `target` through the SOCKS5 proxy listening at `proxy`,
+/// performing the same handshake as [`Socks5Dialer::dial`] asynchronously.
+/// Non-IP hosts are sent to the proxy as domain names, such that they are
+/// never resolved locally.
+///
+/// # Errors
+///
+/// I/O errors are returned as is; protocol errors and failure replies of the
+/// proxy are returned as [`io::ErrorKind::Other`] errors wrapping
+/// [`SocksError`].
+#[cfg(feature = "tokio")]
+pub async fn connect_via_socks5_async(
+    proxy: impl tokio::net::ToSocketAddrs,
+    target: &NetAddr<HostName>,
+    auth: Socks5Auth,
+) -> io::Result<tokio::net::TcpStream> {
+    let mut stream = tokio::net::TcpStream::connect(proxy).await?;
+    match handshake_async(&mut stream, target, &auth).await {
+        Ok(()) => Ok(stream),
+        Err(SocksError::Io(err)) => Err(err),
+        Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn handshake_async(
+    stream: &mut tokio::net::TcpStream,
+    target: &NetAddr<HostName>,
+    auth: &Socks5Auth,
+) -> Result<(), SocksError> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let request = encode_target(target, TargetForm::Native)?;
+
+    let method = match auth {
+        Socks5Auth::None => AUTH_NONE,
+        Socks5Auth::Password { .. } => AUTH_PASSWORD,
+    };
+    stream.write_all(&encode_greeting(method)).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    check_method_reply(reply, method)?;
+
+    if let Socks5Auth::Password { username, password } = auth {
+        stream.write_all(&encode_credentials(username, password)?).await?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await?;
+        check_auth_reply(reply)?;
+    }
+
+    stream.write_all(&request).await?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    // Skip the address the proxy has bound to
+    let len = match bound_addr_len(reply)? {
+        Some(len) => len,
+        None => stream.read_u8().await? as usize,
+    };
+    let mut bound = vec![0u8; len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+fn handshake(
+    stream: &mut (impl Read + Write),
+    target: &NetAddr<HostName>,
+    form: TargetForm,
+) -> Result<(), SocksError> {
+    let request = encode_target(target, form)?;
+
+    stream.write_all(&encode_greeting(AUTH_NONE))?;
+    stream.flush()?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply)?;
+    check_method_reply(reply, AUTH_NONE)?;
+
+    stream.write_all(&request)?;
+    stream.flush()?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply)?;
+    // Skip the address the proxy has bound to
+    let len = match bound_addr_len(reply)? {
+        Some(len) => len,
+        None => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+    };
+    let mut bound = vec![0u8; len + 2];
+    stream.read_exact(&mut bound)?;
+    Ok(())
+}
+
+/// Checks the fixed part of the proxy reply to the CONNECT request, returning
+/// length of the bound address which follows it, or `None` for a domain name,
+/// whose length is given by the next byte.
+fn bound_addr_len(reply: [u8; 4]) -> Result<Option<usize>, SocksError> {
+    let [version, code, _, atyp] = reply;
+    if version != SOCKS_VERSION {
+        return Err(SocksError::InvalidVersion(version));
+    }
+    if code != 0x00 {
+        return Err(SocksError::with_reply(code));
+    }
+    match atyp {
+        ATYP_IPV4 => Ok(Some(4)),
+        ATYP_IPV6 => Ok(Some(16)),
+        ATYP_DOMAIN => Ok(None),
+        atyp => Err(SocksError::UnknownAddrType(atyp)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    /// Proxy stream replaying the predefined replies and recording requests.
+    #[derive(Debug)]
+    struct MockProxy {
+        replies: Cursor<Vec<u8>>,
+        requests: Vec<u8>,
+    }
+
+    impl MockProxy {
+        fn with_reply(code: u8) -> Self {
+            let mut replies = vec![SOCKS_VERSION, AUTH_NONE, SOCKS_VERSION, code, 0, ATYP_IPV4];
+            replies.extend_from_slice(&[127, 0, 0, 1, 0x23, 0x28]);
+            MockProxy {
+                replies: Cursor::new(replies),
+                requests: vec![],
+            }
+        }
+    }
+
+    impl Read for MockProxy {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.replies.read(buf) }
+    }
+
+    impl Write for MockProxy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.requests.write(buf) }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    fn target() -> NetAddr<HostName> {
+        NetAddr {
+            host: HostName::Ip(Ipv4Addr::new(1, 2, 3, 4).into()),
+            port: 8333,
+        }
+    }
+
+    #[test]
+    fn connect() {
+        let stream = Socks5Dialer::new().dial_with(|| Ok(MockProxy::with_reply(0)), &target());
+        assert_eq!(stream.unwrap().requests, vec![
+            5, 1, 0, 5, 1, 0, ATYP_IPV4, 1, 2, 3, 4, 0x20, 0x8D
+        ]);
+    }
+
+    #[test]
+    fn domain_bound_addr() {
+        // Proxy replies with a domain name as the bound address, followed by the
+        // data sent by the remote host, which must not be consumed
+        let mut replies = vec![SOCKS_VERSION, AUTH_NONE, SOCKS_VERSION, 0, 0, ATYP_DOMAIN, 9];
+        replies.extend_from_slice(b"localhost");
+        replies.extend_from_slice(&[0x23, 0x28]);
+        replies.extend_from_slice(b"data");
+        let proxy = || {
+            Ok(MockProxy {
+                replies: Cursor::new(replies.clone()),
+                requests: vec![],
+            })
+        };
+        let mut stream = Socks5Dialer::new().dial_with(proxy, &target()).unwrap();
+        let mut data = vec![];
+        stream.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"data");
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn mock_async_proxy(
+        method: u8,
+        auth_reply: Option<[u8; 2]>,
+        reply: &'static [u8],
+    ) -> (std::net::SocketAddr, tokio::task::JoinHandle<Vec<u8>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut requests = vec![0u8; 3];
+            stream.read_exact(&mut requests).await.unwrap();
+            stream.write_all(&[SOCKS_VERSION, method]).await.unwrap();
+            if let Some(auth_reply) = auth_reply {
+                let mut auth = vec![0u8; 2];
+                stream.read_exact(&mut auth).await.unwrap();
+                let mut rest = vec![0u8; auth[1] as usize + 1];
+                stream.read_exact(&mut rest).await.unwrap();
+                let mut password = vec![0u8; rest[rest.len() - 1] as usize];
+                stream.read_exact(&mut password).await.unwrap();
+                requests.extend(auth.into_iter().chain(rest).chain(password));
+                stream.write_all(&auth_reply).await.unwrap();
+                if auth_reply != [AUTH_PASSWORD_VERSION, 0] {
+                    return requests;
+                }
+            }
+            let mut request = vec![0u8; 10];
+            stream.read_exact(&mut request).await.unwrap();
+            requests.extend(request);
+            // Reply is written in parts to check they are awaited correctly
+            for part in reply.chunks(3) {
+                stream.write_all(part).await.unwrap();
+                stream.flush().await.unwrap();
+                tokio::task::yield_now().await;
+            }
+            requests
+        });
+        (addr, proxy)
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn connect_async() {
+        use tokio::io::AsyncReadExt;
+
+        // Domain name as the bound address, followed by the data sent by the
+        // remote host, which must not be consumed
+        const REPLY: &[u8] = b"\x05\x00\x00\x03\x09localhost\x23\x28data";
+        let (addr, proxy) = mock_async_proxy(AUTH_NONE, None, REPLY).await;
+        let mut stream = connect_via_socks5_async(addr, &target(), Socks5Auth::None).await.unwrap();
+        let mut data = vec![];
+        stream.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"data");
+        assert_eq!(proxy.await.unwrap(), vec![5, 1, 0, 5, 1, 0, ATYP_IPV4, 1, 2, 3, 4, 0x20, 0x8D]);
+
+        let auth = Socks5Auth::Password {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        const IPV4_REPLY: &[u8] = &[5, 0, 0, ATYP_IPV4, 127, 0, 0, 1, 0x23, 0x28];
+        let (addr, proxy) = mock_async_proxy(AUTH_PASSWORD, Some([1, 0]), IPV4_REPLY).await;
+        connect_via_socks5_async(addr, &target(), auth.clone()).await.unwrap();
+        let requests = proxy.await.unwrap();
+        assert_eq!(&requests[..14], b"\x05\x01\x02\x01\x04user\x04pass");
+
+        let (addr, _) = mock_async_proxy(AUTH_PASSWORD, Some([1, 1]), IPV4_REPLY).await;
+        let err = connect_via_socks5_async(addr, &target(), auth.clone()).await.unwrap_err();
+        assert_eq!(err.to_string(), SocksError::AuthFailed.to_string());
+
+        // Sub-negotiation reply with SOCKS version instead of RFC 1929 one
+        let (addr, _) = mock_async_proxy(AUTH_PASSWORD, Some([5, 0]), IPV4_REPLY).await;
+        let err = connect_via_socks5_async(addr, &target(), auth).await.unwrap_err();
+        assert_eq!(err.to_string(), SocksError::InvalidAuthVersion(5).to_string());
+
+        let (addr, _) = mock_async_proxy(0xFF, None, IPV4_REPLY).await;
+        let err = connect_via_socks5_async(addr, &target(), Socks5Auth::None).await.unwrap_err();
+        assert_eq!(err.to_string(), SocksError::NoAcceptableAuth.to_string());
+
+        const REFUSED: &[u8] = &[5, 5, 0, ATYP_IPV4, 127, 0, 0, 1, 0x23, 0x28];
+        let (addr, _) = mock_async_proxy(AUTH_NONE, None, REFUSED).await;
+        let err = connect_via_socks5_async(addr, &target(), Socks5Auth::None).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.to_string(), SocksError::ConnectionRefused.to_string());
+    }
+
+    #[test]
+    fn atyp_unsupported() {
+        let mut attempts = 0;
+        let err = Socks5Dialer::new()
+            .dial_with(
+                || {
+                    attempts += 1;
+                    Ok(MockProxy::with_reply(0x08))
+                },
+                &target(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, SocksError::AddressTypeUnsupported));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn atyp_retry() {
+        let mut replies = vec![0x08, 0x00].into_iter();
+        let stream = Socks5Dialer::new()
+            .with_alternate_atyp(true)
+            .dial_with(|| Ok(MockProxy::with_reply(replies.next().unwrap())), &target())
+            .unwrap();
+        let mut expected = vec![5, 1, 0, 5, 1, 0, ATYP_DOMAIN, 7];
+        expected.extend_from_slice(b"1.2.3.4");
+        expected.extend_from_slice(&[0x20, 0x8D]);
+        assert_eq!(stream.requests, expected);
+        assert_eq!(replies.next(), None);
+
+        // Retries are not repeated
+        let mut replies = vec![0x08, 0x08].into_iter();
+        let err = Socks5Dialer::new()
+            .with_alternate_atyp(true)
+            .dial_with(|| Ok(MockProxy::with_reply(replies.next().unwrap())), &target())
+            .unwrap_err();
+        assert!(matches!(err, SocksError::AddressTypeUnsupported));
+    }
+
+    #[test]
+    fn reply_errors() {
+        for (code, err) in [
+            (0x01, SocksError::GeneralFailure),
+            (0x05, SocksError::ConnectionRefused),
+            (0x7F, SocksError::UnknownReply(0x7F)),
+        ] {
+            let found = Socks5Dialer::new()
+                .dial_with(|| Ok(MockProxy::with_reply(code)), &target())
+                .unwrap_err();
+            assert_eq!(found.to_string(), err.to_string());
+        }
+    }
+
+    #[test]
+    fn privacy_policy() {
+        let dialer = Socks5Dialer::new().with_privacy_policy(PrivacyPolicy::Strict);
+        assert!(dialer.privacy_policy().check_direct(&target().host).is_ok());
+
+        #[cfg(feature = "tor")]
+        {
+            let onion: HostName =
+                "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion".parse().unwrap();
+            let target = NetAddr {
+                host: onion.clone(),
+                port: 9735,
+            };
+            let err = dialer.dial_direct(&target).unwrap_err();
+            assert!(matches!(err, SocksError::ProxyRequired(ref host) if *host == onion));
+            assert!(matches!(
+                Socks5Dialer::new().dial_direct(&target).unwrap_err(),
+                SocksError::Io(ref err) if err.kind() == io::ErrorKind::Unsupported
+            ));
+        }
+        #[cfg(feature = "dns")]
+        {
+            let target = NetAddr {
+                host: HostName::Dns("localhost".to_owned()),
+                port: 1,
+            };
+            assert!(matches!(
+                dialer.dial_direct(&target).unwrap_err(),
+                SocksError::ProxyRequired(HostName::Dns(_))
+            ));
+        }
+    }
+}
@@ -19,14 +19,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::array::TryFromSliceError;
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
-use base32::Alphabet;
 use sha3::Digest;
 
-const ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+use super::{base32, InputSnippet};
+
 pub const ONION_V3_RAW_LEN: usize = 35;
+/// Length of the base32-encoded part of the onion address (without `.onion`
+/// suffix).
+pub const ONION_V3_BASE32_LEN: usize = super::base32::encoded_len(ONION_V3_RAW_LEN);
+
+/// Default length of Tor time period used for the hidden service key blinding,
+/// in minutes.
+pub const TIME_PERIOD_LENGTH: u64 = 1440;
+const BLIND_STRING: &[u8] = b"Derive temporary signing key\0";
+const ED25519_BASEPOINT: &[u8] = b"(15112221349535400772501151409588531511454012693041857206046113283949847762202, \
+                                   46316835694926478169428394003475163141307993866256225615783033603165251855960)";
+const KEY_BLIND_NONCE: &[u8] = b"key-blind";
+/// Length of the input for the hidden service identity key blinding factor
+/// computation returned by [`OnionAddrV3::descriptor_signing_input`].
+pub const BLINDING_INPUT_LEN: usize =
+    BLIND_STRING.len() + 32 + ED25519_BASEPOINT.len() + KEY_BLIND_NONCE.len() + 16;
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(
@@ -41,21 +57,76 @@ pub struct OnionAddrV3 {
 
 impl From<ed25519::PublicKey> for OnionAddrV3 {
     fn from(pk: ed25519::PublicKey) -> Self {
-        let mut h = sha3::Sha3_256::default();
-        h.update(b".onion checksum");
-        h.update(&pk[..]);
-        h.update([3u8]);
-        let hash = h.finalize();
-        let checksum = u16::from_le_bytes([hash[0], hash[1]]);
+        let checksum = onion_checksum(checksum_hasher(), &pk[..]);
         Self { pk, checksum }
     }
 }
 
+/// Constructs hasher which has already absorbed the constant checksum prefix.
+fn checksum_hasher() -> sha3::Sha3_256 { sha3::Sha3_256::new_with_prefix(b".onion checksum") }
+
+/// Completes checksum computation for the public key using the hasher
+/// returned by [`checksum_hasher`].
+fn onion_checksum(mut h: sha3::Sha3_256, pk: &[u8]) -> u16 {
+    h.update(pk);
+    h.update([3u8]);
+    let hash = h.finalize();
+    u16::from_le_bytes([hash[0], hash[1]])
+}
+
+/// Verifies checksums of multiple onion addresses at once, returning for each
+/// of them whether it is a valid V3 onion address.
+///
+/// Unlike parsing each address with [`OnionAddrV3::from_str`], doesn't
+/// construct error values and hashes the constant checksum prefix only once
+/// for the whole batch.
+pub fn verify_onion_batch(addrs: &[&str]) -> Vec<bool> {
+    let prefixed = checksum_hasher();
+    addrs
+        .iter()
+        .map(|addr| {
+            let Some(stripped) = addr.strip_suffix(".onion") else {
+                return false;
+            };
+            if stripped.len() != ONION_V3_BASE32_LEN {
+                return false;
+            }
+            let Some(data) = base32::decode_ct(stripped) else {
+                return false;
+            };
+            if data.len() != ONION_V3_RAW_LEN || data[ONION_V3_RAW_LEN - 1] != 3 {
+                return false;
+            }
+            let checksum = u16::from_le_bytes([data[32], data[33]]);
+            onion_checksum(prefixed.clone(), &data[..32]) == checksum
+        })
+        .collect()
+}
+
+impl From<[u8; 32]> for OnionAddrV3 {
+    /// Constructs onion address from the raw ed25519 public key of the hidden
+    /// service. The key is not checked to be a valid curve point.
+    fn from(pk: [u8; 32]) -> Self { OnionAddrV3::from(ed25519::PublicKey::new(pk)) }
+}
+
+impl TryFrom<&[u8]> for OnionAddrV3 {
+    type Error = TryFromSliceError;
+
+    /// Constructs onion address from the raw ed25519 public key of the hidden
+    /// service, which must be 32 bytes long.
+    fn try_from(pk: &[u8]) -> Result<Self, Self::Error> { <[u8; 32]>::try_from(pk).map(Self::from) }
+}
+
 impl From<OnionAddrV3> for ed25519::PublicKey {
     fn from(onion: OnionAddrV3) -> Self { onion.pk }
 }
 
 impl OnionAddrV3 {
+    /// Returns normalized string representation of the address: lowercase
+    /// base32 encoding followed by `.onion` suffix, which is accepted by
+    /// [`OnionAddrV3::from_str`].
+    pub fn to_canonical(&self) -> String { self.to_string() }
+
     pub fn into_public_key(self) -> ed25519::PublicKey { self.pk }
 
     pub fn into_raw_bytes(self) -> [u8; ONION_V3_RAW_LEN] {
@@ -67,6 +138,84 @@ impl OnionAddrV3 {
     }
 
     pub fn checksum(self) -> u16 { self.checksum }
+
+    /// Constructs the byte string which is hashed with SHA3-256 to produce the
+    /// factor blinding the hidden service identity key for the given time
+    /// `period` number (rend-spec-v3, appendix A.2), using the default time
+    /// period length of [`TIME_PERIOD_LENGTH`] minutes:
+    ///
+    /// `BLIND_STRING | A | B | "key-blind" | INT_8(period) | INT_8(period_length)`,
+    ///
+    /// where `A` is the identity public key and `B` is the string
+    /// representation of the ed25519 basepoint.
+    pub fn descriptor_signing_input(&self, period: u64) -> [u8; BLINDING_INPUT_LEN] {
+        let mut input = [0u8; BLINDING_INPUT_LEN];
+        let mut pos = 0;
+        for item in [
+            BLIND_STRING,
+            self.pk.as_slice(),
+            ED25519_BASEPOINT,
+            KEY_BLIND_NONCE,
+            &period.to_be_bytes(),
+            &TIME_PERIOD_LENGTH.to_be_bytes(),
+        ] {
+            input[pos..pos + item.len()].copy_from_slice(item);
+            pos += item.len();
+        }
+        debug_assert_eq!(pos, BLINDING_INPUT_LEN);
+        input
+    }
+
+    /// Verifies ed25519 signature of the message against the public key
+    /// embedded into the address, authenticating the message as coming from
+    /// the onion service.
+    pub fn verify(&self, msg: &[u8], sig: &[u8; 64]) -> bool {
+        self.pk.verify(msg, &ed25519::Signature::new(*sig)).is_ok()
+    }
+
+    /// Returns short fingerprint of the address key, like `76e7-5ad1`, made of
+    /// the first four bytes of SHA3-256 hash of the public key.
+    ///
+    /// The fingerprint is intended only for displaying peers in logs and user
+    /// interfaces; it is too short to identify or authenticate the peer
+    /// securely.
+    pub fn fingerprint(&self) -> String {
+        let hash = sha3::Sha3_256::digest(self.pk.as_slice());
+        format!("{:02x}{:02x}-{:02x}{:02x}", hash[0], hash[1], hash[2], hash[3])
+    }
+
+    /// Checks whether both addresses refer to the same hidden service identity,
+    /// comparing only their ed25519 public keys.
+    pub fn same_identity(&self, other: &OnionAddrV3) -> bool { self.pk == other.pk }
+
+    /// Checks whether the address is present in the list (like an allow-list)
+    /// using constant-time comparison of the address keys across the whole
+    /// list, not leaking via timing which of the addresses or their prefixes
+    /// matched.
+    pub fn ct_matches(&self, list: &[OnionAddrV3]) -> bool {
+        crate::crypto::ed25519::ct_contains(
+            self.pk.as_slice(),
+            list.iter().map(|onion| onion.pk.as_slice()),
+        )
+    }
+
+    /// Encodes the address public key as Bech32 string with the provided
+    /// human-readable part.
+    #[cfg(feature = "bech32")]
+    pub fn to_bech32(&self, hrp: &str) -> Result<String, super::Bech32Error> {
+        let mut pk = [0u8; 32];
+        pk.copy_from_slice(self.pk.as_slice());
+        super::bech32::encode(hrp, &pk)
+    }
+
+    /// Decodes the address from Bech32 string produced by
+    /// [`OnionAddrV3::to_bech32`], checking its checksum and human-readable
+    /// part.
+    #[cfg(feature = "bech32")]
+    pub fn from_bech32(s: &str, hrp: &str) -> Result<Self, super::Bech32Error> {
+        let pk = super::bech32::decode(s, hrp)?;
+        Ok(OnionAddrV3::from(ed25519::PublicKey::new(pk)))
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
@@ -74,23 +223,29 @@ impl OnionAddrV3 {
 #[display(doc_comments)]
 pub enum OnionAddrError {
     /// onion address {0} doesn't end with `.onion` suffix.
-    NoSuffix(String),
+    NoSuffix(InputSnippet),
 
-    /// onion address {0} has an invalid base32 encoding.
-    InvalidBase32(String),
+    /// invalid base32 in onion address {input}: at position {position}.
+    InvalidBase32 {
+        input: InputSnippet,
+        position: usize,
+    },
 
     /// onion address {0} has an invalid length.
-    InvalidLen(String),
+    InvalidLen(InputSnippet),
 
     /// version {1} encoded in address {0} doesn't match V3.
-    VersionMismatch(String, u8),
+    VersionMismatch(InputSnippet, u8),
 
     /// onion address {addr} has an invalid checksum {found} instead of {expected}.
     InvalidChecksum {
         expected: u16,
         found: u16,
-        addr: String,
+        addr: InputSnippet,
     },
+
+    /// Tor control port reply doesn't contain `ServiceID`.
+    NoServiceId,
 }
 
 impl FromStr for OnionAddrV3 {
@@ -98,15 +253,69 @@ impl FromStr for OnionAddrV3 {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let stripped =
-            s.strip_suffix(".onion").ok_or_else(|| OnionAddrError::NoSuffix(s.to_owned()))?;
-        let data: Vec<u8> = base32::decode(ALPHABET, stripped)
-            .ok_or_else(|| OnionAddrError::InvalidBase32(s.to_owned()))?;
+            s.strip_suffix(".onion").ok_or_else(|| OnionAddrError::NoSuffix(s.into()))?;
+        OnionAddrV3::decode_base32(stripped, s)
+    }
+}
+
+impl OnionAddrV3 {
+    /// Checks whether the string has the shape of a V3 onion address: `.onion`
+    /// suffix, 56 characters of base32 alphabet and the version byte equal to
+    /// 3, without verifying the public key checksum, which requires hashing.
+    ///
+    /// A string passing this check is either parsed by
+    /// [`OnionAddrV3::from_str`] or rejected with
+    /// [`OnionAddrError::InvalidChecksum`]. Intended for cheap live validation
+    /// of the user input, with the full parsing done on submission.
+    pub fn has_valid_shape(s: &str) -> bool {
+        let Some(stripped) = s.strip_suffix(".onion") else {
+            return false;
+        };
+        if stripped.len() != ONION_V3_BASE32_LEN || !super::base32::is_base32(stripped) {
+            return false;
+        }
+        // The version byte is encoded by the lower 3 bits of the penultimate
+        // character and all 5 bits of the last one
+        let bytes = stripped.as_bytes();
+        let hi = super::base32::decode_char(bytes[ONION_V3_BASE32_LEN - 2]).unwrap_or_default();
+        let lo = super::base32::decode_char(bytes[ONION_V3_BASE32_LEN - 1]).unwrap_or_default();
+        ((hi & 0x07) << 5 | lo) == 3
+    }
+
+    /// Parses the reply of Tor control port `ADD_ONION` command, which
+    /// contains `ServiceID=<56 base32 chars>` line (with or without `250-`
+    /// reply code prefix), into the onion address of the created service.
+    /// The reply may contain other lines (like `PrivateKey` and `OK`), which
+    /// are ignored.
+    pub fn from_control_reply(reply: &str) -> Result<Self, OnionAddrError> {
+        let service_id = reply
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .map(|line| {
+                line.strip_prefix("250-").or_else(|| line.strip_prefix("250 ")).unwrap_or(line)
+            })
+            .find_map(|line| line.strip_prefix("ServiceID="))
+            .ok_or(OnionAddrError::NoServiceId)?;
+        if service_id.len() != ONION_V3_BASE32_LEN {
+            return Err(OnionAddrError::InvalidLen(service_id.into()));
+        }
+        OnionAddrV3::decode_base32(service_id, service_id)
+    }
+
+    fn decode_base32(stripped: &str, s: &str) -> Result<Self, OnionAddrError> {
+        if let Some(position) = super::base32::invalid_position(stripped) {
+            return Err(OnionAddrError::InvalidBase32 {
+                input: s.into(),
+                position,
+            });
+        }
+        let data = base32::decode_ct(stripped).expect("base32 alphabet is checked");
         if data.len() != ONION_V3_RAW_LEN {
-            return Err(OnionAddrError::InvalidLen(s.to_owned()));
+            return Err(OnionAddrError::InvalidLen(s.into()));
         }
         let ver = data[ONION_V3_RAW_LEN - 1];
         if ver != 3 {
-            return Err(OnionAddrError::VersionMismatch(s.to_owned(), ver));
+            return Err(OnionAddrError::VersionMismatch(s.into(), ver));
         }
         let pk =
             OnionAddrV3::from(ed25519::PublicKey::from_slice(&data[..32]).expect("fixed length"));
@@ -115,7 +324,7 @@ impl FromStr for OnionAddrV3 {
             return Err(OnionAddrError::InvalidChecksum {
                 expected: pk.checksum,
                 found: checksum,
-                addr: s.to_owned(),
+                addr: s.into(),
             });
         }
         Ok(pk)
@@ -123,9 +332,12 @@ impl FromStr for OnionAddrV3 {
 }
 
 impl Display for OnionAddrV3 {
+    /// Writes lowercase base32 encoding of the address followed by `.onion`
+    /// suffix. Doesn't allocate.
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let b32 = base32::encode(ALPHABET, &self.into_raw_bytes());
-        write!(f, "{}.onion", b32)
+        let mut buf = [0u8; ONION_V3_BASE32_LEN];
+        f.write_str(super::base32::encode_into(&self.into_raw_bytes(), &mut buf))?;
+        f.write_str(".onion")
     }
 }
 
@@ -138,3 +350,256 @@ impl TryFrom<String> for OnionAddrV3 {
 
     fn try_from(value: String) -> Result<Self, Self::Error> { Self::from_str(&value) }
 }
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::FromHex;
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+
+    const ONION: &str = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+
+    #[quickcheck]
+    fn prop_display_from_str(addr: OnionAddrV3) {
+        assert_eq!(OnionAddrV3::from_str(&addr.to_string()), Ok(addr));
+    }
+
+    #[test]
+    fn display_from_str() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        assert_eq!(onion.to_string(), ONION);
+        assert_eq!(format!("{onion}").len(), ONION_V3_BASE32_LEN + ".onion".len());
+        assert_eq!(OnionAddrV3::from_str(&onion.to_string()), Ok(onion));
+        assert_eq!(
+            OnionAddrV3::from_str(&ONION.to_uppercase().replace(".ONION", ".onion")),
+            Ok(onion)
+        );
+    }
+
+    #[test]
+    fn batch() {
+        let mut corrupted = ONION.to_owned();
+        corrupted.replace_range(..1, "3");
+        let uppercase = ONION.to_uppercase().replace(".ONION", ".onion");
+        let addrs = [
+            ONION,
+            &corrupted,
+            "",
+            &ONION[1..],
+            ONION.trim_end_matches(".onion"),
+            &uppercase,
+            "0gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion",
+        ];
+        assert_eq!(verify_onion_batch(&addrs), vec![true, false, false, false, false, true, false]);
+        for (addr, valid) in addrs.iter().zip(verify_onion_batch(&addrs)) {
+            assert_eq!(OnionAddrV3::from_str(addr).is_ok(), valid, "{addr}");
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let uppercase = ONION.to_uppercase().replace(".ONION", ".onion");
+        for s in [ONION, &uppercase] {
+            let canonical = OnionAddrV3::from_str(s).unwrap().to_canonical();
+            assert_eq!(canonical, ONION);
+            assert_eq!(OnionAddrV3::from_str(&canonical).unwrap().to_canonical(), canonical);
+        }
+    }
+
+    #[test]
+    fn descriptor_signing_input() {
+        // Test vector from `test_blinding_basics` of Tor (src/test/test_hs_common.c):
+        // blinding factor of the identity key for the time period 1234
+        let pk = <[u8; 32]>::from_hex(
+            "833990b085c1a688c1d4c8b1f6b56afaf5a2eca674449e1d704f83765ccb7bc6",
+        )
+        .unwrap();
+        let onion = OnionAddrV3::from(ed25519::PublicKey::new(pk));
+        let input = onion.descriptor_signing_input(1234);
+        assert_eq!(
+            sha3::Sha3_256::digest(input).as_slice(),
+            Vec::<u8>::from_hex("379e50db31fee6775abd0af6fb7c371e060308f4f847db09fe4cfe13af602287")
+                .unwrap()
+        );
+        assert_ne!(input, onion.descriptor_signing_input(1235));
+    }
+
+    #[test]
+    fn verify() {
+        let pair = ed25519::KeyPair::from_seed(ed25519::Seed::new([7u8; 32]));
+        let onion = OnionAddrV3::from(pair.pk);
+        let sig = *pair.sk.sign(b"message", None);
+        assert!(onion.verify(b"message", &sig));
+        assert!(!onion.verify(b"massage", &sig));
+        let mut tampered = sig;
+        tampered[0] ^= 1;
+        assert!(!onion.verify(b"message", &tampered));
+        assert!(!OnionAddrV3::from_str(ONION).unwrap().verify(b"message", &sig));
+    }
+
+    #[test]
+    fn valid_shape() {
+        let check = |s: &str| {
+            let shape = OnionAddrV3::has_valid_shape(s);
+            match OnionAddrV3::from_str(s) {
+                Ok(_) | Err(OnionAddrError::InvalidChecksum { .. }) => assert!(shape, "{s}"),
+                Err(_) => assert!(!shape, "{s}"),
+            }
+            shape
+        };
+        assert!(check(ONION));
+        assert!(check(&ONION.to_uppercase().replace(".ONION", ".onion")));
+        assert!(!check(&ONION.to_uppercase()));
+        assert!(!check(ONION.trim_end_matches(".onion")));
+        assert!(!check(&ONION[1..]));
+        assert!(!check(&format!("a{ONION}")));
+        assert!(!check(""));
+        assert!(!check(".onion"));
+
+        // Checksum is not verified
+        let corrupted = ONION.replacen('2', "3", 1);
+        assert!(matches!(
+            OnionAddrV3::from_str(&corrupted),
+            Err(OnionAddrError::InvalidChecksum { .. })
+        ));
+        assert!(check(&corrupted));
+
+        let len = ONION_V3_BASE32_LEN;
+        for pos in [0, 10, len - 3, len - 2, len - 1] {
+            for c in ['a', 'b', 'd', 'q', '7', '0', '1', '8', '=', '-', ' ', '.', '\u{e9}', 'Z'] {
+                let mut mutated = ONION.to_owned();
+                mutated.replace_range(pos..pos + 1, &c.to_string());
+                check(&mutated);
+            }
+        }
+        // Version mismatch
+        let v4 = ONION.replace("wid.onion", "wie.onion");
+        assert!(matches!(OnionAddrV3::from_str(&v4), Err(OnionAddrError::VersionMismatch(_, 4))));
+        assert!(!check(&v4));
+    }
+
+    #[test]
+    fn error_snippet() {
+        let corrupted = ONION.replace("53wid", "53w1d");
+        let err = OnionAddrV3::from_str(&corrupted).unwrap_err();
+        assert_eq!(err, OnionAddrError::InvalidBase32 {
+            input: InputSnippet::new(&corrupted),
+            position: 54,
+        });
+        assert_eq!(
+            err.to_string(),
+            "invalid base32 in onion address \"2gzyxa5ihm7nsggfxnu52rck...\": at position 54."
+        );
+
+        // All errors carrying the input truncate it
+        let long = "x".repeat(4096);
+        let version = ONION.replace("wid.onion", "wie.onion");
+        let checksum = ONION.replacen('2', "3", 1);
+        for s in [long.clone(), format!("{long}.onion"), version, checksum] {
+            let err = OnionAddrV3::from_str(&s).unwrap_err();
+            let (OnionAddrError::NoSuffix(input)
+            | OnionAddrError::InvalidLen(input)
+            | OnionAddrError::VersionMismatch(input, _)
+            | OnionAddrError::InvalidChecksum { addr: input, .. }) = &err
+            else {
+                panic!("unexpected error {err}");
+            };
+            assert_eq!(input, &InputSnippet::new(&s));
+            assert!(input.is_truncated());
+            assert!(err.to_string().len() < 128, "{err}");
+        }
+    }
+
+    #[test]
+    fn fingerprint() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        assert_eq!(onion.fingerprint(), "76e7-5ad1");
+        assert_eq!(onion.fingerprint(), onion.fingerprint());
+        assert_eq!(OnionAddrV3::from([0u8; 32]).fingerprint(), "9e62-9197");
+    }
+
+    #[test]
+    fn ct_matches() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        let others = (0u8..4).map(|i| OnionAddrV3::from([i; 32])).collect::<Vec<_>>();
+        let mut with_onion = others.clone();
+        with_onion.insert(2, onion);
+        let mut prefix = <[u8; 32]>::try_from(onion.into_public_key().as_slice()).unwrap();
+        prefix[31] ^= 1;
+        let lists =
+            [vec![], vec![onion], others.clone(), with_onion, vec![OnionAddrV3::from(prefix)]];
+        for list in lists {
+            let naive = list.iter().any(|other| other == &onion);
+            assert_eq!(onion.ct_matches(&list), naive, "{list:?}");
+        }
+    }
+
+    #[test]
+    fn same_identity() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        let uppercase = ONION.to_uppercase().replace(".ONION", ".onion");
+        assert!(onion.same_identity(&OnionAddrV3::from_str(&uppercase).unwrap()));
+        assert!(onion.same_identity(&OnionAddrV3::from(onion.into_public_key())));
+        assert!(onion.same_identity(&onion));
+        assert!(!onion.same_identity(&OnionAddrV3::from([0x42u8; 32])));
+    }
+
+    #[test]
+    fn from_array() {
+        let onion = OnionAddrV3::from_str(ONION).unwrap();
+        let mut pk = [0u8; 32];
+        pk.copy_from_slice(onion.into_public_key().as_slice());
+        assert_eq!(OnionAddrV3::from(pk), onion);
+        assert_eq!(OnionAddrV3::try_from(&pk[..]).unwrap(), onion);
+        assert!(OnionAddrV3::try_from(&pk[1..]).is_err());
+    }
+
+    #[test]
+    fn control_reply() {
+        let reply = "250-ServiceID=2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid\r\n\
+                     250-PrivateKey=ED25519-V3:iP5xSr7B3Kj2gkAcvHBvEqyBdkTVqFHmcn2+TQ7keGY\
+                     HLsUZRwvXBSdhAbIuoIGuSCrfDctvLFcLeLtPD3nAg==\r\n\
+                     250 OK\r\n";
+        let onion = OnionAddrV3::from_control_reply(reply).unwrap();
+        assert_eq!(onion, OnionAddrV3::from_str(ONION).unwrap());
+
+        assert_eq!(
+            OnionAddrV3::from_control_reply(
+                "ServiceID=2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid"
+            ),
+            Ok(onion)
+        );
+        assert_eq!(OnionAddrV3::from_control_reply("250 OK\r\n"), Err(OnionAddrError::NoServiceId));
+        assert!(matches!(
+            OnionAddrV3::from_control_reply(
+                "250-ServiceID=3gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid\r\n"
+            ),
+            Err(OnionAddrError::InvalidChecksum { .. })
+        ));
+        assert!(matches!(
+            OnionAddrV3::from_control_reply("250-ServiceID=2gzyxa5ihm7nsggf\r\n"),
+            Err(OnionAddrError::InvalidLen(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "bech32")]
+    fn bech32() {
+        let addr = OnionAddrV3::from_str(ONION).unwrap();
+        let encoded = addr.to_bech32("addr").unwrap();
+        assert!(encoded.starts_with("addr1"));
+        assert_eq!(OnionAddrV3::from_bech32(&encoded, "addr"), Ok(addr));
+        assert_eq!(OnionAddrV3::from_bech32(&encoded.to_uppercase(), "addr"), Ok(addr));
+        assert!(OnionAddrV3::from_bech32(&encoded, "other").is_err());
+
+        let mut corrupted = encoded.into_bytes();
+        let last = corrupted.last_mut().unwrap();
+        *last = if *last == b'q' { b'p' } else { b'q' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert!(matches!(
+            OnionAddrV3::from_bech32(&corrupted, "addr"),
+            Err(crate::addr::Bech32Error::InvalidEncoding(_))
+        ));
+    }
+}
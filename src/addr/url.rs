@@ -0,0 +1,189 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Peer URLs of `cyphernet://<pubkey>@<host>:<port>?proxy=<ip>:<port>` form.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::{AddrParseError, Host, HostName, NetAddr, NetAddrError, PeerAddr, PeerAddrParseError};
+use crate::crypto::EcPk;
+
+/// Errors parsing [`PeerUrl`].
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PeerUrlError<Id: Debug>
+where
+    Id: FromStr,
+    <Id as FromStr>::Err: std::error::Error,
+{
+    /// unexpected or absent URL scheme. The address should start with '{0}://'
+    InvalidScheme(&'static str),
+
+    /// invalid peer address in URL. Details: {0}
+    #[from]
+    Peer(PeerAddrParseError<Id>),
+
+    /// invalid proxy address in URL. Details: {0}
+    Proxy(NetAddrError),
+
+    /// malformed URL query parameter '{0}'; parameters must have `name=value` form.
+    MalformedParam(String),
+
+    /// unknown URL query parameter '{0}'.
+    UnknownParam(String),
+
+    /// repeated URL query parameter '{0}'.
+    RepeatedParam(String),
+}
+
+/// URL of a peer, containing peer public key, network address and optional
+/// proxy which should be used for connecting to the peer, like
+/// `cyphernet://<pubkey>@<host>:<port>?proxy=127.0.0.1:9050`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PeerUrl<Id: EcPk, H: Host = HostName> {
+    pub peer: PeerAddr<Id, NetAddr<H>>,
+    pub proxy: Option<NetAddr<IpAddr>>,
+}
+
+impl<Id: EcPk, H: Host> PeerUrl<Id, H> {
+    /// URL scheme used by the peer URLs.
+    pub const SCHEME: &'static str = "cyphernet";
+
+    pub fn new(peer: PeerAddr<Id, NetAddr<H>>, proxy: Option<NetAddr<IpAddr>>) -> Self {
+        Self { peer, proxy }
+    }
+}
+
+impl<Id: EcPk, H: Host> Display for PeerUrl<Id, H>
+where
+    Id: Display,
+    H: Display,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", Self::SCHEME, self.peer)?;
+        if let Some(proxy) = &self.proxy {
+            write!(f, "?proxy={proxy}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<Id: EcPk, H: Host> FromStr for PeerUrl<Id, H>
+where
+    Id: FromStr + Debug,
+    <Id as FromStr>::Err: std::error::Error,
+    H: FromStr,
+    AddrParseError: From<H::Err>,
+{
+    type Err = PeerUrlError<Id>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix(Self::SCHEME)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .ok_or(PeerUrlError::InvalidScheme(Self::SCHEME))?;
+        let (peer, query) = match rest.split_once('?') {
+            Some((peer, query)) => (peer, Some(query)),
+            None => (rest, None),
+        };
+
+        let mut proxy = None;
+        for param in query.into_iter().flat_map(|query| query.split('&')) {
+            match param.split_once('=') {
+                Some(("proxy", _)) if proxy.is_some() => {
+                    return Err(PeerUrlError::RepeatedParam(param.to_owned()))
+                }
+                Some(("proxy", addr)) => {
+                    proxy = Some(NetAddr::<IpAddr>::from_str(addr).map_err(PeerUrlError::Proxy)?)
+                }
+                Some((name, _)) if !name.is_empty() => {
+                    return Err(PeerUrlError::UnknownParam(name.to_owned()))
+                }
+                _ => return Err(PeerUrlError::MalformedParam(param.to_owned())),
+            }
+        }
+
+        Ok(PeerUrl {
+            peer: PeerAddr::from_str(peer)?,
+            proxy,
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "ed25519")]
+mod test {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use super::*;
+    use crate::crypto::ed25519::PublicKey;
+
+    fn pubkey() -> PublicKey { PublicKey::from(::ed25519::PublicKey::new([7u8; 32])) }
+
+    #[test]
+    fn full_url() {
+        let s = format!("cyphernet://{}@1.2.3.4:8333?proxy=127.0.0.1:9050", pubkey());
+        let url = PeerUrl::<PublicKey>::from_str(&s).unwrap();
+        assert_eq!(url.peer.id(), &pubkey());
+        assert_eq!(url.peer.addr(), &NetAddr::from_str("1.2.3.4:8333").unwrap());
+        assert_eq!(url.proxy, Some(NetAddr::from(SocketAddr::from((Ipv4Addr::LOCALHOST, 9050)))));
+        assert_eq!(url.to_string(), s);
+    }
+
+    #[test]
+    fn no_proxy() {
+        let s = format!("cyphernet://{}@1.2.3.4:8333", pubkey());
+        let url = PeerUrl::<PublicKey>::from_str(&s).unwrap();
+        assert_eq!(url.proxy, None);
+        assert_eq!(url.to_string(), s);
+
+        let url = PeerUrl::<PublicKey>::from_str(&format!("{s}?")).unwrap_err();
+        assert!(matches!(url, PeerUrlError::MalformedParam(_)));
+    }
+
+    #[test]
+    fn errors() {
+        let peer = format!("{}@1.2.3.4:8333", pubkey());
+        let parse = |s: String| PeerUrl::<PublicKey>::from_str(&s).unwrap_err();
+        assert!(matches!(parse(format!("http://{peer}")), PeerUrlError::InvalidScheme(_)));
+        assert!(matches!(parse(peer.clone()), PeerUrlError::InvalidScheme(_)));
+        assert!(matches!(
+            parse(format!("cyphernet://{peer}?tor=1")),
+            PeerUrlError::UnknownParam(_)
+        ));
+        assert!(matches!(
+            parse(format!("cyphernet://{peer}?proxy")),
+            PeerUrlError::MalformedParam(_)
+        ));
+        assert!(matches!(parse(format!("cyphernet://{peer}?=1")), PeerUrlError::MalformedParam(_)));
+        assert!(matches!(
+            parse(format!("cyphernet://{peer}?proxy=127.0.0.1:9050&proxy=127.0.0.1:9050")),
+            PeerUrlError::RepeatedParam(_)
+        ));
+        assert!(matches!(
+            parse(format!("cyphernet://{peer}?proxy=127.0.0.1")),
+            PeerUrlError::Proxy(NetAddrError::PortAbsent(_))
+        ));
+        assert!(matches!(parse("cyphernet://1.2.3.4:8333".to_owned()), PeerUrlError::Peer(_)));
+    }
+}
@@ -19,10 +19,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::cmp::Ordering;
-use std::fmt::{self, Display, Formatter};
-use std::ops::Deref;
-use std::str::FromStr;
+use alloc::string::String;
+use core::cmp::Ordering;
+use core::fmt::{self, Display, Formatter};
+use core::ops::Deref;
+use core::str::FromStr;
 
 use ::ed25519::x25519;
 
@@ -64,6 +65,30 @@ impl Ecdh for PrivateKey {
     }
 }
 
+/// Converts ed25519 public key into the birationally equivalent x25519
+/// (Montgomery form) public key, such that the same key material can be used
+/// both as an identity key (like Tor onion service key) and as a Noise
+/// handshake key.
+///
+/// # Returns
+///
+/// `None` if the data doesn't represent a valid ed25519 curve point or the
+/// point has a small order.
+pub fn ed25519_to_x25519(pk: &[u8; 32]) -> Option<[u8; 32]> {
+    x25519::PublicKey::from_ed25519(&::ed25519::PublicKey::new(*pk)).ok().map(|pk| *pk)
+}
+
+/// Converts ed25519 secret key `seed` into x25519 secret key matching the
+/// public key produced by [`ed25519_to_x25519`] from the ed25519 public key
+/// of the same seed.
+///
+/// The x25519 key is the clamped first half of SHA-512 hash of the seed, i.e.
+/// the same scalar which is used by ed25519 signing.
+pub fn ed25519_sk_to_x25519(seed: &[u8; 32]) -> [u8; 32] {
+    let sk = ::ed25519::KeyPair::from_seed(::ed25519::Seed::new(*seed)).sk;
+    *x25519::SecretKey::from_ed25519(&sk).expect("x25519 secret key has a fixed length")
+}
+
 #[derive(Wrapper, Copy, Clone, PartialEq, Eq, Hash, Debug, From)]
 #[wrapper(Deref)]
 #[cfg_attr(
@@ -112,12 +137,39 @@ impl PublicKey {
 
     #[cfg(feature = "pem")]
     pub fn to_pem(&self) -> String { self.0.to_pem() }
+
+    /// Checks whether the key is present in the list (like an allow-list)
+    /// without leaking via timing which of the keys or their prefixes
+    /// matched: all keys in the list are compared in full.
+    pub fn ct_matches(&self, list: &[PublicKey]) -> bool {
+        ct_contains(self.0.as_ref(), list.iter().map(|pk| pk.0.as_ref()))
+    }
+}
+
+/// Checks whether `item` is equal to any of the `list` items in constant time,
+/// comparing all bytes of each of the items. The execution time depends only
+/// on the number and length of the items.
+pub(crate) fn ct_contains<'a>(item: &[u8], list: impl IntoIterator<Item = &'a [u8]>) -> bool {
+    let mut found = 0u8;
+    for other in list {
+        let mut diff = (item.len() != other.len()) as u8;
+        for (a, b) in item.iter().zip(other) {
+            diff |= a ^ b;
+        }
+        // 0xFF if diff is zero, 0x00 otherwise
+        found |= ((diff as u16).wrapping_sub(1) >> 8) as u8;
+    }
+    found != 0
 }
 
 impl From<[u8; 32]> for PublicKey {
     fn from(other: [u8; 32]) -> Self { Self(::ed25519::PublicKey::new(other)) }
 }
 
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] { self.0.as_ref() }
+}
+
 impl TryFrom<&[u8]> for PublicKey {
     type Error = ::ed25519::Error;
 
@@ -126,7 +178,8 @@ impl TryFrom<&[u8]> for PublicKey {
     }
 }
 
-#[derive(Debug, Display, Error, From)]
+#[derive(Debug, Display, From)]
+#[cfg_attr(feature = "std", derive(Error))]
 #[display(doc_comments)]
 pub enum PublicKeyError {
     /// invalid length {0}
@@ -250,7 +303,8 @@ impl EcSig for Signature {
 }
 */
 
-#[derive(Debug, Display, Error, From)]
+#[derive(Debug, Display, From)]
+#[cfg_attr(feature = "std", derive(Error))]
 #[display(doc_comments)]
 pub enum SignatureError {
     /// invalid multibase string: {0}
@@ -284,9 +338,10 @@ impl FromStr for Signature {
 mod test {
     use std::str::FromStr;
 
+    use amplify::hex::FromHex;
     use quickcheck_macros::quickcheck;
 
-    use super::PublicKey;
+    use super::*;
 
     #[quickcheck]
     fn prop_encode_decode(input: PublicKey) {
@@ -296,6 +351,15 @@ mod test {
         assert_eq!(input, decoded);
     }
 
+    #[quickcheck]
+    fn prop_ct_matches(key: PublicKey, list: Vec<PublicKey>, insert_at: usize) {
+        assert_eq!(key.ct_matches(&list), list.iter().any(|pk| pk == &key));
+
+        let mut list = list;
+        list.insert(insert_at % (list.len() + 1), key);
+        assert!(key.ct_matches(&list));
+    }
+
     #[test]
     fn test_encode_decode() {
         let input = "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
@@ -303,4 +367,35 @@ mod test {
 
         assert_eq!(key.to_string(), input);
     }
+
+    #[test]
+    fn x25519_conversion() {
+        // Test vector from libsodium `ed25519_convert` test
+        let seed = <[u8; 32]>::from_hex(
+            "421151a459faeade3d247115f94aedae42318124095afabe4d1451a559faedee",
+        )
+        .unwrap();
+        let pk = <[u8; 32]>::from_hex(
+            "b5076a8474a832daee4dd5b4040983b6623b5f344aca57d4d6ee4baf3f259e6e",
+        )
+        .unwrap();
+        assert_eq!(*::ed25519::KeyPair::from_seed(::ed25519::Seed::new(seed)).pk, pk);
+
+        let xpk = <[u8; 32]>::from_hex(
+            "f1814f0e8ff1043d8a44d25babff3cedcae6c22c3edaa48f857ae70de2baae50",
+        )
+        .unwrap();
+        let xsk = <[u8; 32]>::from_hex(
+            "8052030376d47112be7f73ed7a019293dd12ad910b654455798b4667d73de166",
+        )
+        .unwrap();
+        assert_eq!(ed25519_to_x25519(&pk), Some(xpk));
+        assert_eq!(ed25519_sk_to_x25519(&seed), xsk);
+        assert_eq!(*x25519::SecretKey::new(xsk).recover_public_key().unwrap(), xpk);
+
+        // Small-order point
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        assert_eq!(ed25519_to_x25519(&identity), None);
+    }
 }
@@ -30,8 +30,7 @@
 //! - Tor, Nym, I2P and other mix networks and SOCKS proxies
 //! - P2P addresses with node public keys
 //! - May be used in a way that prevents using DNS names (outside mixnet scope).
-//! - **Noise protocol framework** (module `noise`) for end-to-end encrypted
-//! network communications.
+//! - **Noise protocol framework** (module `noise`) for end-to-end encrypted network communications.
 //!
 //! The library tries to minimize number of dependencies. Most of its
 //! functionality is available via non-default features, like:
@@ -40,6 +39,12 @@
 //!   additional crypto libraries for parsing public keys)
 //! - `serde`: encoding for addresses types
 //! - `dns`: enable use of DNS names alongside IP addresses and mixnet names.
+//! - `url`: conversion of network addresses into `url::Url`.
+//! - `std` (default): `std::net`-based network address types, socket and SOCKS5 adaptors and
+//!   implementation of `std::error::Error` for the error types. Without it the library is `no_std`
+//!   and requires only `alloc`, providing `crypto` and `noise` modules (including ChaCha20Poly1305
+//!   AEAD framing) and the fixed-size address types from the `addr` module (address traits,
+//!   [`addr::InputSnippet`] and, with `nym` feature, Nym addresses).
 //!
 //! Network addresses provided by the library include the following types:
 //! * [`addr::InetHost`] - IP addr or DNS name
@@ -50,9 +55,18 @@
 //! * [`addr::PeerAddr`] - any of the above addresses + node public key for authentication
 //! * [`addr::ProxiedHost`] - host name + proxy (there are IP/DNS w/o proxy and with proxy)
 //! * [`addr::ProxiedAddr`] - any of the above addresses + proxy (thus IP/DNS is always proxied)
+//! * [`addr::MultiAddr`] - ordered list of addresses under which a peer can be reached (dual-stack
+//!   peers)
+//! * [`addr::PeerUrl`] - `cyphernet://` URL with peer address and optional proxy
+
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
 #[macro_use]
+#[cfg_attr(not(feature = "std"), allow(unused_imports))]
 extern crate amplify;
+#[macro_use]
+#[allow(unused_imports)]
+extern crate alloc;
 #[cfg(feature = "serde")]
 #[macro_use]
 extern crate serde;
@@ -20,25 +20,127 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use chacha20poly1305::aead::{Aead, Payload};
-use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+//! ChaCha20Poly1305 AEAD primitives used by the Noise protocol.
 
+use alloc::vec::Vec;
+
+use chacha20poly1305::aead::{self, Aead, AeadInPlace, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use rand_core::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use super::framing::{strip_padding, PaddingPolicy};
 use super::EncryptionError;
 
 pub const TAG_SIZE: usize = 16;
 
-fn _nonce(nonce: u64) -> Nonce {
-    let mut chacha_nonce = [0u8; 12];
-    chacha_nonce[4..].copy_from_slice(&nonce.to_le_bytes());
-    *Nonce::from_slice(&chacha_nonce[..])
+/// Size of ChaCha20Poly1305 key.
+pub const KEY_SIZE: usize = 32;
+
+/// Maximum length of a plaintext which can be encrypted with ChaCha20Poly1305
+/// AEAD under a single nonce (RFC 8439 `P_MAX`, ~256 GiB).
+pub const MAX_PLAINTEXT_LEN: u64 = (1 << 38) - 64;
+
+/// Checks that the plaintext of `len` bytes doesn't exceed
+/// [`MAX_PLAINTEXT_LEN`].
+fn check_plaintext_len(len: usize) -> Result<(), EncryptionError> {
+    if len as u64 > MAX_PLAINTEXT_LEN {
+        return Err(EncryptionError::MessageTooLong(len));
+    }
+    Ok(())
 }
 
-fn _cypher(key: &[u8]) -> ChaCha20Poly1305 {
-    let key = Key::from_slice(key);
-    ChaCha20Poly1305::new(key)
+/// Constructs raw 12-byte ChaCha20Poly1305 nonce from the message `counter`:
+/// four zero bytes followed by the little-endian counter, as defined by the
+/// Noise protocol. Allows to use the same nonces with external AEAD
+/// implementations.
+pub fn nonce_bytes(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    nonce
 }
 
-/// Encrypt a plaintext with associated data using the key and nonce.
+/// Hard limit on the number of sessions using the same key with random
+/// [`SessionNonce`] prefixes, at which the prefixes collide with the birthday
+/// bound probability of about 40%.
+pub const MAX_RANDOM_SESSIONS: u64 = 1 << 16;
+
+fn _nonce(nonce: u64) -> Nonce { Nonce::from(nonce_bytes(nonce)) }
+
+/// Nonce composed of a random per-session prefix and a per-message counter,
+/// which allows to reuse the same key across multiple sessions without
+/// coordinating the counters between them.
+///
+/// The 12-byte nonce consists of the 4-byte prefix, taking place of the zero
+/// bytes of the Noise nonce (see [`nonce_bytes`]), followed by the
+/// little-endian counter. Within a session the counter must never repeat.
+///
+/// # Session limit
+///
+/// Random prefixes of different sessions collide with the birthday bound
+/// probability of about `n^2 / 2^33` for `n` sessions, and a collision means
+/// nonce reuse for the messages with the same counter, breaking both the
+/// confidentiality and the authenticity of the sessions. This probability
+/// reaches 40% at [`MAX_RANDOM_SESSIONS`] (`2^16`) sessions, which is a hard
+/// limit on the number of sessions with random prefixes per key; the key must
+/// be rotated well before it (for example, after 2^8 sessions the collision
+/// probability is about 2^-17).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SessionNonce {
+    pub prefix: [u8; 4],
+    pub counter: u64,
+}
+
+impl SessionNonce {
+    /// Constructs nonce for the first message of a session with the given
+    /// prefix.
+    pub fn new(prefix: [u8; 4]) -> Self { Self { prefix, counter: 0 } }
+
+    /// Constructs nonce for the first message of a new session with the prefix
+    /// generated by the operating system random number generator.
+    #[cfg(feature = "std")]
+    pub fn random() -> Self { Self::with_rng(&mut rand_core::OsRng) }
+
+    /// Constructs nonce for the first message of a new session with the prefix
+    /// generated by the provided random number generator.
+    pub fn with_rng(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut prefix = [0u8; 4];
+        rng.fill_bytes(&mut prefix);
+        Self::new(prefix)
+    }
+
+    /// Returns raw 12-byte nonce: the prefix followed by the little-endian
+    /// counter.
+    pub fn to_bytes(self) -> [u8; 12] {
+        let mut nonce = nonce_bytes(self.counter);
+        nonce[..4].copy_from_slice(&self.prefix);
+        nonce
+    }
+
+    /// Returns the current nonce and advances the counter to the next
+    /// message, or returns `None` if the counter is exhausted.
+    pub fn next_nonce(&mut self) -> Option<Self> {
+        let current = *self;
+        self.counter = self.counter.checked_add(1)?;
+        Some(current)
+    }
+}
+
+impl From<SessionNonce> for [u8; 12] {
+    fn from(nonce: SessionNonce) -> Self { nonce.to_bytes() }
+}
+
+/// Constructs the cipher, checking the key length.
+fn _cypher(key: &[u8]) -> Result<ChaCha20Poly1305, EncryptionError> {
+    if key.len() != KEY_SIZE {
+        return Err(EncryptionError::InvalidKeyLen(key.len()));
+    }
+    Ok(ChaCha20Poly1305::new(Key::from_slice(key)))
+}
+
+/// Encrypt a plaintext with associated data using the key and nonce. The key
+/// is borrowed from any type referencing its bytes (like `SymmetricKey`,
+/// `[u8]` or `Vec<u8>`), so it is never copied, and must be 32 bytes long.
 ///
 /// # Returns
 ///
@@ -48,18 +150,25 @@ fn _cypher(key: &[u8]) -> ChaCha20Poly1305 {
 /// # Panics
 ///
 /// Function panics if `plaintext` and `cyphertext` have different length.
+///
+/// # Errors
+///
+/// [`EncryptionError::MessageTooLong`] if the plaintext exceeds
+/// [`MAX_PLAINTEXT_LEN`], or [`EncryptionError::InvalidKeyLen`] if the key is
+/// not 32 bytes long.
 pub fn encrypt(
-    key: &[u8],
+    key: &(impl AsRef<[u8]> + ?Sized),
     nonce: u64,
     associated_data: &[u8],
     plaintext: &[u8],
     ciphertext: Option<&mut [u8]>,
 ) -> Result<Vec<u8>, EncryptionError> {
+    check_plaintext_len(plaintext.len())?;
     let payload = Payload {
         msg: plaintext,
         aad: associated_data,
     };
-    let encrypted = _cypher(key).encrypt(&_nonce(nonce), payload)?;
+    let encrypted = _cypher(key.as_ref())?.encrypt(&_nonce(nonce), payload)?;
     if let Some(e) = ciphertext {
         e.copy_from_slice(&encrypted)
     }
@@ -68,6 +177,12 @@ pub fn encrypt(
 
 /// Decrypts the ciphertext with key, nonce and associated data.
 ///
+/// The authentication tag is verified in constant time, and the execution
+/// time depends only on the lengths of the ciphertext and associated data.
+/// However, ciphertext shorter than the tag is rejected before running the
+/// AEAD; use [`decrypt_ct`] when the time of the failure must not depend on
+/// the reason (truncated ciphertext or invalid tag).
+///
 /// # Returns
 ///
 /// Returns the decrypted msg, which is also copied to plaintext array, if
@@ -77,7 +192,7 @@ pub fn encrypt(
 ///
 /// Function panics if `plaintext` and `cyphertext` have different length.
 pub fn decrypt(
-    key: &[u8],
+    key: &(impl AsRef<[u8]> + ?Sized),
     nonce: u64,
     associated_data: &[u8],
     ciphertext: &[u8],
@@ -87,18 +202,527 @@ pub fn decrypt(
         msg: ciphertext,
         aad: associated_data,
     };
-    let decrypted = _cypher(key).decrypt(&_nonce(nonce), payload)?;
+    let decrypted = _cypher(key.as_ref())?.decrypt(&_nonce(nonce), payload)?;
     if let Some(d) = plaintext {
         d.copy_from_slice(&decrypted)
     }
     Ok(decrypted)
 }
 
+/// Encrypts a plaintext with associated data like [`encrypt`], but using
+/// [`SessionNonce`] composed of the session prefix and the message counter.
+///
+/// # Errors
+///
+/// [`EncryptionError::MessageTooLong`] if the plaintext exceeds
+/// [`MAX_PLAINTEXT_LEN`], or [`EncryptionError::InvalidKeyLen`] if the key is
+/// not 32 bytes long.
+pub fn encrypt_session(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: SessionNonce,
+    associated_data: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    check_plaintext_len(plaintext.len())?;
+    let payload = Payload {
+        msg: plaintext,
+        aad: associated_data,
+    };
+    Ok(_cypher(key.as_ref())?.encrypt(&Nonce::from(nonce.to_bytes()), payload)?)
+}
+
+/// Decrypts the ciphertext produced by [`encrypt_session`] with the same key,
+/// [`SessionNonce`] and associated data.
+pub fn decrypt_session(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: SessionNonce,
+    associated_data: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let payload = Payload {
+        msg: ciphertext,
+        aad: associated_data,
+    };
+    Ok(_cypher(key.as_ref())?.decrypt(&Nonce::from(nonce.to_bytes()), payload)?)
+}
+
+/// Decrypts the ciphertext of a message expected to have `plaintext_len`
+/// bytes, always running the full AEAD over `plaintext_len` bytes: shorter
+/// ciphertext is padded with zeros and longer one is truncated, failing the
+/// authentication. Thus, truncated ciphertext, ciphertext of a wrong length
+/// and an invalid tag are all processed in the same time and are reported
+/// with the same error.
+///
+/// # Errors
+///
+/// [`EncryptionError::ChaCha`] if the ciphertext length doesn't match or it
+/// can't be authenticated, or [`EncryptionError::InvalidKeyLen`] if the key
+/// is not 32 bytes long.
+pub fn decrypt_ct(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: u64,
+    associated_data: &[u8],
+    ciphertext: &[u8],
+    plaintext_len: usize,
+) -> Result<Vec<u8>, EncryptionError> {
+    let cypher = _cypher(key.as_ref())?;
+    let mut buf = vec![0u8; plaintext_len + TAG_SIZE];
+    let copied = ciphertext.len().min(buf.len());
+    buf[..copied].copy_from_slice(&ciphertext[..copied]);
+    let len_matches = ciphertext.len() == buf.len();
+    let (data, tag) = buf.split_at_mut(plaintext_len);
+    let tag = Tag::clone_from_slice(tag);
+    let authentic =
+        cypher.decrypt_in_place_detached(&_nonce(nonce), associated_data, data, &tag).is_ok();
+    // Non-short-circuiting to process both outcomes uniformly
+    if !(authentic & len_matches) {
+        buf.fill(0);
+        return Err(aead::Error.into());
+    }
+    buf.truncate(plaintext_len);
+    Ok(buf)
+}
+
+/// Decrypts the ciphertext followed by the authentication tag in place, without
+/// allocating, and returns the part of the `buf` containing the plaintext
+/// (i.e. the buffer minus the trailing tag).
+///
+/// # Errors
+///
+/// If the buffer is shorter than the tag or the ciphertext can't be
+/// authenticated. In this case the whole buffer is zeroed.
+pub fn decrypt_in_place_slice<'buf>(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: u64,
+    associated_data: &[u8],
+    buf: &'buf mut [u8],
+) -> Result<&'buf [u8], EncryptionError> {
+    let cypher = match _cypher(key.as_ref()) {
+        Ok(cypher) if buf.len() >= TAG_SIZE => cypher,
+        Ok(_) => {
+            buf.fill(0);
+            return Err(aead::Error.into());
+        }
+        Err(err) => {
+            buf.fill(0);
+            return Err(err);
+        }
+    };
+    let (data, tag) = buf.split_at_mut(buf.len() - TAG_SIZE);
+    let tag = Tag::clone_from_slice(tag);
+    if let Err(err) = cypher.decrypt_in_place_detached(&_nonce(nonce), associated_data, data, &tag)
+    {
+        buf.fill(0);
+        return Err(err.into());
+    }
+    Ok(&buf[..buf.len() - TAG_SIZE])
+}
+
+/// Decrypts the ciphertext like [`decrypt`], checking first that the
+/// associated data has the expected length, if provided. This allows to detect
+/// framing desynchronization before running the AEAD, which would fail with a
+/// generic authentication error.
+///
+/// # Errors
+///
+/// [`EncryptionError::InvalidAad`] if the length of the associated data
+/// doesn't match `expected_aad_len`.
+pub fn decrypt_checked(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: u64,
+    associated_data: &[u8],
+    expected_aad_len: Option<usize>,
+    ciphertext: &[u8],
+    plaintext: Option<&mut [u8]>,
+) -> Result<Vec<u8>, EncryptionError> {
+    match expected_aad_len {
+        Some(expected) if expected != associated_data.len() => Err(EncryptionError::InvalidAad {
+            expected,
+            found: associated_data.len(),
+        }),
+        _ => decrypt(key, nonce, associated_data, ciphertext, plaintext),
+    }
+}
+
+/// Pads the plaintext according to the padding `policy` and encrypts it like
+/// [`encrypt`]. The real plaintext length is stored as a 2-byte big-endian
+/// prefix inside the encrypted data, such that it is authenticated by the
+/// AEAD. If the policy doesn't pad messages, only the length prefix is added.
+///
+/// # Errors
+///
+/// [`EncryptionError::ExceedingMaxLength`] if the plaintext doesn't fit into
+/// the [`u16::MAX`] length prefix.
+pub fn encrypt_padded(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: u64,
+    associated_data: &[u8],
+    policy: PaddingPolicy,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let padded = match policy.pad(plaintext)? {
+        Some(padded) => padded,
+        None if plaintext.len() + PaddingPolicy::LEN_PREFIX_SIZE > u16::MAX as usize => {
+            return Err(EncryptionError::ExceedingMaxLength(plaintext.len()));
+        }
+        None => {
+            let mut data = Vec::with_capacity(PaddingPolicy::LEN_PREFIX_SIZE + plaintext.len());
+            data.extend_from_slice(&(plaintext.len() as u16).to_be_bytes());
+            data.extend_from_slice(plaintext);
+            data
+        }
+    };
+    encrypt(key, nonce, associated_data, &padded, None)
+}
+
+/// Decrypts the ciphertext produced by [`encrypt_padded`], reading the real
+/// plaintext length from the authenticated length prefix and returning only
+/// the real plaintext without the padding.
+///
+/// # Errors
+///
+/// [`EncryptionError::InvalidPadding`] if the declared length exceeds the
+/// size of the decrypted data.
+pub fn decrypt_padded(
+    key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: u64,
+    associated_data: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let mut decrypted = decrypt(key, nonce, associated_data, ciphertext, None)?;
+    let len = strip_padding(&decrypted)?.len();
+    decrypted.drain(..PaddingPolicy::LEN_PREFIX_SIZE);
+    decrypted.truncate(len);
+    Ok(decrypted)
+}
+
+/// Re-encrypts the ciphertext produced under the `old_key` with the
+/// `new_key`, keeping the same nonce and associated data. Used for rotating
+/// keys of the stored encrypted data. The intermediate plaintext is zeroized
+/// before the function returns.
+///
+/// # Errors
+///
+/// If the ciphertext can't be decrypted and authenticated with the old key.
+pub fn reencrypt(
+    old_key: &(impl AsRef<[u8]> + ?Sized),
+    new_key: &(impl AsRef<[u8]> + ?Sized),
+    nonce: u64,
+    associated_data: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let plaintext = Zeroizing::new(decrypt(old_key, nonce, associated_data, ciphertext, None)?);
+    encrypt(new_key, nonce, associated_data, &plaintext, None)
+}
+
 #[cfg(test)]
 mod test {
     use chacha20poly1305::aead::{Aead, AeadInPlace};
     use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
 
+    use super::*;
+
+    #[test]
+    fn key_forms() {
+        let key: crate::noise::SymmetricKey = [0x42u8; 32];
+        let key_ref: &crate::noise::SymmetricKey = &key;
+        let key_vec: Vec<u8> = vec![0x42u8; 32];
+        let expected = encrypt(&key, 1, b"aad", b"msg", None).unwrap();
+        assert_eq!(encrypt(key_ref, 1, b"aad", b"msg", None).unwrap(), expected);
+        assert_eq!(encrypt(&key[..], 1, b"aad", b"msg", None).unwrap(), expected);
+        assert_eq!(encrypt(&key_vec, 1, b"aad", b"msg", None).unwrap(), expected);
+        assert_eq!(decrypt(&key, 1, b"aad", &expected, None).unwrap(), b"msg");
+        assert_eq!(decrypt(key_ref, 1, b"aad", &expected, None).unwrap(), b"msg");
+        assert_eq!(decrypt(&key[..], 1, b"aad", &expected, None).unwrap(), b"msg");
+        assert_eq!(decrypt(&key_vec, 1, b"aad", &expected, None).unwrap(), b"msg");
+
+        for len in [0, 16, 31, 33, 64] {
+            let key = vec![0x42u8; len];
+            assert_eq!(
+                encrypt(&key, 1, b"aad", b"msg", None),
+                Err(EncryptionError::InvalidKeyLen(len))
+            );
+            assert_eq!(
+                decrypt(&key, 1, b"aad", &expected, None),
+                Err(EncryptionError::InvalidKeyLen(len))
+            );
+            let mut buf = expected.clone();
+            assert_eq!(
+                decrypt_in_place_slice(&key, 1, b"aad", &mut buf),
+                Err(EncryptionError::InvalidKeyLen(len))
+            );
+            assert_eq!(buf, vec![0u8; expected.len()]);
+        }
+    }
+
+    #[test]
+    fn in_place_slice() {
+        let key = [0x42u8; 32];
+        for msg in [&b""[..], b"msg", &[0xA5u8; 1000]] {
+            let mut buf = encrypt(&key, 3, b"aad", msg, None).unwrap();
+            let expected = decrypt(&key, 3, b"aad", &buf, None).unwrap();
+            let plaintext = decrypt_in_place_slice(&key, 3, b"aad", &mut buf).unwrap();
+            assert_eq!(plaintext, expected);
+            assert_eq!(plaintext.len(), buf.len() - TAG_SIZE);
+        }
+
+        let mut buf = encrypt(&key, 3, b"aad", b"msg", None).unwrap();
+        assert!(decrypt(&key, 4, b"aad", &buf, None).is_err());
+        assert!(decrypt_in_place_slice(&key, 4, b"aad", &mut buf).is_err());
+        assert_eq!(buf, [0u8; 3 + TAG_SIZE]);
+
+        let mut buf = [1u8; TAG_SIZE - 1];
+        assert!(decrypt_in_place_slice(&key, 0, &[], &mut buf).is_err());
+        assert_eq!(buf, [0u8; TAG_SIZE - 1]);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn plaintext_len_limit() {
+        assert_eq!(check_plaintext_len(0), Ok(()));
+        assert_eq!(check_plaintext_len(MAX_PLAINTEXT_LEN as usize), Ok(()));
+        assert_eq!(
+            check_plaintext_len(MAX_PLAINTEXT_LEN as usize + 1),
+            Err(EncryptionError::MessageTooLong(MAX_PLAINTEXT_LEN as usize + 1))
+        );
+        assert_eq!(
+            check_plaintext_len(usize::MAX),
+            Err(EncryptionError::MessageTooLong(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn nonce_layout() {
+        assert_eq!(nonce_bytes(0), [0u8; 12]);
+        assert_eq!(nonce_bytes(1), [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(nonce_bytes(0x0102_0304_0506_0708), [
+            0, 0, 0, 0, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01
+        ]);
+        assert_eq!(nonce_bytes(u64::MAX), [
+            0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+        ]);
+
+        // The nonce matches the one used by the encryption
+        let key = [0x42u8; 32];
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        let expected = cipher.encrypt(Nonce::from_slice(&nonce_bytes(7)), &b"msg"[..]).unwrap();
+        assert_eq!(encrypt(&key, 7, &[], b"msg", None).unwrap(), expected);
+    }
+
+    #[test]
+    fn decrypt_ct_agrees() {
+        let key = [0x42u8; 32];
+        let ciphertext = encrypt(&key, 3, b"aad", b"message", None).unwrap();
+        assert_eq!(decrypt_ct(&key, 3, b"aad", &ciphertext, 7).unwrap(), b"message");
+        assert_eq!(
+            decrypt_ct(&key, 3, b"aad", &ciphertext, 7),
+            decrypt(&key, 3, b"aad", &ciphertext, None)
+        );
+
+        let mut tampered = ciphertext.clone();
+        tampered[2] ^= 1;
+        let mut bad_tag = ciphertext.clone();
+        *bad_tag.last_mut().unwrap() ^= 1;
+        let mut extended = ciphertext.clone();
+        extended.push(0);
+        for (invalid, aad, nonce) in [
+            (&tampered[..], &b"aad"[..], 3),
+            (&bad_tag, b"aad", 3),
+            (&ciphertext, b"other", 3),
+            (&ciphertext, b"aad", 4),
+            (&ciphertext[..ciphertext.len() - 1], b"aad", 3),
+            (&ciphertext[..TAG_SIZE - 1], b"aad", 3),
+            (&[], b"aad", 3),
+            (&extended, b"aad", 3),
+        ] {
+            let expected = Err(EncryptionError::ChaCha(aead::Error));
+            assert_eq!(decrypt_ct(&key, nonce, aad, invalid, 7), expected);
+            assert_eq!(decrypt(&key, nonce, aad, invalid, None), expected);
+        }
+        assert_eq!(
+            decrypt_ct(&key[..31], 3, b"aad", &ciphertext, 7),
+            Err(EncryptionError::InvalidKeyLen(31))
+        );
+    }
+
+    #[test]
+    fn session_nonce() {
+        let key = [0x42u8; 32];
+        let mut first = SessionNonce::new([1, 2, 3, 4]);
+        let mut second = SessionNonce::new([1, 2, 3, 5]);
+        for counter in [0u64, 1, 2, 0xFFFF_FFFF, u64::MAX - 1] {
+            first.counter = counter;
+            second.counter = counter;
+            assert_ne!(first.to_bytes(), second.to_bytes());
+            assert_eq!(first.to_bytes()[..4], first.prefix);
+            assert_eq!(first.to_bytes()[4..], counter.to_le_bytes());
+
+            let encrypted = encrypt_session(&key, first, b"aad", b"msg").unwrap();
+            assert_ne!(encrypt_session(&key, second, b"aad", b"msg").unwrap(), encrypted);
+            assert_eq!(decrypt_session(&key, first, b"aad", &encrypted).unwrap(), b"msg");
+            assert!(decrypt_session(&key, second, b"aad", &encrypted).is_err());
+        }
+
+        // Session with zero prefix uses the Noise nonces
+        let zero = SessionNonce {
+            prefix: [0; 4],
+            counter: 7,
+        };
+        assert_eq!(<[u8; 12]>::from(zero), nonce_bytes(7));
+        assert_eq!(
+            encrypt_session(&key, zero, &[], b"msg").unwrap(),
+            encrypt(&key, 7, &[], b"msg", None).unwrap()
+        );
+
+        let mut nonce = SessionNonce::new([9; 4]);
+        assert_eq!(nonce.next_nonce().unwrap().counter, 0);
+        assert_eq!(nonce.next_nonce().unwrap().counter, 1);
+        nonce.counter = u64::MAX;
+        assert_eq!(nonce.next_nonce(), None);
+        assert_eq!(nonce.counter, u64::MAX);
+    }
+
+    /// Deterministic generator (SplitMix64) for reproducible session prefixes.
+    struct TestRng(u64);
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 { self.next_u64() as u32 }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for TestRng {}
+
+    /// Counts sessions which have the same random prefix as some earlier one.
+    fn prefix_collisions(rng: &mut TestRng, sessions: u64) -> usize {
+        let mut prefixes =
+            (0..sessions).map(|_| SessionNonce::with_rng(rng).prefix).collect::<Vec<_>>();
+        prefixes.sort_unstable();
+        prefixes.windows(2).filter(|pair| pair[0] == pair[1]).count()
+    }
+
+    #[test]
+    fn session_limit() {
+        // Far below the limit the prefixes are unique
+        for seed in 0..16 {
+            assert_eq!(prefix_collisions(&mut TestRng(seed), MAX_RANDOM_SESSIONS >> 8), 0);
+        }
+        // At the limit a large fraction of the keys reuse nonces, and above it
+        // every key does
+        let reused = (0..16)
+            .filter(|seed| prefix_collisions(&mut TestRng(*seed), MAX_RANDOM_SESSIONS) > 0)
+            .count();
+        assert!((2..=12).contains(&reused), "{reused}");
+        for seed in 0..4 {
+            assert!(prefix_collisions(&mut TestRng(seed), MAX_RANDOM_SESSIONS << 2) > 0);
+        }
+    }
+
+    #[test]
+    fn padded_round_trip() {
+        let key = [0x42u8; 32];
+        let aad = b"aad";
+        for policy in [
+            PaddingPolicy::None,
+            PaddingPolicy::FixedBucket(1),
+            PaddingPolicy::FixedBucket(16),
+            PaddingPolicy::FixedBucket(256),
+            PaddingPolicy::FixedBucket(1024),
+            PaddingPolicy::PowerOfTwo,
+        ] {
+            for len in [0usize, 1, 14, 15, 16, 255, 1000, 4096] {
+                let msg = vec![0xA5u8; len];
+                let encrypted = encrypt_padded(&key, 1, aad, policy, &msg).unwrap();
+                let padded_len = policy.padded_len(len).unwrap().unwrap_or(len + 2);
+                assert_eq!(encrypted.len(), padded_len + TAG_SIZE);
+                assert_eq!(decrypt_padded(&key, 1, aad, &encrypted).unwrap(), msg);
+            }
+        }
+    }
+
+    #[test]
+    fn padded_invalid_len() {
+        let key = [0x42u8; 32];
+        let mut data = 100u16.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 10]);
+        let encrypted = encrypt(&key, 0, &[], &data, None).unwrap();
+        assert_eq!(
+            decrypt_padded(&key, 0, &[], &encrypted),
+            Err(EncryptionError::InvalidPadding {
+                declared: 100,
+                found: 10
+            })
+        );
+        let encrypted = encrypt(&key, 0, &[], &[1], None).unwrap();
+        assert_eq!(
+            decrypt_padded(&key, 0, &[], &encrypted),
+            Err(EncryptionError::InvalidPadding {
+                declared: 0,
+                found: 1
+            })
+        );
+
+        // The length prefix is authenticated
+        let mut encrypted =
+            encrypt_padded(&key, 0, &[], PaddingPolicy::PowerOfTwo, b"msg").unwrap();
+        encrypted[0] ^= 0x01;
+        assert!(decrypt_padded(&key, 0, &[], &encrypted).is_err());
+    }
+
+    #[test]
+    fn reencrypt_rotates_key() {
+        let old_key = [0x01u8; 32];
+        let new_key = [0x02u8; 32];
+        let aad = b"record id";
+        let encrypted = encrypt(&old_key, 5, aad, b"stored data", None).unwrap();
+        let reencrypted = reencrypt(&old_key, &new_key, 5, aad, &encrypted).unwrap();
+        assert_ne!(reencrypted, encrypted);
+        assert_eq!(decrypt(&new_key, 5, aad, &reencrypted, None).unwrap(), b"stored data");
+        assert!(decrypt(&old_key, 5, aad, &reencrypted, None).is_err());
+
+        // Data must be authenticated with the old key
+        assert!(reencrypt(&new_key, &old_key, 5, aad, &encrypted).is_err());
+        assert!(reencrypt(&old_key, &new_key, 5, b"other", &encrypted).is_err());
+    }
+
+    #[test]
+    fn decrypt_checked_aad_len() {
+        let key = [0x42u8; 32];
+        let aad = 7u64.to_be_bytes();
+        let encrypted = encrypt(&key, 0, &aad, b"message", None).unwrap();
+        assert_eq!(decrypt_checked(&key, 0, &aad, Some(8), &encrypted, None).unwrap(), b"message");
+        assert_eq!(decrypt_checked(&key, 0, &aad, None, &encrypted, None).unwrap(), b"message");
+        assert_eq!(
+            decrypt_checked(&key, 0, &aad[..4], Some(8), &encrypted, None),
+            Err(EncryptionError::InvalidAad {
+                expected: 8,
+                found: 4
+            })
+        );
+        // Without the check the same error is reported as generic AEAD failure
+        assert_eq!(
+            decrypt_checked(&key, 0, &aad[..4], None, &encrypted, None),
+            Err(EncryptionError::ChaCha(chacha20poly1305::aead::Error))
+        );
+    }
+
     #[test]
     fn test1() {
         // Encrypt decrypt a plain text
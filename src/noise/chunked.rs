@@ -0,0 +1,267 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Chunked encryption of data streams with ChaCha20Poly1305 AEAD.
+//!
+//! The stream is split into chunks, each encrypted under the same key with
+//! the chunk number used as the nonce. Each encrypted chunk starts with a
+//! single byte marking whether it is the final chunk of the stream, which is
+//! authenticated as the chunk associated data. This allows the receiver to
+//! detect reordered, dropped and truncated chunks. A key must be used for a
+//! single stream only.
+
+use alloc::vec::Vec;
+
+use super::{chacha, EncryptionError, SymmetricKey};
+
+const CHUNK: u8 = 0x00;
+const FINAL_CHUNK: u8 = 0x01;
+
+/// Size of the final chunk marker prefixing each encrypted chunk.
+pub const CHUNK_MARKER_SIZE: usize = 1;
+
+/// Encryptor of a chunked data stream.
+#[derive(Clone, Debug)]
+pub struct StreamEncryptor {
+    key: SymmetricKey,
+    counter: u64,
+    finished: bool,
+}
+
+impl StreamEncryptor {
+    /// Constructs encryptor for a new stream. The key must not be used for any
+    /// other stream.
+    pub fn new(key: SymmetricKey) -> Self {
+        StreamEncryptor {
+            key,
+            counter: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns number of the chunks encrypted so far.
+    pub fn counter(&self) -> u64 { self.counter }
+
+    /// Encrypts next chunk of the stream. The last chunk of the stream must be
+    /// encrypted with `last` set to `true`.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::StreamFinished`] if the final chunk was already
+    /// encrypted.
+    pub fn encrypt_chunk(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>, EncryptionError> {
+        if self.finished {
+            return Err(EncryptionError::StreamFinished);
+        }
+        let marker = if last { FINAL_CHUNK } else { CHUNK };
+        let encrypted = chacha::encrypt(&self.key, self.counter, &[marker], chunk, None)?;
+        self.counter += 1;
+        self.finished = last;
+
+        let mut data = Vec::with_capacity(CHUNK_MARKER_SIZE + encrypted.len());
+        data.push(marker);
+        data.extend_from_slice(&encrypted);
+        Ok(data)
+    }
+}
+
+/// Moment when the decrypted chunk plaintext is released to the caller by
+/// [`StreamDecryptor`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum ChunkRelease {
+    /// Plaintext of each chunk is released as soon as the chunk is decrypted.
+    /// If the stream gets truncated, the data which were already returned are
+    /// not the complete stream; the truncation is reported only by
+    /// [`StreamDecryptor::finish`].
+    #[default]
+    Immediate,
+
+    /// Plaintext of the most recent chunk is withheld until either the next
+    /// chunk is received (proving the chunk is not the last one) or the chunk
+    /// is marked as final. A truncated stream never has its last received chunk
+    /// released as if it completes the stream. This delays the data by one
+    /// chunk and requires buffering of a single chunk plaintext.
+    Authenticated,
+}
+
+/// Decryptor of a chunked data stream produced by [`StreamEncryptor`].
+#[derive(Clone, Debug)]
+pub struct StreamDecryptor {
+    key: SymmetricKey,
+    counter: u64,
+    finished: bool,
+    release: ChunkRelease,
+    pending: Vec<u8>,
+}
+
+impl StreamDecryptor {
+    /// Constructs decryptor for a new stream using the given plaintext
+    /// `release` mode.
+    pub fn new(key: SymmetricKey, release: ChunkRelease) -> Self {
+        StreamDecryptor {
+            key,
+            counter: 0,
+            finished: false,
+            release,
+            pending: vec![],
+        }
+    }
+
+    /// Returns number of chunks decrypted so far.
+    pub fn counter(&self) -> u64 { self.counter }
+
+    /// Detects whether the final chunk of the stream was decrypted.
+    pub fn is_finished(&self) -> bool { self.finished }
+
+    /// Decrypts next chunk of the stream, returning plaintext which can be
+    /// released to the application according to the [`ChunkRelease`] mode. The
+    /// returned data may be empty.
+    ///
+    /// # Errors
+    ///
+    /// If the chunk can't be authenticated (for instance, since it was
+    /// reordered or replayed), [`EncryptionError::InvalidChunk`] if it is
+    /// too short to be a chunk, and [`EncryptionError::StreamFinished`] if the
+    /// final chunk was already decrypted.
+    pub fn decrypt_chunk(&mut self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if self.finished {
+            return Err(EncryptionError::StreamFinished);
+        }
+        let (marker, encrypted) = match chunk {
+            [marker @ (CHUNK | FINAL_CHUNK), encrypted @ ..]
+                if encrypted.len() >= chacha::TAG_SIZE =>
+            {
+                (*marker, encrypted)
+            }
+            _ => return Err(EncryptionError::InvalidChunk(chunk.len())),
+        };
+        let plaintext = chacha::decrypt(&self.key, self.counter, &[marker], encrypted, None)?;
+        self.counter += 1;
+        self.finished = marker == FINAL_CHUNK;
+
+        match self.release {
+            ChunkRelease::Immediate => Ok(plaintext),
+            ChunkRelease::Authenticated if self.finished => {
+                let mut released = core::mem::take(&mut self.pending);
+                released.extend_from_slice(&plaintext);
+                Ok(released)
+            }
+            ChunkRelease::Authenticated => Ok(core::mem::replace(&mut self.pending, plaintext)),
+        }
+    }
+
+    /// Completes decryption of the stream once no more chunks are available.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::UnterminatedStream`] if the final chunk was not
+    /// received, i.e. the stream was truncated. In this case the withheld
+    /// plaintext is discarded.
+    pub fn finish(self) -> Result<(), EncryptionError> {
+        if !self.finished {
+            return Err(EncryptionError::UnterminatedStream(self.counter));
+        }
+        debug_assert!(self.pending.is_empty());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: SymmetricKey = [0x42u8; 32];
+
+    fn encrypt_stream(chunks: &[&[u8]]) -> Vec<Vec<u8>> {
+        let mut encryptor = StreamEncryptor::new(KEY);
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(no, chunk)| encryptor.encrypt_chunk(chunk, no == chunks.len() - 1).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let chunks: [&[u8]; 3] = [b"first", b"", b"last"];
+        for release in [ChunkRelease::Immediate, ChunkRelease::Authenticated] {
+            let mut decryptor = StreamDecryptor::new(KEY, release);
+            let mut data = vec![];
+            for chunk in encrypt_stream(&chunks) {
+                data.extend(decryptor.decrypt_chunk(&chunk).unwrap());
+            }
+            assert!(decryptor.is_finished());
+            decryptor.finish().unwrap();
+            assert_eq!(data, b"firstlast");
+        }
+    }
+
+    #[test]
+    fn authenticated_release() {
+        let chunks = encrypt_stream(&[b"one", b"two", b"three"]);
+        let mut decryptor = StreamDecryptor::new(KEY, ChunkRelease::Authenticated);
+        assert_eq!(decryptor.decrypt_chunk(&chunks[0]).unwrap(), b"");
+        assert_eq!(decryptor.decrypt_chunk(&chunks[1]).unwrap(), b"one");
+        assert_eq!(decryptor.decrypt_chunk(&chunks[2]).unwrap(), b"twothree");
+        assert_eq!(decryptor.decrypt_chunk(&chunks[2]), Err(EncryptionError::StreamFinished));
+    }
+
+    #[test]
+    fn truncated() {
+        let chunks = encrypt_stream(&[b"one", b"two", b"three"]);
+        let mut decryptor = StreamDecryptor::new(KEY, ChunkRelease::Authenticated);
+        let mut data = vec![];
+        for chunk in &chunks[..2] {
+            data.extend(decryptor.decrypt_chunk(chunk).unwrap());
+        }
+        assert_eq!(data, b"one");
+        assert_eq!(decryptor.finish(), Err(EncryptionError::UnterminatedStream(2)));
+
+        let mut decryptor = StreamDecryptor::new(KEY, ChunkRelease::Immediate);
+        for chunk in &chunks[..2] {
+            decryptor.decrypt_chunk(chunk).unwrap();
+        }
+        assert_eq!(decryptor.finish(), Err(EncryptionError::UnterminatedStream(2)));
+    }
+
+    #[test]
+    fn tampered() {
+        let chunks = encrypt_stream(&[b"one", b"two", b"three"]);
+
+        // Reordered chunks
+        let mut decryptor = StreamDecryptor::new(KEY, ChunkRelease::Authenticated);
+        assert!(decryptor.decrypt_chunk(&chunks[1]).is_err());
+
+        // Non-final chunk marked as final
+        let mut forged = chunks[0].clone();
+        forged[0] = FINAL_CHUNK;
+        let mut decryptor = StreamDecryptor::new(KEY, ChunkRelease::Authenticated);
+        assert!(decryptor.decrypt_chunk(&forged).is_err());
+
+        let mut decryptor = StreamDecryptor::new(KEY, ChunkRelease::Authenticated);
+        assert_eq!(decryptor.decrypt_chunk(&[CHUNK; 16]), Err(EncryptionError::InvalidChunk(16)));
+        assert_eq!(decryptor.decrypt_chunk(&[0x02; 17]), Err(EncryptionError::InvalidChunk(17)));
+
+        let mut encryptor = StreamEncryptor::new(KEY);
+        encryptor.encrypt_chunk(b"last", true).unwrap();
+        assert_eq!(encryptor.encrypt_chunk(b"more", false), Err(EncryptionError::StreamFinished));
+    }
+}
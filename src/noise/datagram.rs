@@ -0,0 +1,255 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encryption of datagrams which may arrive out of order, with a sliding
+//! window replay protection (like in DTLS, RFC 6347 section 4.1.2.6).
+//!
+//! Each datagram is prefixed with its 8-byte big-endian sequence number, which
+//! is used as the AEAD nonce and authenticated as the associated data. A key
+//! must be used for a single datagram flow only.
+
+use alloc::vec::Vec;
+
+use super::{chacha, EncryptionError, SymmetricKey};
+
+/// Size of the sequence number prefixing each datagram.
+pub const SEQUENCE_SIZE: usize = 8;
+
+/// Sliding window filter accepting each sequence number once, as long as it
+/// is not older than the window size relative to the highest sequence number
+/// accepted so far.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ReplayWindow {
+    size: u32,
+    highest: Option<u64>,
+    // Bit `n` is set if sequence number `highest - n` was accepted
+    bitmap: u128,
+}
+
+impl ReplayWindow {
+    /// Maximal supported window size.
+    pub const MAX_SIZE: u32 = 128;
+
+    /// Constructs window of the given size.
+    ///
+    /// # Panics
+    ///
+    /// If the size is zero or exceeds [`ReplayWindow::MAX_SIZE`].
+    pub fn new(size: u32) -> Self {
+        assert!(
+            size > 0 && size <= Self::MAX_SIZE,
+            "replay window size must be in range 1..={}",
+            Self::MAX_SIZE
+        );
+        ReplayWindow {
+            size,
+            highest: None,
+            bitmap: 0,
+        }
+    }
+
+    /// Returns size of the window.
+    pub fn size(&self) -> u32 { self.size }
+
+    /// Returns the highest sequence number accepted so far.
+    pub fn highest(&self) -> Option<u64> { self.highest }
+
+    /// Checks whether the sequence number would be accepted, without
+    /// registering it.
+    pub fn check(&self, seq: u64) -> bool {
+        match self.highest {
+            None => true,
+            Some(highest) if seq > highest => true,
+            Some(highest) => {
+                let age = highest - seq;
+                age < self.size as u64 && self.bitmap & (1 << age) == 0
+            }
+        }
+    }
+
+    /// Registers the sequence number, returning `false` if it was already
+    /// accepted before or is too old to be checked for duplicates.
+    pub fn accept(&mut self, seq: u64) -> bool {
+        if !self.check(seq) {
+            return false;
+        }
+        match self.highest {
+            Some(highest) if seq <= highest => self.bitmap |= 1 << (highest - seq),
+            Some(highest) => {
+                let shift = seq - highest;
+                self.bitmap = if shift >= Self::MAX_SIZE as u64 { 0 } else { self.bitmap << shift };
+                self.bitmap |= 1;
+                self.highest = Some(seq);
+            }
+            None => {
+                self.bitmap = 1;
+                self.highest = Some(seq);
+            }
+        }
+        true
+    }
+}
+
+/// Encryptor of datagrams, assigning them consecutive sequence numbers.
+#[derive(Clone, Debug)]
+pub struct DatagramEncryptor {
+    key: SymmetricKey,
+    next_seq: u64,
+}
+
+impl DatagramEncryptor {
+    /// Constructs encryptor for a new datagram flow.
+    pub fn new(key: SymmetricKey) -> Self { DatagramEncryptor { key, next_seq: 0 } }
+
+    /// Returns sequence number which will be used for the next datagram.
+    pub fn next_seq(&self) -> u64 { self.next_seq }
+
+    /// Encrypts the message into a datagram prefixed with its sequence number.
+    pub fn encrypt(&mut self, msg: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let seq = self.next_seq.to_be_bytes();
+        let encrypted = chacha::encrypt(&self.key, self.next_seq, &seq, msg, None)?;
+        self.next_seq += 1;
+
+        let mut datagram = Vec::with_capacity(SEQUENCE_SIZE + encrypted.len());
+        datagram.extend_from_slice(&seq);
+        datagram.extend_from_slice(&encrypted);
+        Ok(datagram)
+    }
+}
+
+/// Decryptor of datagrams produced by [`DatagramEncryptor`], accepting
+/// reordered datagrams within a [`ReplayWindow`] and rejecting duplicates.
+#[derive(Clone, Debug)]
+pub struct DatagramDecryptor {
+    key: SymmetricKey,
+    window: ReplayWindow,
+}
+
+impl DatagramDecryptor {
+    /// Constructs decryptor with the replay window of the given size.
+    ///
+    /// # Panics
+    ///
+    /// If the window size is zero or exceeds [`ReplayWindow::MAX_SIZE`].
+    pub fn new(key: SymmetricKey, window_size: u32) -> Self {
+        DatagramDecryptor {
+            key,
+            window: ReplayWindow::new(window_size),
+        }
+    }
+
+    /// Returns the replay window state.
+    pub fn window(&self) -> &ReplayWindow { &self.window }
+
+    /// Decrypts the datagram, returning its sequence number and the message.
+    /// The sequence number is registered by the replay window only if the
+    /// datagram is authentic.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::InvalidDatagram`] if the datagram is too short,
+    /// [`EncryptionError::Replayed`] if the sequence number was already
+    /// accepted or is outside of the window, and an AEAD error if the datagram
+    /// can't be authenticated.
+    pub fn decrypt(&mut self, datagram: &[u8]) -> Result<(u64, Vec<u8>), EncryptionError> {
+        if datagram.len() < SEQUENCE_SIZE + chacha::TAG_SIZE {
+            return Err(EncryptionError::InvalidDatagram(datagram.len()));
+        }
+        let (seq_bytes, encrypted) = datagram.split_at(SEQUENCE_SIZE);
+        let mut seq = [0u8; SEQUENCE_SIZE];
+        seq.copy_from_slice(seq_bytes);
+        let seq_no = u64::from_be_bytes(seq);
+        if !self.window.check(seq_no) {
+            return Err(EncryptionError::Replayed(seq_no));
+        }
+        let msg = chacha::decrypt(&self.key, seq_no, &seq, encrypted, None)?;
+        self.window.accept(seq_no);
+        Ok((seq_no, msg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn window() {
+        let mut window = ReplayWindow::new(64);
+        assert!(window.accept(10));
+        assert!(!window.accept(10));
+        // In-window out-of-order sequences
+        assert!(window.accept(5));
+        assert!(window.accept(100));
+        assert!(window.accept(37));
+        assert!(window.accept(99));
+        assert!(!window.accept(99));
+        assert!(!window.accept(37));
+        // Too old
+        assert!(!window.check(36));
+        assert!(!window.accept(5));
+        assert_eq!(window.highest(), Some(100));
+
+        // Large jump clears the window
+        assert!(window.accept(1000));
+        assert!(window.accept(999));
+        assert!(!window.accept(100));
+    }
+
+    #[test]
+    fn window_size() {
+        let mut window = ReplayWindow::new(1);
+        assert!(window.accept(0));
+        assert!(!window.accept(0));
+        assert!(window.accept(2));
+        assert!(!window.accept(1));
+
+        let mut window = ReplayWindow::new(ReplayWindow::MAX_SIZE);
+        assert!(window.accept(200));
+        assert!(window.accept(73));
+        assert!(!window.accept(72));
+    }
+
+    #[test]
+    #[should_panic(expected = "replay window size must be in range 1..=128")]
+    fn window_empty() { ReplayWindow::new(0); }
+
+    #[test]
+    fn datagrams() {
+        let key = [0x42u8; 32];
+        let mut encryptor = DatagramEncryptor::new(key);
+        let mut decryptor = DatagramDecryptor::new(key, 16);
+        let datagrams = (0u8..4).map(|i| encryptor.encrypt(&[i; 4]).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(decryptor.decrypt(&datagrams[2]).unwrap(), (2, vec![2; 4]));
+        assert_eq!(decryptor.decrypt(&datagrams[0]).unwrap(), (0, vec![0; 4]));
+        assert_eq!(decryptor.decrypt(&datagrams[3]).unwrap(), (3, vec![3; 4]));
+        assert_eq!(decryptor.decrypt(&datagrams[2]), Err(EncryptionError::Replayed(2)));
+        assert_eq!(decryptor.decrypt(&datagrams[1]).unwrap(), (1, vec![1; 4]));
+
+        // Forged sequence number fails authentication and is not registered
+        let mut forged = datagrams[0].clone();
+        forged[..SEQUENCE_SIZE].copy_from_slice(&5u64.to_be_bytes());
+        assert!(decryptor.decrypt(&forged).is_err());
+        assert!(decryptor.window().check(5));
+
+        assert_eq!(decryptor.decrypt(&[0u8; 23]), Err(EncryptionError::InvalidDatagram(23)));
+    }
+}
@@ -19,7 +19,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(debug_assertions)]
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
 use ed25519::x25519::PublicKey;
+use rand_core::{CryptoRng, RngCore};
 
 use super::hkdf::sha2_256 as hkdf;
 use super::{chacha, EncryptionError, SymmetricKey};
@@ -27,11 +32,181 @@ use crate::noise::{Handshake, HandshakeError};
 
 pub const KEY_ROTATION_PERIOD: u32 = 1000;
 
+/// Type of the frame, encoded as the first byte of the encrypted frame
+/// plaintext when heartbeats are enabled (see
+/// [`NoiseEncryptor::set_heartbeat_len`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
+pub enum FrameType {
+    /// Frame carrying application data.
+    Data = 0,
+
+    /// Heartbeat frame carrying only random padding, which is dropped by the
+    /// receiver.
+    Heartbeat = 1,
+}
+
+impl TryFrom<u8> for FrameType {
+    type Error = EncryptionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FrameType::Data),
+            1 => Ok(FrameType::Heartbeat),
+            unknown => Err(EncryptionError::UnknownFrameType(unknown)),
+        }
+    }
+}
+
+/// Debug-build detector of nonce reuse for the same encryption key, tracking a
+/// bounded number of recently used nonces. In release builds it has no fields
+/// and all checks are compiled out.
+#[derive(Clone, Debug, Default)]
+pub(in crate::noise) struct NonceTracker {
+    #[cfg(debug_assertions)]
+    key: SymmetricKey,
+    #[cfg(debug_assertions)]
+    recent: VecDeque<u64>,
+}
+
+impl NonceTracker {
+    #[cfg(debug_assertions)]
+    const CAPACITY: usize = 64;
+
+    /// Registers use of the `nonce` with the `key`.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, if the nonce was recently used with the same key.
+    #[cfg(debug_assertions)]
+    fn track(&mut self, key: &SymmetricKey, nonce: u64) {
+        if &self.key != key {
+            self.key = *key;
+            self.recent.clear();
+        }
+        debug_assert!(!self.recent.contains(&nonce), "nonce {nonce} is reused with the same key");
+        if self.recent.len() == Self::CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(nonce);
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline(always)]
+    fn track(&mut self, _key: &SymmetricKey, _nonce: u64) {}
+}
+
+/// Policy for padding plaintext messages before their encryption, hiding the
+/// real message length from the network observers.
+///
+/// With any policy other than [`PaddingPolicy::None`], the encrypted part of
+/// the frame contains 2-byte big-endian real message length, followed by the
+/// message and zero bytes up to the padded size. Since the length and the
+/// padding are encrypted, they are authenticated by the AEAD. Both peers must
+/// use the same (padded or non-padded) policy.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum PaddingPolicy {
+    /// Messages are not padded and are encrypted as is (BOLT-8 compatible).
+    #[default]
+    None,
+
+    /// Messages are padded to the nearest multiple of the bucket size.
+    FixedBucket(usize),
+
+    /// Messages are padded to the nearest power of two.
+    PowerOfTwo,
+}
+
+impl PaddingPolicy {
+    /// Size of the real message length prefix added to the padded messages.
+    pub const LEN_PREFIX_SIZE: usize = 2;
+
+    /// Detects whether messages are padded under the policy.
+    pub fn is_padded(self) -> bool { self != PaddingPolicy::None }
+
+    /// Computes length of the plaintext (including real message length prefix
+    /// and padding) for a message of `len` bytes, or `None` if the policy
+    /// doesn't pad messages.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::ExceedingMaxLength`] if the padded length exceeds
+    /// the maximum frame message length ([`u16::MAX`]). The size is not clamped
+    /// to the limit, since the clamped size doesn't belong to any bucket.
+    pub fn padded_len(self, len: usize) -> Result<Option<usize>, EncryptionError> {
+        let prefixed = len + Self::LEN_PREFIX_SIZE;
+        let padded = match self {
+            PaddingPolicy::None => return Ok(None),
+            PaddingPolicy::FixedBucket(bucket) => {
+                let bucket = bucket.max(1);
+                prefixed.saturating_add(bucket - 1) / bucket * bucket
+            }
+            PaddingPolicy::PowerOfTwo => prefixed.checked_next_power_of_two().unwrap_or(usize::MAX),
+        };
+        if padded > u16::MAX as usize {
+            return Err(EncryptionError::ExceedingMaxLength(len));
+        }
+        Ok(Some(padded.max(prefixed)))
+    }
+
+    /// Pads the message according to the policy. If the policy doesn't pad
+    /// messages, returns `None`.
+    pub fn pad(self, msg: &[u8]) -> Result<Option<Vec<u8>>, EncryptionError> {
+        let Some(padded_len) = self.padded_len(msg.len())? else {
+            return Ok(None);
+        };
+        let mut padded = vec![0u8; padded_len];
+        padded[..Self::LEN_PREFIX_SIZE].copy_from_slice(&(msg.len() as u16).to_be_bytes());
+        padded[Self::LEN_PREFIX_SIZE..Self::LEN_PREFIX_SIZE + msg.len()].copy_from_slice(msg);
+        Ok(Some(padded))
+    }
+
+    /// Strips padding from the decrypted data, returning the real message. If
+    /// the policy doesn't pad messages, returns the data unmodified.
+    pub fn unpad(self, data: &[u8]) -> Result<&[u8], EncryptionError> {
+        if !self.is_padded() {
+            return Ok(data);
+        }
+        strip_padding(data)
+    }
+}
+
+/// Strips the real message length prefix and padding from the decrypted data,
+/// returning the real message.
+pub(in crate::noise) fn strip_padding(data: &[u8]) -> Result<&[u8], EncryptionError> {
+    if data.len() < PaddingPolicy::LEN_PREFIX_SIZE {
+        return Err(EncryptionError::InvalidPadding {
+            declared: 0,
+            found: data.len(),
+        });
+    }
+    let (len, rest) = data.split_at(PaddingPolicy::LEN_PREFIX_SIZE);
+    let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+    if len > rest.len() {
+        return Err(EncryptionError::InvalidPadding {
+            declared: len,
+            found: rest.len(),
+        });
+    }
+    Ok(&rest[..len])
+}
+
 #[derive(Clone, Debug)]
 pub struct NoiseEncryptor {
     pub(in crate::noise) sending_key: SymmetricKey,
     pub(in crate::noise) sending_chaining_key: SymmetricKey,
     pub(in crate::noise) sending_nonce: u32,
+    pub(in crate::noise) key_rotation_period: u32,
+    pub(in crate::noise) padding_policy: PaddingPolicy,
+    pub(in crate::noise) output_buffer: Vec<u8>,
+    pub(in crate::noise) high_water_mark: Option<usize>,
+    pub(in crate::noise) nonce_tracker: NonceTracker,
+    pub(in crate::noise) sequence: u64,
+    pub(in crate::noise) sequence_aad: bool,
+    /// Domain separation prefix of the associated data followed by the space
+    /// for the sequence number (see [`frame_aad`]), or empty without prefix.
+    pub(in crate::noise) aad: Vec<u8>,
+    pub(in crate::noise) heartbeat_len: Option<u16>,
     pub(in crate::noise) remote_pubkey: PublicKey,
 }
 
@@ -39,7 +214,179 @@ impl NoiseEncryptor {
     pub const TAGGED_MESSAGE_LENGTH_HEADER_SIZE: usize = Self::MESSAGE_LEN_SIZE + chacha::TAG_SIZE;
     const MESSAGE_LEN_SIZE: usize = 2;
 
+    pub(in crate::noise) fn new(
+        sending_key: SymmetricKey,
+        chaining_key: SymmetricKey,
+        remote_pubkey: PublicKey,
+    ) -> Self {
+        NoiseEncryptor {
+            sending_key,
+            sending_chaining_key: chaining_key,
+            sending_nonce: 0,
+            key_rotation_period: KEY_ROTATION_PERIOD,
+            padding_policy: PaddingPolicy::None,
+            output_buffer: Vec::new(),
+            high_water_mark: None,
+            nonce_tracker: NonceTracker::default(),
+            sequence: 0,
+            sequence_aad: false,
+            aad: vec![],
+            heartbeat_len: None,
+            remote_pubkey,
+        }
+    }
+
+    /// Returns padding policy used for the encrypted messages.
+    pub fn padding_policy(&self) -> PaddingPolicy { self.padding_policy }
+
+    /// Sets padding policy for the messages encrypted after the call.
+    pub fn set_padding_policy(&mut self, policy: PaddingPolicy) { self.padding_policy = policy }
+
+    /// Returns number of nonces after which the sending key is rotated. Each
+    /// message uses two nonces: for its length header and for the body.
+    pub fn key_rotation_period(&self) -> u32 { self.key_rotation_period }
+
+    /// Sets number of nonces after which the sending key is rotated; defaults
+    /// to [`KEY_ROTATION_PERIOD`]. Both peers must use the same period.
+    ///
+    /// # Panics
+    ///
+    /// If the period is zero or is less than the nonces already used with the
+    /// current key.
+    pub fn set_key_rotation_period(&mut self, period: u32) {
+        assert!(period > self.sending_nonce, "key rotation period must exceed the used nonces");
+        self.key_rotation_period = period
+    }
+
+    /// Returns number of messages which can be encrypted before the sending
+    /// key is rotated, including the message during which the rotation
+    /// happens. Allows to rotate keys proactively during idle periods.
+    pub fn messages_until_rekey(&self) -> u64 {
+        messages_until_rekey(self.sending_nonce, self.key_rotation_period)
+    }
+
+    /// Returns number of frames encrypted so far.
+    pub fn sequence(&self) -> u64 { self.sequence }
+
+    /// Detects whether the frame sequence number is included into the
+    /// associated data of the encrypted frames.
+    pub fn sequence_aad(&self) -> bool { self.sequence_aad }
+
+    /// Enables or disables inclusion of the frame sequence number (as 8-byte
+    /// big-endian integer) into the associated data of the frames encrypted
+    /// after the call, such that reordered or replayed frames fail
+    /// authentication. Both peers must use the same mode.
+    pub fn set_sequence_aad(&mut self, enabled: bool) { self.sequence_aad = enabled }
+
+    /// Returns domain separation prefix of the associated data.
+    pub fn aad_prefix(&self) -> &[u8] { aad_prefix(&self.aad) }
+
+    /// Sets domain separation prefix (like the protocol name and version)
+    /// which is prepended to the associated data of the frames encrypted after
+    /// the call, such that the frames can't be accepted by a protocol using a
+    /// different prefix even with the same keys. Both peers must use the same
+    /// prefix.
+    pub fn set_aad_prefix(&mut self, prefix: impl Into<Vec<u8>>) {
+        self.aad = with_sequence_space(prefix.into())
+    }
+
+    /// Returns length of the random padding of the heartbeat frames, if the
+    /// heartbeats are enabled.
+    pub fn heartbeat_len(&self) -> Option<u16> { self.heartbeat_len }
+
+    /// Enables heartbeat frames carrying `len` bytes of random padding, or
+    /// disables them with `None`. With heartbeats enabled, the plaintext of
+    /// each frame encrypted after the call starts with the [`FrameType`]
+    /// byte, thus the remote peer must enable heartbeats with
+    /// [`NoiseDecryptor::set_heartbeats`].
+    pub fn set_heartbeat_len(&mut self, len: Option<u16>) { self.heartbeat_len = len }
+
+    /// Returns high-water mark of the internal output buffer used by
+    /// [`NoiseEncryptor::encrypt_buffered`], if any.
+    pub fn high_water_mark(&self) -> Option<usize> { self.high_water_mark }
+
+    /// Sets high-water mark for the internal output buffer, enabling bounded
+    /// buffer mode. `None` makes the buffer unbounded.
+    pub fn set_high_water_mark(&mut self, high_water_mark: Option<usize>) {
+        self.high_water_mark = high_water_mark
+    }
+
+    /// Encrypts the message and appends it to the internal output buffer.
+    ///
+    /// # Errors
+    ///
+    /// If the buffer has already reached the high-water mark, returns
+    /// [`EncryptionError::WouldBlock`] without encrypting the message; the
+    /// caller has to flush the buffer using [`NoiseEncryptor::buffered`] and
+    /// [`NoiseEncryptor::consume`] before trying again. Since the check is
+    /// performed before encryption, the buffer may exceed the high-water mark
+    /// by at most a single message.
+    pub fn encrypt_buffered(&mut self, buffer: &[u8]) -> Result<(), EncryptionError> {
+        if let Some(high_water_mark) = self.high_water_mark {
+            if self.output_buffer.len() >= high_water_mark {
+                return Err(EncryptionError::WouldBlock(high_water_mark));
+            }
+        }
+        let ciphertext = self.encrypt_buf(buffer)?;
+        self.output_buffer.extend_from_slice(&ciphertext);
+        Ok(())
+    }
+
+    /// Returns encrypted data from the internal output buffer which has to be
+    /// sent to the remote peer.
+    pub fn buffered(&self) -> &[u8] { &self.output_buffer }
+
+    /// Removes `len` bytes which were sent to the remote peer from the
+    /// beginning of the internal output buffer.
+    ///
+    /// # Panics
+    ///
+    /// If `len` exceeds the length of the buffered data.
+    pub fn consume(&mut self, len: usize) { self.output_buffer.drain(..len); }
+
     pub fn encrypt_buf(&mut self, buffer: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if self.heartbeat_len.is_none() {
+            return self.encrypt_frame(buffer);
+        }
+        let mut typed = Vec::with_capacity(1 + buffer.len());
+        typed.push(FrameType::Data as u8);
+        typed.extend_from_slice(buffer);
+        self.encrypt_frame(&typed)
+    }
+
+    /// Encrypts heartbeat frame with random padding of the configured length
+    /// using the operating system random number generator.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::HeartbeatsDisabled`] if heartbeats are not enabled
+    /// with [`NoiseEncryptor::set_heartbeat_len`].
+    #[cfg(feature = "std")]
+    pub fn encrypt_heartbeat(&mut self) -> Result<Vec<u8>, EncryptionError> {
+        self.encrypt_heartbeat_with(&mut rand_core::OsRng)
+    }
+
+    /// Encrypts heartbeat frame with random padding of the configured length
+    /// generated by the provided random number generator.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::HeartbeatsDisabled`] if heartbeats are not enabled
+    /// with [`NoiseEncryptor::set_heartbeat_len`].
+    pub fn encrypt_heartbeat_with(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let len = self.heartbeat_len.ok_or(EncryptionError::HeartbeatsDisabled)?;
+        let mut heartbeat = vec![0u8; 1 + len as usize];
+        heartbeat[0] = FrameType::Heartbeat as u8;
+        rng.fill_bytes(&mut heartbeat[1..]);
+        self.encrypt_frame(&heartbeat)
+    }
+
+    fn encrypt_frame(&mut self, buffer: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let padded = self.padding_policy.pad(buffer)?;
+        let buffer = padded.as_deref().unwrap_or(buffer);
         let length = buffer.len();
         let length_bytes = if length > u16::MAX as usize {
             return Err(EncryptionError::ExceedingMaxLength(length));
@@ -49,24 +396,35 @@ impl NoiseEncryptor {
 
         let mut ciphertext =
             vec![0u8; Self::TAGGED_MESSAGE_LENGTH_HEADER_SIZE + length + chacha::TAG_SIZE];
+        let sequence = self.sequence_aad.then_some(self.sequence);
+        let mut sequence_buf = [0u8; SEQUENCE_SIZE];
+        let aad = frame_aad(&mut self.aad, sequence, &mut sequence_buf);
 
+        self.nonce_tracker.track(&self.sending_key, self.sending_nonce as u64);
         chacha::encrypt(
             &self.sending_key,
             self.sending_nonce as u64,
-            &[0; 0],
+            aad,
             &length_bytes,
             Some(&mut ciphertext[..Self::TAGGED_MESSAGE_LENGTH_HEADER_SIZE]),
         )?;
-        self.increment_nonce();
+        increment_nonce(
+            &mut self.sending_nonce,
+            &mut self.sending_chaining_key,
+            &mut self.sending_key,
+            self.key_rotation_period,
+        );
 
+        self.nonce_tracker.track(&self.sending_key, self.sending_nonce as u64);
         let _ = &chacha::encrypt(
             &self.sending_key,
             self.sending_nonce as u64,
-            &[0; 0],
+            aad,
             buffer,
             Some(&mut ciphertext[Self::TAGGED_MESSAGE_LENGTH_HEADER_SIZE..]),
         )?;
         self.increment_nonce();
+        self.sequence += 1;
 
         Ok(ciphertext)
     }
@@ -76,6 +434,7 @@ impl NoiseEncryptor {
             &mut self.sending_nonce,
             &mut self.sending_chaining_key,
             &mut self.sending_key,
+            self.key_rotation_period,
         );
     }
 }
@@ -85,11 +444,21 @@ pub struct NoiseDecryptor {
     pub(in crate::noise) receiving_key: SymmetricKey,
     pub(in crate::noise) receiving_chaining_key: SymmetricKey,
     pub(in crate::noise) receiving_nonce: u32,
+    pub(in crate::noise) key_rotation_period: u32,
 
     pub(in crate::noise) pending_message_length: Option<usize>,
     pub(in crate::noise) read_buffer: Option<Vec<u8>>,
+    pub(in crate::noise) padding_policy: PaddingPolicy,
     pub(in crate::noise) poisoned: bool, /* signal an error has occurred so None is returned on
                                           * iteration after failure */
+    pub(in crate::noise) sequence: u64,
+    pub(in crate::noise) sequence_aad: bool,
+    /// Domain separation prefix of the associated data followed by the space
+    /// for the sequence number (see [`frame_aad`]), or empty without prefix.
+    pub(in crate::noise) aad: Vec<u8>,
+    pub(in crate::noise) heartbeats: bool,
+    pub(in crate::noise) heartbeat_count: u64,
+    pub(in crate::noise) max_frame_len: u16,
     pub(in crate::noise) remote_pubkey: PublicKey,
 }
 
@@ -97,6 +466,102 @@ impl NoiseDecryptor {
     pub const TAGGED_MESSAGE_LENGTH_HEADER_SIZE: usize = Self::MESSAGE_LEN_SIZE + chacha::TAG_SIZE;
     const MESSAGE_LEN_SIZE: usize = 2;
 
+    pub(in crate::noise) fn new(
+        receiving_key: SymmetricKey,
+        chaining_key: SymmetricKey,
+        remote_pubkey: PublicKey,
+    ) -> Self {
+        NoiseDecryptor {
+            receiving_key,
+            receiving_chaining_key: chaining_key,
+            receiving_nonce: 0,
+            key_rotation_period: KEY_ROTATION_PERIOD,
+            pending_message_length: None,
+            read_buffer: None,
+            padding_policy: PaddingPolicy::None,
+            poisoned: false,
+            sequence: 0,
+            sequence_aad: false,
+            aad: vec![],
+            heartbeats: false,
+            heartbeat_count: 0,
+            max_frame_len: u16::MAX,
+            remote_pubkey,
+        }
+    }
+
+    /// Returns padding policy expected from the remote peer.
+    pub fn padding_policy(&self) -> PaddingPolicy { self.padding_policy }
+
+    /// Sets padding policy expected for the messages decrypted after the call.
+    pub fn set_padding_policy(&mut self, policy: PaddingPolicy) { self.padding_policy = policy }
+
+    /// Returns number of nonces after which the receiving key is rotated; see
+    /// [`NoiseEncryptor::key_rotation_period`].
+    pub fn key_rotation_period(&self) -> u32 { self.key_rotation_period }
+
+    /// Sets number of nonces after which the receiving key is rotated; see
+    /// [`NoiseEncryptor::set_key_rotation_period`].
+    ///
+    /// # Panics
+    ///
+    /// If the period is zero or is less than the nonces already used with the
+    /// current key.
+    pub fn set_key_rotation_period(&mut self, period: u32) {
+        assert!(period > self.receiving_nonce, "key rotation period must exceed the used nonces");
+        self.key_rotation_period = period
+    }
+
+    /// Returns number of messages which can be decrypted before the receiving
+    /// key is rotated; see [`NoiseEncryptor::messages_until_rekey`].
+    pub fn messages_until_rekey(&self) -> u64 {
+        messages_until_rekey(self.receiving_nonce, self.key_rotation_period)
+    }
+
+    /// Returns number of frames decrypted so far.
+    pub fn sequence(&self) -> u64 { self.sequence }
+
+    /// Detects whether the frame sequence number is expected in the associated
+    /// data of the received frames.
+    pub fn sequence_aad(&self) -> bool { self.sequence_aad }
+
+    /// Enables or disables checking of the frame sequence number in the
+    /// associated data of the frames decrypted after the call; see
+    /// [`NoiseEncryptor::set_sequence_aad`].
+    pub fn set_sequence_aad(&mut self, enabled: bool) { self.sequence_aad = enabled }
+
+    /// Returns domain separation prefix expected in the associated data.
+    pub fn aad_prefix(&self) -> &[u8] { aad_prefix(&self.aad) }
+
+    /// Sets domain separation prefix of the associated data of the frames
+    /// decrypted after the call; see [`NoiseEncryptor::set_aad_prefix`].
+    pub fn set_aad_prefix(&mut self, prefix: impl Into<Vec<u8>>) {
+        self.aad = with_sequence_space(prefix.into())
+    }
+
+    /// Detects whether the received frames are expected to start with the
+    /// [`FrameType`] byte.
+    pub fn heartbeats(&self) -> bool { self.heartbeats }
+
+    /// Enables or disables heartbeat frames for the frames decrypted after the
+    /// call; see [`NoiseEncryptor::set_heartbeat_len`]. Received heartbeats
+    /// are dropped and never returned as messages.
+    pub fn set_heartbeats(&mut self, enabled: bool) { self.heartbeats = enabled }
+
+    /// Returns number of heartbeat frames received and dropped so far.
+    pub fn heartbeat_count(&self) -> u64 { self.heartbeat_count }
+
+    /// Returns maximal length of the frame message accepted from the remote
+    /// peer, including padding and the frame type byte.
+    pub fn max_frame_len(&self) -> u16 { self.max_frame_len }
+
+    /// Sets maximal length of the frame message accepted from the remote peer;
+    /// defaults to `u16::MAX`. A frame declaring a longer message in its
+    /// length header is rejected with [`EncryptionError::MessageTooLong`]
+    /// before its body is buffered and decrypted, capping the memory a hostile
+    /// peer can make the local party allocate for a single frame.
+    pub fn set_max_frame_len(&mut self, len: u16) { self.max_frame_len = len }
+
     pub fn read_buf(&mut self, data: &[u8]) {
         let read_buffer = self.read_buffer.get_or_insert(Vec::new());
         read_buffer.extend_from_slice(data);
@@ -106,25 +571,69 @@ impl NoiseDecryptor {
     /// been received, only the first message will be returned, and the rest
     /// stored in the internal buffer. If a message pending in the buffer
     /// still hasn't been decrypted, that message will be returned in lieu
-    /// of anything new, even if new data is provided.
+    /// of anything new, even if new data is provided. Heartbeat frames
+    /// preceding the message are dropped.
     pub fn decrypt_single_message(
         &mut self,
         new_data: Option<&[u8]>,
     ) -> Result<Option<Vec<u8>>, EncryptionError> {
-        let mut read_buffer =
-            if let Some(buffer) = self.read_buffer.take() { buffer } else { Vec::new() };
+        let mut read_buffer = self.read_buffer.take().unwrap_or_default();
 
         if let Some(data) = new_data {
             read_buffer.extend_from_slice(data);
         }
 
-        let (current_message, offset) = self.decrypt_buf(&read_buffer[..])?;
-        read_buffer.drain(..offset); // drain the read buffer
-        self.read_buffer = Some(read_buffer); // assign the new value to the built-in buffer
-        Ok(current_message)
+        loop {
+            let (current_message, offset) = self.decrypt_buf(&read_buffer[..])?;
+            read_buffer.drain(..offset); // drain the read buffer
+            if current_message.is_some() || offset == 0 {
+                self.read_buffer = Some(read_buffer); // assign the new value to the built-in buffer
+                return Ok(current_message);
+            }
+            // a heartbeat was dropped, try the next frame
+        }
+    }
+
+    /// Decrypts all complete frames contained in `buf` in one pass, appending
+    /// the decrypted messages to `messages`, and returns the remaining part
+    /// of `buf` with an incomplete frame. The remaining data have to be
+    /// provided again, followed by the newly received data, with the next
+    /// call. Heartbeat frames are dropped.
+    ///
+    /// Unlike [`NoiseDecryptor::decrypt_single_message`], the internal buffer
+    /// is not used and must be empty.
+    ///
+    /// # Errors
+    ///
+    /// Stops on the first frame which fails authentication; messages decrypted
+    /// before it are kept in `messages`. Returns
+    /// [`EncryptionError::NonEmptyBuffer`] without decrypting anything if the
+    /// internal buffer contains data left by
+    /// [`NoiseDecryptor::decrypt_single_message`].
+    pub fn decrypt_all<'buf>(
+        &mut self,
+        mut buf: &'buf [u8],
+        messages: &mut Vec<Vec<u8>>,
+    ) -> Result<&'buf [u8], EncryptionError> {
+        if self.read_buffer_len() != 0 {
+            return Err(EncryptionError::NonEmptyBuffer(self.read_buffer_len()));
+        }
+        loop {
+            let (message, offset) = self.decrypt_buf(buf)?;
+            buf = &buf[offset..];
+            match message {
+                Some(message) => messages.push(message),
+                None if offset == 0 => return Ok(buf),
+                None => {}
+            }
+        }
     }
 
     fn decrypt_buf(&mut self, buffer: &[u8]) -> Result<(Option<Vec<u8>>, usize), EncryptionError> {
+        let sequence = self.sequence_aad.then_some(self.sequence);
+        let mut sequence_buf = [0u8; SEQUENCE_SIZE];
+        let aad = frame_aad(&mut self.aad, sequence, &mut sequence_buf);
+
         let message_length = if let Some(length) = self.pending_message_length {
             // we have already decrypted the header
             length
@@ -137,22 +646,26 @@ impl NoiseDecryptor {
 
             let encrypted_length = &buffer[0..Self::TAGGED_MESSAGE_LENGTH_HEADER_SIZE];
 
-            let mut decrypt = |length_bytes: &mut [u8]| -> Result<(), EncryptionError> {
-                chacha::decrypt(
-                    &self.receiving_key,
-                    self.receiving_nonce as u64,
-                    &[0; 0],
-                    encrypted_length,
-                    Some(length_bytes),
-                )?;
-                self.increment_nonce();
-                Ok(())
-            };
-
             // the message length
             let mut length_bytes = [0u8; 2];
-            decrypt(&mut length_bytes)?;
-            u16::from_be_bytes(length_bytes) as usize
+            chacha::decrypt(
+                &self.receiving_key,
+                self.receiving_nonce as u64,
+                aad,
+                encrypted_length,
+                Some(&mut length_bytes),
+            )?;
+            increment_nonce(
+                &mut self.receiving_nonce,
+                &mut self.receiving_chaining_key,
+                &mut self.receiving_key,
+                self.key_rotation_period,
+            );
+            let length = u16::from_be_bytes(length_bytes);
+            if length > self.max_frame_len {
+                return Err(EncryptionError::MessageTooLong(length as usize));
+            }
+            length as usize
         };
 
         let message_end_index =
@@ -171,12 +684,28 @@ impl NoiseDecryptor {
         chacha::decrypt(
             &self.receiving_key,
             self.receiving_nonce as u64,
-            &[0; 0],
+            aad,
             encrypted_message,
             Some(&mut message),
         )?;
 
         self.increment_nonce();
+        self.sequence += 1;
+
+        if self.padding_policy.is_padded() {
+            let len = self.padding_policy.unpad(&message)?.len();
+            message.drain(..PaddingPolicy::LEN_PREFIX_SIZE);
+            message.truncate(len);
+        }
+
+        if self.heartbeats {
+            let frame_type = message.first().copied().ok_or(EncryptionError::IncompleteFrame(0))?;
+            message.remove(0);
+            if FrameType::try_from(frame_type)? == FrameType::Heartbeat {
+                self.heartbeat_count += 1;
+                return Ok((None, message_end_index));
+            }
+        }
 
         Ok((Some(message), message_end_index))
     }
@@ -186,17 +715,23 @@ impl NoiseDecryptor {
             &mut self.receiving_nonce,
             &mut self.receiving_chaining_key,
             &mut self.receiving_key,
+            self.key_rotation_period,
         );
     }
 
+    /// Returns length of the received data which are not yet decrypted.
+    pub fn read_buffer_len(&self) -> usize {
+        self.read_buffer.as_ref().map(Vec::len).unwrap_or_default()
+    }
+
     // Used in tests to determine whether or not excess bytes entered the
     // conduit without needing to bring up infrastructure to properly encode
     // it
     #[cfg(test)]
     pub fn read_buffer_length(&self) -> usize {
-        match &self.read_buffer {
-            &Some(ref vec) => vec.len(),
-            &None => 0,
+        match self.read_buffer {
+            Some(ref vec) => vec.len(),
+            None => 0,
         }
     }
 }
@@ -220,7 +755,8 @@ impl Iterator for NoiseDecryptor {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[cfg_attr(feature = "std", derive(Error))]
 #[display("incomplete Noise handshake")]
 pub struct IncompleteHandshake;
 pub trait NoiseState: Sized {
@@ -230,6 +766,18 @@ pub trait NoiseState: Sized {
     fn next_handshake_len(&self) -> usize;
     fn is_handshake_complete(&self) -> bool;
 
+    /// Returns number of bytes which must be read from the remote peer to
+    /// complete the current handshake step, or `None` if the handshake is
+    /// complete. Zero is returned when the local party has to write the next
+    /// act before reading anything.
+    fn next_read_len(&self) -> Option<usize> {
+        if self.is_handshake_complete() {
+            None
+        } else {
+            Some(self.next_handshake_len())
+        }
+    }
+
     fn with_split(encryptor: NoiseEncryptor, decryptor: NoiseDecryptor) -> Self;
     fn try_as_split(&self) -> Result<(&NoiseEncryptor, &NoiseDecryptor), IncompleteHandshake>;
     fn try_as_split_mut(
@@ -265,6 +813,8 @@ impl<S: NoiseState> NoiseState for NoiseTranscoder<S> {
 
     fn is_handshake_complete(&self) -> bool { self.state.is_handshake_complete() }
 
+    fn next_read_len(&self) -> Option<usize> { self.state.next_read_len() }
+
     fn with_split(encryptor: NoiseEncryptor, decryptor: NoiseDecryptor) -> Self {
         NoiseTranscoder {
             state: S::with_split(encryptor, decryptor),
@@ -302,6 +852,21 @@ impl<S: NoiseState> NoiseTranscoder<S> {
 
     pub fn read_buf(&mut self, data: &[u8]) { self.expect_decryptor().read_buf(data) }
 
+    /// Sets padding policy used by both the local and remote peers.
+    pub fn set_padding_policy(&mut self, policy: PaddingPolicy) {
+        let (encryptor, decryptor) = self.try_as_split_mut().expect("incomplete Noise handshake");
+        encryptor.set_padding_policy(policy);
+        decryptor.set_padding_policy(policy);
+    }
+
+    /// Enables or disables inclusion of the frame sequence number into the
+    /// associated data for both the local and remote peers.
+    pub fn set_sequence_aad(&mut self, enabled: bool) {
+        let (encryptor, decryptor) = self.try_as_split_mut().expect("incomplete Noise handshake");
+        encryptor.set_sequence_aad(enabled);
+        decryptor.set_sequence_aad(enabled);
+    }
+
     /// Decrypt a single message. If data containing more than one message has
     /// been received, only the first message will be returned, and the rest
     /// stored in the internal buffer. If a message pending in the buffer
@@ -315,16 +880,386 @@ impl<S: NoiseState> NoiseTranscoder<S> {
     }
 }
 
-fn increment_nonce(nonce: &mut u32, chaining_key: &mut SymmetricKey, key: &mut SymmetricKey) {
+/// Size of the frame sequence number in the associated data.
+const SEQUENCE_SIZE: usize = 8;
+
+/// Appends space for the sequence number to the non-empty domain separation
+/// prefix of the associated data.
+fn with_sequence_space(mut prefix: Vec<u8>) -> Vec<u8> {
+    if !prefix.is_empty() {
+        prefix.extend_from_slice(&[0u8; SEQUENCE_SIZE]);
+    }
+    prefix
+}
+
+/// Returns domain separation prefix from the associated data constructed with
+/// [`with_sequence_space`].
+fn aad_prefix(aad: &[u8]) -> &[u8] { &aad[..aad.len().saturating_sub(SEQUENCE_SIZE)] }
+
+/// Constructs associated data of a frame from the domain separation prefix
+/// (with the space for the sequence number, see [`with_sequence_space`]) and
+/// the optional frame sequence number, without allocation. The sequence number
+/// is written to the space after the prefix or, if there is no prefix, to
+/// `sequence_buf`.
+fn frame_aad<'a>(
+    aad: &'a mut [u8],
+    sequence: Option<u64>,
+    sequence_buf: &'a mut [u8; SEQUENCE_SIZE],
+) -> &'a [u8] {
+    let prefix_len = aad_prefix(aad).len();
+    match sequence {
+        None => &aad[..prefix_len],
+        Some(sequence) if aad.is_empty() => {
+            *sequence_buf = sequence.to_be_bytes();
+            sequence_buf
+        }
+        Some(sequence) => {
+            aad[prefix_len..].copy_from_slice(&sequence.to_be_bytes());
+            aad
+        }
+    }
+}
+
+fn increment_nonce(
+    nonce: &mut u32,
+    chaining_key: &mut SymmetricKey,
+    key: &mut SymmetricKey,
+    period: u32,
+) {
     *nonce += 1;
-    if *nonce == KEY_ROTATION_PERIOD {
+    if *nonce == period {
         rotate_key(chaining_key, key);
         *nonce = 0;
     }
 }
 
+/// Computes number of messages, each using two nonces, until the key rotation.
+fn messages_until_rekey(nonce: u32, period: u32) -> u64 { (u64::from(period - nonce) + 1) / 2 }
+
 fn rotate_key(chaining_key: &mut SymmetricKey, key: &mut SymmetricKey) {
     let (new_chaining_key, new_key) = hkdf::derive(chaining_key, key);
     chaining_key.copy_from_slice(&new_chaining_key);
     key.copy_from_slice(&new_key);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::noise::Transport;
+
+    #[test]
+    fn decrypt_all() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        let mut buf = vec![];
+        for msg in [&b"first"[..], b"second", b"third"] {
+            buf.extend(encryptor.encrypt_buf(msg).unwrap());
+        }
+        let next = encryptor.encrypt_buf(b"next").unwrap();
+        buf.extend_from_slice(&next[..10]);
+
+        let mut messages = vec![];
+        let leftover = decryptor.decrypt_all(&buf, &mut messages).unwrap();
+        assert_eq!(messages, vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]);
+        assert_eq!(leftover, &next[..10]);
+        messages.clear();
+        assert_eq!(decryptor.decrypt_all(&next, &mut messages).unwrap(), b"");
+        assert_eq!(messages, vec![b"next".to_vec()]);
+
+        // Leftover with already decrypted length header
+        let frame = encryptor.encrypt_buf(b"split").unwrap();
+        let split = NoiseDecryptor::TAGGED_MESSAGE_LENGTH_HEADER_SIZE + 2;
+        messages.clear();
+        assert_eq!(decryptor.decrypt_all(&frame[..split], &mut messages).unwrap(), &frame[..split]);
+        assert_eq!(decryptor.decrypt_all(&frame, &mut messages).unwrap(), b"");
+        assert_eq!(messages, vec![b"split".to_vec()]);
+
+        // Data buffered by `decrypt_single_message` are not skipped
+        let frame = encryptor.encrypt_buf(b"buffered").unwrap();
+        assert_eq!(decryptor.decrypt_single_message(Some(&frame[..10])), Ok(None));
+        assert_eq!(
+            decryptor.decrypt_all(&frame[10..], &mut messages),
+            Err(EncryptionError::NonEmptyBuffer(10))
+        );
+        assert_eq!(
+            decryptor.decrypt_single_message(Some(&frame[10..])).unwrap().unwrap(),
+            b"buffered"
+        );
+    }
+
+    #[test]
+    fn decrypt_all_corrupted() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        let mut buf = vec![];
+        for msg in [&b"first"[..], b"second", b"third"] {
+            buf.extend(encryptor.encrypt_buf(msg).unwrap());
+        }
+        let second_end = 2 * NoiseEncryptor::TAGGED_MESSAGE_LENGTH_HEADER_SIZE + 5 + 6 + 32;
+        buf[second_end - 1] ^= 1;
+
+        let mut messages = vec![];
+        assert_eq!(
+            decryptor.decrypt_all(&buf, &mut messages),
+            Err(EncryptionError::ChaCha(chacha20poly1305::aead::Error))
+        );
+        assert_eq!(messages, vec![b"first".to_vec()]);
+    }
+
+    #[test]
+    fn heartbeat() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        assert_eq!(
+            encryptor.encrypt_heartbeat_with(&mut rand_core::OsRng),
+            Err(EncryptionError::HeartbeatsDisabled)
+        );
+        encryptor.set_heartbeat_len(Some(32));
+        decryptor.set_heartbeats(true);
+
+        let heartbeat = encryptor.encrypt_heartbeat_with(&mut rand_core::OsRng).unwrap();
+        assert_eq!(heartbeat.len(), NoiseEncryptor::TAGGED_MESSAGE_LENGTH_HEADER_SIZE + 33 + 16);
+        let other = encryptor.encrypt_heartbeat_with(&mut rand_core::OsRng).unwrap();
+        assert_eq!(other.len(), heartbeat.len());
+        let data = encryptor.encrypt_buf(b"data").unwrap();
+
+        assert_eq!(decryptor.decrypt_single_message(Some(&heartbeat)).unwrap(), None);
+        assert_eq!(decryptor.heartbeat_count(), 1);
+        assert_eq!(decryptor.read_buffer_len(), 0);
+
+        let mut buf = other;
+        buf.extend(data);
+        assert_eq!(decryptor.decrypt_single_message(Some(&buf)).unwrap(), Some(b"data".to_vec()));
+        assert_eq!(decryptor.heartbeat_count(), 2);
+        assert_eq!(decryptor.sequence(), 3);
+
+        // Peer not expecting heartbeats receives the frame type byte
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_heartbeat_len(Some(0));
+        let frame = encryptor.encrypt_buf(b"data").unwrap();
+        assert_eq!(
+            decryptor.decrypt_single_message(Some(&frame)).unwrap(),
+            Some(b"\0data".to_vec())
+        );
+
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        decryptor.set_heartbeats(true);
+        let frame = encryptor.encrypt_buf(&[7, 1, 2]).unwrap();
+        assert_eq!(
+            decryptor.decrypt_single_message(Some(&frame)),
+            Err(EncryptionError::UnknownFrameType(7))
+        );
+    }
+
+    #[test]
+    fn max_frame_len() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        assert_eq!(decryptor.max_frame_len(), u16::MAX);
+        decryptor.set_max_frame_len(4);
+        let frame = encryptor.encrypt_buf(b"data").unwrap();
+        assert_eq!(decryptor.decrypt_single_message(Some(&frame)).unwrap(), Some(b"data".to_vec()));
+
+        // Only the length header is provided: the frame is rejected without
+        // waiting for and buffering its body
+        let frame = encryptor.encrypt_buf(b"large").unwrap();
+        assert_eq!(
+            decryptor.decrypt_single_message(Some(
+                &frame[..NoiseDecryptor::TAGGED_MESSAGE_LENGTH_HEADER_SIZE]
+            )),
+            Err(EncryptionError::MessageTooLong(5))
+        );
+        assert_eq!(decryptor.read_buffer_len(), 0);
+    }
+
+    #[test]
+    fn padded_len() {
+        assert_eq!(PaddingPolicy::None.padded_len(10), Ok(None));
+        assert_eq!(PaddingPolicy::FixedBucket(256).padded_len(0), Ok(Some(256)));
+        assert_eq!(PaddingPolicy::FixedBucket(256).padded_len(254), Ok(Some(256)));
+        assert_eq!(PaddingPolicy::FixedBucket(256).padded_len(255), Ok(Some(512)));
+        assert_eq!(PaddingPolicy::FixedBucket(0).padded_len(10), Ok(Some(12)));
+        assert_eq!(PaddingPolicy::PowerOfTwo.padded_len(10), Ok(Some(16)));
+        assert_eq!(PaddingPolicy::PowerOfTwo.padded_len(14), Ok(Some(16)));
+        assert_eq!(PaddingPolicy::PowerOfTwo.padded_len(32766), Ok(Some(32768)));
+        // Padding up to the bucket would exceed the frame limit
+        assert_eq!(
+            PaddingPolicy::PowerOfTwo.padded_len(40000),
+            Err(EncryptionError::ExceedingMaxLength(40000))
+        );
+        assert_eq!(
+            PaddingPolicy::FixedBucket(1000).padded_len(65000),
+            Err(EncryptionError::ExceedingMaxLength(65000))
+        );
+        assert_eq!(PaddingPolicy::FixedBucket(1).padded_len(65533), Ok(Some(65535)));
+    }
+
+    #[test]
+    fn fixed_bucket_hides_length() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_padding_policy(PaddingPolicy::FixedBucket(256));
+        decryptor.set_padding_policy(PaddingPolicy::FixedBucket(256));
+
+        let short = encryptor.encrypt_buf(&[0xAA; 10]).unwrap();
+        let long = encryptor.encrypt_buf(&[0xBB; 200]).unwrap();
+        assert_eq!(short.len(), long.len());
+        assert_eq!(short.len(), NoiseEncryptor::TAGGED_MESSAGE_LENGTH_HEADER_SIZE + 256 + 16);
+
+        assert_eq!(decryptor.decrypt_single_message(Some(&short)).unwrap(), Some(vec![0xAA; 10]));
+        assert_eq!(decryptor.decrypt_single_message(Some(&long)).unwrap(), Some(vec![0xBB; 200]));
+    }
+
+    #[test]
+    fn power_of_two_roundtrip() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_padding_policy(PaddingPolicy::PowerOfTwo);
+        decryptor.set_padding_policy(PaddingPolicy::PowerOfTwo);
+
+        for len in [0usize, 1, 14, 15, 1000] {
+            let msg = vec![0x42; len];
+            let frame = encryptor.encrypt_buf(&msg).unwrap();
+            assert_eq!(decryptor.decrypt_single_message(Some(&frame)).unwrap(), Some(msg));
+        }
+    }
+
+    #[test]
+    fn unpad_invalid_length() {
+        let policy = PaddingPolicy::FixedBucket(16);
+        assert_eq!(
+            policy.unpad(&[0, 20, 0, 0]),
+            Err(EncryptionError::InvalidPadding {
+                declared: 20,
+                found: 2
+            })
+        );
+        assert_eq!(policy.unpad(&[0, 1, 7, 0]), Ok(&[7u8][..]));
+    }
+
+    #[test]
+    fn too_long_for_padding() {
+        let mut encryptor = Transport::test_pair().0.into_split().0;
+        encryptor.set_padding_policy(PaddingPolicy::PowerOfTwo);
+        assert_eq!(
+            encryptor.encrypt_buf(&[0u8; u16::MAX as usize - 1]),
+            Err(EncryptionError::ExceedingMaxLength(u16::MAX as usize - 1))
+        );
+    }
+
+    #[test]
+    fn sequence_aad() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_sequence_aad(true);
+        decryptor.set_sequence_aad(true);
+        let first = encryptor.encrypt_buf(b"first").unwrap();
+        let second = encryptor.encrypt_buf(b"second").unwrap();
+        assert_eq!(encryptor.sequence(), 2);
+
+        let mut swapped = decryptor.clone();
+        assert!(swapped.decrypt_single_message(Some(&second)).is_err());
+
+        assert_eq!(decryptor.decrypt_single_message(Some(&first)).unwrap().unwrap(), b"first");
+        assert_eq!(decryptor.decrypt_single_message(Some(&second)).unwrap().unwrap(), b"second");
+        assert_eq!(decryptor.sequence(), 2);
+    }
+
+    #[test]
+    fn frame_aad_buffer() {
+        let mut sequence_buf = [0u8; SEQUENCE_SIZE];
+        let mut aad = with_sequence_space(b"proto/1".to_vec());
+        assert_eq!(super::aad_prefix(&aad), b"proto/1");
+        assert_eq!(frame_aad(&mut aad, None, &mut sequence_buf), b"proto/1");
+        assert_eq!(frame_aad(&mut aad, Some(1), &mut sequence_buf), b"proto/1\0\0\0\0\0\0\0\x01");
+        assert_eq!(
+            frame_aad(&mut aad, Some(258), &mut sequence_buf),
+            b"proto/1\0\0\0\0\0\0\x01\x02"
+        );
+        assert_eq!(super::aad_prefix(&aad), b"proto/1");
+        assert_eq!(frame_aad(&mut aad, None, &mut sequence_buf), b"proto/1");
+
+        let mut aad = with_sequence_space(vec![]);
+        assert!(aad.is_empty());
+        assert_eq!(frame_aad(&mut aad, None, &mut sequence_buf), b"");
+        assert_eq!(frame_aad(&mut aad, Some(2), &mut sequence_buf), 2u64.to_be_bytes());
+        assert!(aad.is_empty());
+    }
+
+    #[test]
+    fn aad_prefix() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_aad_prefix(b"proto-a/1".to_vec());
+        let frame = encryptor.encrypt_buf(b"message").unwrap();
+
+        let mut other = decryptor.clone();
+        other.set_aad_prefix(&b"proto-b/1"[..]);
+        assert!(other.decrypt_single_message(Some(&frame)).is_err());
+        let mut other = decryptor.clone();
+        assert!(other.decrypt_single_message(Some(&frame)).is_err());
+
+        let mut decryptor = decryptor;
+        decryptor.set_aad_prefix(&b"proto-a/1"[..]);
+        assert_eq!(decryptor.decrypt_single_message(Some(&frame)).unwrap().unwrap(), b"message");
+
+        // Prefix is combined with the sequence number
+        encryptor.set_sequence_aad(true);
+        decryptor.set_sequence_aad(true);
+        let frame = encryptor.encrypt_buf(b"next").unwrap();
+        assert_eq!(decryptor.decrypt_single_message(Some(&frame)).unwrap().unwrap(), b"next");
+    }
+
+    #[test]
+    fn sequence_aad_mismatch() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_sequence_aad(true);
+        let frame = encryptor.encrypt_buf(b"message").unwrap();
+        assert!(decryptor.decrypt_single_message(Some(&frame)).is_err());
+    }
+
+    #[test]
+    fn backpressure() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        encryptor.set_high_water_mark(Some(64));
+        encryptor.encrypt_buffered(&[1u8; 16]).unwrap();
+        encryptor.encrypt_buffered(&[2u8; 16]).unwrap();
+        assert_eq!(encryptor.buffered().len(), 2 * (16 + 34));
+        assert_eq!(encryptor.encrypt_buffered(&[3u8; 16]), Err(EncryptionError::WouldBlock(64)));
+
+        let flushed = encryptor.buffered().to_vec();
+        encryptor.consume(flushed.len());
+        assert!(encryptor.buffered().is_empty());
+        encryptor.encrypt_buffered(&[3u8; 16]).unwrap();
+
+        decryptor.read_buf(&flushed);
+        decryptor.read_buf(encryptor.buffered());
+        for msg in [[1u8; 16], [2u8; 16], [3u8; 16]] {
+            assert_eq!(decryptor.decrypt_single_message(None).unwrap().unwrap(), msg);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "nonce 0 is reused with the same key")]
+    fn nonce_reuse() {
+        let mut encryptor = Transport::test_pair().0.into_split().0;
+        encryptor.encrypt_buf(b"first").unwrap();
+        encryptor.sending_nonce = 0;
+        encryptor.encrypt_buf(b"second").unwrap();
+    }
+
+    #[test]
+    fn nonce_rotation_no_reuse() {
+        let (initiator, responder) = Transport::test_pair();
+        let (mut encryptor, mut decryptor) = (initiator.into_split().0, responder.into_split().1);
+        for _ in 0..KEY_ROTATION_PERIOD {
+            let msg = encryptor.encrypt_buf(b"msg").unwrap();
+            assert_eq!(decryptor.decrypt_single_message(Some(&msg)).unwrap().unwrap(), b"msg");
+        }
+    }
+}
@@ -19,6 +19,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::vec::Vec;
+
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
@@ -49,11 +51,32 @@ pub(crate) fn derive(salt: &[u8], ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
     // When the message is composed of several elements we use concatenation
     // (denoted |) in the second argument; for example, HMAC(K, elem1 |
     // elem2 | elem3).
+    let okm = expand(&extract(salt, ikm), &[], 64);
+    let mut t1 = [0u8; 32];
+    let mut t2 = [0u8; 32];
+    t1.copy_from_slice(&okm[..32]);
+    t2.copy_from_slice(&okm[32..]);
+    (t1, t2)
+}
 
+/// HKDF-Extract step of [RFC 5869](https://tools.ietf.org/html/rfc5869pub),
+/// producing pseudorandom key from the input keying material.
+pub(crate) fn extract(salt: &[u8], ikm: &[u8]) -> [u8; 32] {
     // 2.2. Step 1: Extract
     // HKDF-Extract(salt, IKM) -> PRK
     // PRK = HMAC-Hash(salt, IKM)
-    let prk = hmac_sha256(salt, [ikm]);
+    hmac_sha256(salt, [ikm])
+}
+
+/// HKDF-Expand step of [RFC 5869](https://tools.ietf.org/html/rfc5869pub),
+/// deriving `out_len` bytes of output keying material bound to the `info`
+/// context from the pseudorandom key.
+///
+/// # Panics
+///
+/// If `out_len` exceeds 255 * 32 bytes.
+pub(crate) fn expand(prk: &[u8; 32], info: &[u8], out_len: usize) -> Vec<u8> {
+    assert!(out_len <= 255 * 32, "HKDF output length {out_len} exceeds 255 hash lengths");
 
     // 2.3.  Step 2: Expand
     // HKDF-Expand(PRK, info, L) -> OKM
@@ -64,11 +87,16 @@ pub(crate) fn derive(salt: &[u8], ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
     // where:
     // T(0) = empty string (zero length)
     // T(1) = HMAC-Hash(PRK, T(0) | info | 0x01)
-    let t1 = hmac_sha256(prk, [&[1]]);
     // T(2) = HMAC-Hash(PRK, T(1) | info | 0x02)
-    let t2 = hmac_sha256(prk, [&t1[..], &[2][..]]);
-
-    (t1, t2)
+    let mut okm = Vec::with_capacity(out_len + 32);
+    let mut t = [0u8; 32];
+    for counter in 1..=((out_len + 31) / 32) as u8 {
+        let prev = if counter == 1 { &[][..] } else { &t[..] };
+        t = hmac_sha256(prk, [prev, info, &[counter]]);
+        okm.extend_from_slice(&t);
+    }
+    okm.truncate(out_len);
+    okm
 }
 
 // Appendix A.  Test Vectors
@@ -76,7 +104,7 @@ pub(crate) fn derive(salt: &[u8], ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
 mod test {
     use amplify::hex::FromHex;
 
-    use super::derive;
+    use super::*;
 
     // Test with SHA-256 and zero-length salt/info
     // Our implementation uses a zero-length info field and returns the first 64
@@ -92,4 +120,43 @@ mod test {
         calculated_okm.truncate(42);
         assert_eq!(calculated_okm, Vec::<u8>::from_hex("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8").unwrap());
     }
+
+    // Test with SHA-256 and longer inputs/outputs
+    #[test]
+    fn rfc_5869_test_vector_2() {
+        let ikm = Vec::<u8>::from_hex(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\
+             202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f\
+             404142434445464748494a4b4c4d4e4f",
+        )
+        .unwrap();
+        let salt = Vec::<u8>::from_hex(
+            "606162636465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f\
+             808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f\
+             a0a1a2a3a4a5a6a7a8a9aaabacadaeaf",
+        )
+        .unwrap();
+        let info = Vec::<u8>::from_hex(
+            "b0b1b2b3b4b5b6b7b8b9babbbcbdbebfc0c1c2c3c4c5c6c7c8c9cacbcccdcecf\
+             d0d1d2d3d4d5d6d7d8d9dadbdcdddedfe0e1e2e3e4e5e6e7e8e9eaebecedeeef\
+             f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff",
+        )
+        .unwrap();
+        let prk = extract(&salt, &ikm);
+        assert_eq!(
+            prk.to_vec(),
+            Vec::<u8>::from_hex("06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244")
+                .unwrap()
+        );
+        assert_eq!(
+            expand(&prk, &info, 82),
+            Vec::<u8>::from_hex(
+                "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c\
+                 59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71\
+                 cc30c58179ec3e87c14c01d5c1f3434f1d87"
+            )
+            .unwrap()
+        );
+        assert_eq!(expand(&prk, &info, 0), Vec::<u8>::new());
+    }
 }
@@ -0,0 +1,126 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! X25519 keys used by the Noise handshakes.
+
+use ed25519::x25519::{PublicKey, SecretKey};
+use rand_core::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use super::HandshakeError;
+use crate::crypto::Ecdh;
+
+/// Generates a new random X25519 keypair, which can be used as a static or
+/// ephemeral handshake key, using the operating system random number
+/// generator. The secret key is zeroized on drop.
+#[cfg(feature = "std")]
+pub fn generate_keypair() -> (SecretKey, PublicKey) { generate_keypair_with(&mut rand_core::OsRng) }
+
+/// Generates a new X25519 keypair using the provided random number generator.
+/// Allows deterministic key generation with a seeded generator in tests.
+pub fn generate_keypair_with(rng: &mut (impl RngCore + CryptoRng)) -> (SecretKey, PublicKey) {
+    let mut bytes = Zeroizing::new([0u8; SecretKey::BYTES]);
+    rng.fill_bytes(bytes.as_mut());
+    let sk = SecretKey::new(*bytes);
+    let pk = sk.recover_public_key().expect("clamped secret key has a valid public key");
+    (sk, pk)
+}
+
+/// Computes X25519 Diffie-Hellman shared secret (RFC 7748), which is used as
+/// an input to the HKDF chaining of the handshake.
+///
+/// The secret key is clamped before the multiplication. The public keys which
+/// are low-order points of the curve and result in all-zero shared secret are
+/// rejected with [`HandshakeError::LowOrderPubkey`].
+pub fn dh(secret: &SecretKey, public: &PublicKey) -> Result<[u8; 32], HandshakeError> {
+    secret.ecdh(public).map(|ss| *ss).map_err(|_| HandshakeError::LowOrderPubkey)
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::FromHex;
+
+    use super::*;
+
+    fn sk(hex: &str) -> SecretKey { SecretKey::new(<[u8; 32]>::from_hex(hex).unwrap()) }
+
+    fn pk(hex: &str) -> PublicKey { PublicKey::new(<[u8; 32]>::from_hex(hex).unwrap()) }
+
+    #[test]
+    fn dh_rfc7748_scalar_mult() {
+        // RFC 7748 section 5.2
+        let shared = dh(
+            &sk("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4"),
+            &pk("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c"),
+        )
+        .unwrap();
+        assert_eq!(
+            shared,
+            <[u8; 32]>::from_hex(
+                "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn dh_rfc7748_key_agreement() {
+        // RFC 7748 section 6.1
+        let alice_sk = sk("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let alice_pk = pk("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob_sk = sk("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let bob_pk = pk("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = <[u8; 32]>::from_hex(
+            "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742",
+        )
+        .unwrap();
+        assert_eq!(alice_sk.recover_public_key().unwrap(), alice_pk);
+        assert_eq!(bob_sk.recover_public_key().unwrap(), bob_pk);
+        assert_eq!(dh(&alice_sk, &bob_pk).unwrap(), shared);
+        assert_eq!(dh(&bob_sk, &alice_pk).unwrap(), shared);
+    }
+
+    #[test]
+    fn dh_low_order() {
+        let secret = sk("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        for low_order in [[0u8; 32], {
+            let mut one = [0u8; 32];
+            one[0] = 1;
+            one
+        }] {
+            assert_eq!(
+                dh(&secret, &PublicKey::new(low_order)),
+                Err(HandshakeError::LowOrderPubkey)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn generate_keypair_ecdh() {
+        let (sk1, pk1) = generate_keypair();
+        let (sk2, pk2) = generate_keypair();
+        assert_ne!(pk1, pk2);
+        assert_ne!(*sk1, *sk2);
+        assert_eq!(sk1.recover_public_key().unwrap(), pk1);
+        assert_eq!(dh(&sk1, &pk2), dh(&sk2, &pk1));
+    }
+}
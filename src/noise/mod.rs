@@ -19,26 +19,112 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-mod chacha;
+pub mod chacha;
+pub mod chunked;
+pub mod datagram;
 pub mod framing;
 mod hkdf;
+mod keys;
+pub mod multi;
+pub mod seal;
+#[cfg(feature = "std")]
+mod stream;
+mod suite;
+mod transport;
 pub mod xk;
 
+#[cfg(feature = "std")]
+pub use keys::generate_keypair;
+pub use keys::{dh, generate_keypair_with};
+#[cfg(feature = "std")]
+pub use stream::NoiseStream;
+pub use suite::CipherSuite;
+pub use transport::{Role, Transport, TransportBuilder, TransportKeys};
+
 pub type SymmetricKey = [u8; 32];
 
-#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[derive(Clone, Eq, PartialEq, Debug, Display, From)]
+#[cfg_attr(feature = "std", derive(Error))]
 #[display(doc_comments)]
 pub enum EncryptionError {
     /// message length {0} exceeds maximum size allowed for the encryption
     /// protocol frame.
     ExceedingMaxLength(usize),
 
+    /// padded message declares length {declared} exceeding the size of the
+    /// decrypted data ({found} bytes).
+    InvalidPadding { declared: usize, found: usize },
+
+    /// message length {0} exceeds maximum plaintext size supported by
+    /// ChaCha20Poly1305 AEAD.
+    MessageTooLong(usize),
+
+    /// associated data has length {found} instead of the expected {expected}
+    /// bytes.
+    InvalidAad { expected: usize, found: usize },
+
+    /// frame of {0} bytes doesn't contain complete encrypted message.
+    IncompleteFrame(usize),
+
+    /// chunk of {0} bytes is not a valid encrypted stream chunk.
+    InvalidChunk(usize),
+
+    /// stream was truncated after {0} chunks without receiving the final
+    /// chunk.
+    UnterminatedStream(u64),
+
+    /// final chunk of the stream was already processed.
+    StreamFinished,
+
+    /// datagram of {0} bytes is too short to contain sequence number and
+    /// encrypted message.
+    InvalidDatagram(usize),
+
+    /// datagram with sequence number {0} is a replay or is too old.
+    Replayed(u64),
+
+    /// encryptor output buffer has reached its high-water mark of {0} bytes and
+    /// must be flushed before encrypting more data.
+    WouldBlock(usize),
+
+    /// symmetric key has length {0} instead of 32 bytes.
+    InvalidKeyLen(usize),
+
+    /// recipient public key #{0} is a low-order point of the curve.
+    InvalidRecipient(usize),
+
+    /// message is not encrypted to the local key.
+    NotRecipient,
+
+    /// heartbeat frames are not enabled.
+    HeartbeatsDisabled,
+
+    /// decrypted frame has unknown type {0:#04x}.
+    UnknownFrameType(u8),
+
+    /// transport checkpoint nonce {nonce} exceeds its key rotation period of
+    /// {period} messages.
+    InvalidCheckpoint { nonce: u64, period: u32 },
+
+    /// key rotation period of {period} nonces doesn't exceed {nonce} nonces
+    /// already used with the current key.
+    InvalidRotationPeriod { nonce: u64, period: u32 },
+
+    /// decryptor internal buffer holds {0} bytes of data received with
+    /// `decrypt_single_message`, which must be decrypted first.
+    NonEmptyBuffer(usize),
+
+    /// transport can't be exported while {0} bytes of a partially received
+    /// frame are not decrypted.
+    PendingFrame(usize),
+
     /// ChaCha20Poly1305 AEAD encryptor error.
     #[from]
     ChaCha(chacha20poly1305::aead::Error),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Display, Error, From)]
+#[derive(Debug, Clone, PartialEq, Eq, Display, From)]
+#[cfg_attr(feature = "std", derive(Error))]
 #[display(doc_comments)]
 pub enum HandshakeError {
     /// unexpected version of noise protocol {version} in act {act} of handshake.
@@ -50,6 +136,19 @@ pub enum HandshakeError {
     /// the initiator has provided an invalid pubkey
     InvalidInitiatorPubkey,
 
+    /// remote public key is a low-order point of the curve, resulting in an
+    /// all-zero Diffie-Hellman shared secret.
+    LowOrderPubkey,
+
+    /// authentication tag of handshake act {act} is invalid.
+    ///
+    /// The tag is checked with a key derived from the Diffie-Hellman shared
+    /// secret of the act, so this also covers the case when the secret
+    /// doesn't match the one computed by the remote peer (for instance, the
+    /// initiator uses a wrong static key of the responder): the two cases
+    /// are cryptographically indistinguishable.
+    InvalidActTag { act: u8 },
+
     /// invalid length of handshake act {act}: expected {expected}, provided {found}
     InvalidActLen {
         act: u8,
@@ -57,13 +156,34 @@ pub enum HandshakeError {
         found: usize,
     },
 
+    /// remote peer has not proposed any supported cipher suite.
+    NoCommonCipherSuite,
+
+    /// remote peer has confirmed unknown or not proposed cipher suite {0:#04x}.
+    UnexpectedCipherSuite(u8),
+
+    /// cipher suite confirmation must be a single byte, while {0} bytes were
+    /// provided.
+    InvalidSuiteConfirmation(usize),
+
     #[from]
     #[from(chacha20poly1305::aead::Error)]
     #[display(inner)]
     Encryption(EncryptionError),
 
+    /// noise handshake is awaiting act {0} from the remote peer and can't
+    /// write a message.
+    AwaitingRemote(u8),
+
+    /// noise handshake has to write act {0} before reading a message from the
+    /// remote peer.
+    AwaitingLocal(u8),
+
     /// noise handshake is complete, nothing to process.
     Complete,
+
+    /// remote peer has reused ephemeral key of a recent handshake.
+    EphemeralReuse,
 }
 
 pub trait Handshake: AsRef<[u8]> {
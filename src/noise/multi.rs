@@ -0,0 +1,191 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encryption of a payload to multiple recipients at once.
+//!
+//! The payload is encrypted once under a random content key, and the content
+//! key is wrapped for each of the recipients with a key derived from the
+//! ephemeral-static X25519 Diffie-Hellman between the sender ephemeral key and
+//! the recipient static key. The recipient list is not hidden: the number of
+//! recipients is visible from the message size.
+
+use alloc::vec::Vec;
+
+use ed25519::x25519::{PublicKey, SecretKey};
+use zeroize::Zeroizing;
+
+use super::hkdf::sha2_256 as hkdf;
+use super::{chacha, dh, EncryptionError, SymmetricKey};
+
+/// Size of the content key wrapped for a single recipient.
+pub const WRAPPED_KEY_SIZE: usize = 32 + chacha::TAG_SIZE;
+
+/// Payload encrypted to multiple recipients with [`seal_multi`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MultiRecipientMessage {
+    /// Ephemeral public key of the sender.
+    pub ephemeral: PublicKey,
+    /// Content key wrapped for each of the recipients, in the order of the
+    /// recipient list.
+    pub wrapped_keys: Vec<[u8; WRAPPED_KEY_SIZE]>,
+    /// Payload encrypted with the content key.
+    pub ciphertext: Vec<u8>,
+}
+
+/// Derives key wrapping the content key for the recipient from the
+/// Diffie-Hellman shared secret, binding both the ephemeral and the recipient
+/// public keys.
+fn wrapping_key(shared: &[u8; 32], ephemeral: &PublicKey, recipient: &PublicKey) -> SymmetricKey {
+    let prk = hkdf::extract(ephemeral.as_ref(), shared);
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&hkdf::expand(&prk, recipient.as_ref(), 32));
+    key
+}
+
+/// Encrypts the payload to all of the `recipients` using a random ephemeral
+/// key and content key; see [`seal_multi_with`].
+#[cfg(feature = "std")]
+pub fn seal_multi(
+    recipients: &[PublicKey],
+    payload: &[u8],
+) -> Result<MultiRecipientMessage, EncryptionError> {
+    let (ephemeral, _) = super::generate_keypair();
+    let content_key = Zeroizing::new(*ed25519::Seed::generate());
+    seal_multi_with(&ephemeral, &content_key, recipients, payload)
+}
+
+/// Encrypts the payload under the `content_key` and wraps the content key for
+/// each of the `recipients` using the `ephemeral` secret key. Both keys must be
+/// random and never reused.
+///
+/// # Errors
+///
+/// [`EncryptionError::InvalidRecipient`] if one of the recipient keys is a
+/// low-order point of the curve, and [`EncryptionError::MessageTooLong`] if
+/// the payload exceeds the AEAD limits.
+pub fn seal_multi_with(
+    ephemeral: &SecretKey,
+    content_key: &SymmetricKey,
+    recipients: &[PublicKey],
+    payload: &[u8],
+) -> Result<MultiRecipientMessage, EncryptionError> {
+    let ephemeral_pk = ephemeral.recover_public_key().expect("invalid secret key");
+    let wrapped_keys = recipients
+        .iter()
+        .enumerate()
+        .map(|(index, recipient)| {
+            let shared =
+                dh(ephemeral, recipient).map_err(|_| EncryptionError::InvalidRecipient(index))?;
+            let key = wrapping_key(&shared, &ephemeral_pk, recipient);
+            let mut wrapped = [0u8; WRAPPED_KEY_SIZE];
+            chacha::encrypt(&key, 0, &[], content_key, Some(&mut wrapped))?;
+            Ok(wrapped)
+        })
+        .collect::<Result<_, EncryptionError>>()?;
+    let ciphertext = chacha::encrypt(content_key, 0, ephemeral_pk.as_ref(), payload, None)?;
+    Ok(MultiRecipientMessage {
+        ephemeral: ephemeral_pk,
+        wrapped_keys,
+        ciphertext,
+    })
+}
+
+/// Decrypts the message sealed with [`seal_multi`] using the static `secret`
+/// key of one of the recipients. All of the wrapped keys are tried, so the
+/// time taken doesn't depend on the position of the recipient in the list.
+///
+/// # Errors
+///
+/// [`EncryptionError::NotRecipient`] if none of the wrapped content keys can
+/// be unwrapped with the secret key, or AEAD error if the payload is not
+/// authentic.
+pub fn open(secret: &SecretKey, msg: &MultiRecipientMessage) -> Result<Vec<u8>, EncryptionError> {
+    let shared = dh(secret, &msg.ephemeral).map_err(|_| EncryptionError::NotRecipient)?;
+    let recipient = secret.recover_public_key().expect("invalid secret key");
+    let key = wrapping_key(&shared, &msg.ephemeral, &recipient);
+    let mut content_key = Zeroizing::new([0u8; 32]);
+    let mut found = false;
+    for wrapped in &msg.wrapped_keys {
+        let mut buf = Zeroizing::new(*wrapped);
+        if let Ok(unwrapped) = chacha::decrypt_in_place_slice(&key, 0, &[], buf.as_mut()) {
+            if !found {
+                content_key.copy_from_slice(unwrapped);
+                found = true;
+            }
+        }
+    }
+    if !found {
+        return Err(EncryptionError::NotRecipient);
+    }
+    chacha::decrypt(&*content_key, 0, msg.ephemeral.as_ref(), &msg.ciphertext, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keypair(seed: u8) -> (SecretKey, PublicKey) {
+        let sk = SecretKey::new([seed; 32]);
+        let pk = sk.recover_public_key().unwrap();
+        (sk, pk)
+    }
+
+    #[test]
+    fn three_recipients() {
+        let recipients = [keypair(1), keypair(2), keypair(3)];
+        let pubkeys = recipients.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+        let (ephemeral, _) = keypair(9);
+        let msg = seal_multi_with(&ephemeral, &[7u8; 32], &pubkeys, b"group message").unwrap();
+        assert_eq!(msg.wrapped_keys.len(), 3);
+        assert_eq!(msg.ciphertext.len(), b"group message".len() + chacha::TAG_SIZE);
+        for (sk, _) in &recipients {
+            assert_eq!(open(sk, &msg).unwrap(), b"group message");
+        }
+
+        let (outsider, _) = keypair(4);
+        assert_eq!(open(&outsider, &msg), Err(EncryptionError::NotRecipient));
+
+        let mut tampered = msg.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(open(&recipients[0].0, &tampered).is_err());
+    }
+
+    #[test]
+    fn invalid_recipient() {
+        let (ephemeral, _) = keypair(9);
+        let recipients = [keypair(1).1, PublicKey::new([0u8; 32])];
+        assert_eq!(
+            seal_multi_with(&ephemeral, &[7u8; 32], &recipients, b"msg"),
+            Err(EncryptionError::InvalidRecipient(1))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn random_keys() {
+        let (sk, pk) = keypair(1);
+        let msg1 = seal_multi(&[pk], b"msg").unwrap();
+        let msg2 = seal_multi(&[pk], b"msg").unwrap();
+        assert_ne!(msg1, msg2);
+        assert_eq!(open(&sk, &msg1).unwrap(), b"msg");
+        assert_eq!(open(&sk, &msg2).unwrap(), b"msg");
+    }
+}
@@ -0,0 +1,311 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Online encryption of data streams of unknown length with the STREAM
+//! construction (Hoang, Reyhanitabar, Rogaway and Vizár, "Online
+//! Authenticated-Encryption and its Nonce-Reuse Misuse-Resistance") over
+//! ChaCha20Poly1305 AEAD.
+//!
+//! Each chunk is encrypted with a nonce made of the stream nonce prefix, the
+//! chunk number and a flag marking the last chunk, so the receiver detects
+//! reordered, dropped and truncated chunks. Encrypted chunks are
+//! self-delimiting: each starts with a header of [`CHUNK_HEADER_SIZE`] bytes
+//! containing the last chunk marker and the length of the chunk ciphertext,
+//! which is authenticated as the associated data.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::Payload;
+use chacha20poly1305::{ChaCha20Poly1305, Key};
+
+use super::{chacha, EncryptionError, SymmetricKey};
+
+const CHUNK: u8 = 0x00;
+const LAST_CHUNK: u8 = 0x01;
+
+/// Size of the header prefixing each encrypted chunk: the last chunk marker
+/// and the 32-bit big-endian length of the chunk ciphertext.
+pub const CHUNK_HEADER_SIZE: usize = 5;
+
+/// Size of the stream nonce prefix, which, together with the chunk number and
+/// the last chunk flag, makes ChaCha20Poly1305 nonce.
+pub const STREAM_NONCE_SIZE: usize = 7;
+
+/// Returns total length of the encrypted chunk starting at the beginning of
+/// the data, including its header, or `None` if the data don't contain the
+/// complete header. Allows to split received data into chunks.
+pub fn chunk_len(data: &[u8]) -> Option<usize> {
+    let header = data.get(..CHUNK_HEADER_SIZE)?;
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+    Some(CHUNK_HEADER_SIZE + len as usize)
+}
+
+/// Detects whether the encrypted chunk is marked as the last chunk of the
+/// stream. The marker is authenticated only when the chunk is decrypted.
+pub fn is_last_chunk(chunk: &[u8]) -> bool { chunk.first() == Some(&LAST_CHUNK) }
+
+fn chunk_header(
+    marker: u8,
+    plaintext_len: usize,
+) -> Result<[u8; CHUNK_HEADER_SIZE], EncryptionError> {
+    let len = u32::try_from(plaintext_len + chacha::TAG_SIZE)
+        .map_err(|_| EncryptionError::MessageTooLong(plaintext_len))?;
+    let mut header = [marker; CHUNK_HEADER_SIZE];
+    header[1..].copy_from_slice(&len.to_be_bytes());
+    Ok(header)
+}
+
+fn split_chunk(marker: u8, chunk: &[u8]) -> Result<(&[u8], &[u8]), EncryptionError> {
+    if chunk.first() != Some(&marker)
+        || chunk.len() < CHUNK_HEADER_SIZE + chacha::TAG_SIZE
+        || chunk_len(chunk) != Some(chunk.len())
+    {
+        return Err(EncryptionError::InvalidChunk(chunk.len()));
+    }
+    Ok(chunk.split_at(CHUNK_HEADER_SIZE))
+}
+
+/// Encryptor of a data stream of unknown length.
+pub struct StreamSeal {
+    encryptor: EncryptorBE32<ChaCha20Poly1305>,
+    counter: u32,
+}
+
+impl Debug for StreamSeal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSeal").field("counter", &self.counter).finish_non_exhaustive()
+    }
+}
+
+impl StreamSeal {
+    /// Constructs encryptor for a new stream. The same pair of the key and
+    /// nonce must never be used for more than a single stream.
+    pub fn new(key: SymmetricKey, nonce: [u8; STREAM_NONCE_SIZE]) -> Self {
+        StreamSeal {
+            encryptor: EncryptorBE32::new(Key::from_slice(&key), GenericArray::from_slice(&nonce)),
+            counter: 0,
+        }
+    }
+
+    /// Returns number of the chunks encrypted so far.
+    pub fn counter(&self) -> u32 { self.counter }
+
+    /// Encrypts next chunk of the stream, which is not the last one.
+    ///
+    /// # Errors
+    ///
+    /// If the chunk is too long for its length to fit the header or the
+    /// stream has reached the maximal number of chunks.
+    pub fn encrypt_next(&mut self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let header = chunk_header(CHUNK, chunk.len())?;
+        let encrypted = self.encryptor.encrypt_next(Payload {
+            msg: chunk,
+            aad: &header,
+        })?;
+        self.counter += 1;
+        Ok(join_chunk(header, encrypted))
+    }
+
+    /// Encrypts the last chunk of the stream, which may be empty, completing
+    /// the stream.
+    ///
+    /// # Errors
+    ///
+    /// If the chunk is too long for its length to fit the header.
+    pub fn encrypt_last(self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let header = chunk_header(LAST_CHUNK, chunk.len())?;
+        let encrypted = self.encryptor.encrypt_last(Payload {
+            msg: chunk,
+            aad: &header,
+        })?;
+        Ok(join_chunk(header, encrypted))
+    }
+}
+
+fn join_chunk(header: [u8; CHUNK_HEADER_SIZE], encrypted: Vec<u8>) -> Vec<u8> {
+    let mut data = Vec::with_capacity(CHUNK_HEADER_SIZE + encrypted.len());
+    data.extend_from_slice(&header);
+    data.extend_from_slice(&encrypted);
+    data
+}
+
+/// Decryptor of a data stream produced by [`StreamSeal`].
+pub struct StreamOpen {
+    decryptor: DecryptorBE32<ChaCha20Poly1305>,
+    counter: u32,
+}
+
+impl Debug for StreamOpen {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamOpen").field("counter", &self.counter).finish_non_exhaustive()
+    }
+}
+
+impl StreamOpen {
+    /// Constructs decryptor for a stream encrypted with the same key and
+    /// nonce.
+    pub fn new(key: SymmetricKey, nonce: [u8; STREAM_NONCE_SIZE]) -> Self {
+        StreamOpen {
+            decryptor: DecryptorBE32::new(Key::from_slice(&key), GenericArray::from_slice(&nonce)),
+            counter: 0,
+        }
+    }
+
+    /// Returns number of the chunks decrypted so far.
+    pub fn counter(&self) -> u32 { self.counter }
+
+    /// Decrypts next chunk of the stream, which must not be the last one (see
+    /// [`is_last_chunk`]). The chunk must be complete, with the length
+    /// matching its header (see [`chunk_len`]).
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::InvalidChunk`] if the chunk is malformed or marked
+    /// as the last one, and AEAD error if the chunk can't be authenticated
+    /// (for instance, since it was reordered or replayed).
+    pub fn decrypt_next(&mut self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let (header, encrypted) = split_chunk(CHUNK, chunk)?;
+        let plaintext = self.decryptor.decrypt_next(Payload {
+            msg: encrypted,
+            aad: header,
+        })?;
+        self.counter += 1;
+        Ok(plaintext)
+    }
+
+    /// Decrypts the last chunk of the stream, completing the stream. A stream
+    /// ending without a successfully decrypted last chunk is truncated and
+    /// must be rejected.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::InvalidChunk`] if the chunk is malformed or not
+    /// marked as the last one, and AEAD error if the chunk can't be
+    /// authenticated.
+    pub fn decrypt_last(self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let (header, encrypted) = split_chunk(LAST_CHUNK, chunk)?;
+        Ok(self.decryptor.decrypt_last(Payload {
+            msg: encrypted,
+            aad: header,
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: SymmetricKey = [0x42u8; 32];
+    const NONCE: [u8; STREAM_NONCE_SIZE] = [7u8; STREAM_NONCE_SIZE];
+
+    // Splits source data into chunks as they arrive, without knowing the
+    // total length in advance.
+    fn seal(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut seal = StreamSeal::new(KEY, NONCE);
+        let mut stream = vec![];
+        let mut chunks = data.chunks(chunk_size).peekable();
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_some() {
+                stream.extend(seal.encrypt_next(chunk).unwrap());
+            } else {
+                stream.extend(seal.encrypt_last(chunk).unwrap());
+                return stream;
+            }
+        }
+        stream.extend(seal.encrypt_last(b"").unwrap());
+        stream
+    }
+
+    fn open(mut stream: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let mut open = StreamOpen::new(KEY, NONCE);
+        let mut data = vec![];
+        while let Some(len) = chunk_len(stream) {
+            let len = len.min(stream.len());
+            let (chunk, rest) = stream.split_at(len);
+            if is_last_chunk(chunk) {
+                data.extend(open.decrypt_last(chunk)?);
+                return if rest.is_empty() {
+                    Ok(data)
+                } else {
+                    Err(EncryptionError::StreamFinished)
+                };
+            }
+            data.extend(open.decrypt_next(chunk)?);
+            stream = rest;
+        }
+        Err(EncryptionError::UnterminatedStream(open.counter() as u64))
+    }
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        for chunk_size in [1, 16, 100, 999, 1000, 2000] {
+            let stream = seal(&data, chunk_size);
+            assert_eq!(open(&stream).unwrap(), data);
+        }
+        assert_eq!(open(&seal(b"", 16)).unwrap(), b"");
+
+        let mut seal = StreamSeal::new(KEY, NONCE);
+        let chunk = seal.encrypt_next(b"data").unwrap();
+        assert_eq!(seal.counter(), 1);
+        assert_eq!(chunk.len(), CHUNK_HEADER_SIZE + 4 + chacha::TAG_SIZE);
+        assert_eq!(chunk_len(&chunk), Some(chunk.len()));
+        assert_eq!(chunk_len(&chunk[..4]), None);
+        assert!(!is_last_chunk(&chunk));
+        assert!(is_last_chunk(&seal.encrypt_last(b"").unwrap()));
+    }
+
+    #[test]
+    fn tampered() {
+        let data = [1u8; 48];
+        let stream = seal(&data, 16);
+        let chunk = CHUNK_HEADER_SIZE + 16 + chacha::TAG_SIZE;
+        assert_eq!(stream.len(), chunk * 3);
+
+        // Truncated stream: last chunk dropped
+        assert_eq!(open(&stream[..chunk * 2]), Err(EncryptionError::UnterminatedStream(2)));
+        // Non-last chunk marked as the last one
+        let mut forged = stream.clone();
+        forged[chunk] = LAST_CHUNK;
+        assert!(matches!(open(&forged[..chunk * 2]), Err(EncryptionError::ChaCha(_))));
+        // Reordered chunks
+        let mut reordered = stream[chunk..chunk * 2].to_vec();
+        reordered.extend(&stream[..chunk]);
+        reordered.extend(&stream[chunk * 2..]);
+        assert!(matches!(open(&reordered), Err(EncryptionError::ChaCha(_))));
+        // Different stream nonce
+        let mut open = StreamOpen::new(KEY, [8u8; STREAM_NONCE_SIZE]);
+        assert!(open.decrypt_next(&stream[..chunk]).is_err());
+
+        let mut open = StreamOpen::new(KEY, NONCE);
+        assert_eq!(
+            open.decrypt_next(&stream[..chunk - 1]),
+            Err(EncryptionError::InvalidChunk(chunk - 1))
+        );
+        assert_eq!(
+            StreamOpen::new(KEY, NONCE).decrypt_last(&stream[..chunk]),
+            Err(EncryptionError::InvalidChunk(chunk))
+        );
+    }
+}
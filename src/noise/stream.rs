@@ -0,0 +1,232 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking stream encrypting and decrypting data with the Noise transport.
+
+use std::io::{self, Read, Write};
+
+use super::framing::PaddingPolicy;
+use super::{EncryptionError, Transport};
+
+/// Blocking stream over an underlying reader/writer (like a TCP socket),
+/// encrypting all written data and decrypting all the data read with the
+/// Noise transport established by a completed handshake.
+///
+/// Each call to [`Write::write`] encrypts (a part of) the data into a single
+/// frame, which is appended to the encryptor output buffer and then written to
+/// the underlying stream. If the underlying stream accepts only a part of the
+/// frame, the rest remains buffered and is written with the following calls
+/// to [`Write::write`] or [`Write::flush`], so short writes never corrupt the
+/// stream. [`Write::flush`] must be called to ensure all buffered frames are
+/// sent.
+#[derive(Debug)]
+pub struct NoiseStream<S: Read + Write> {
+    inner: S,
+    transport: Transport,
+    read_pending: Vec<u8>,
+}
+
+impl<S: Read + Write> NoiseStream<S> {
+    /// Maximal length of the data encrypted into a single frame, accounting
+    /// for the padding length prefix.
+    const MAX_CHUNK_LEN: usize = u16::MAX as usize - PaddingPolicy::LEN_PREFIX_SIZE;
+
+    /// Constructs stream from the underlying stream and the Noise transport.
+    pub fn new(inner: S, transport: Transport) -> Self {
+        NoiseStream {
+            inner,
+            transport,
+            read_pending: vec![],
+        }
+    }
+
+    /// Returns reference to the underlying stream.
+    pub fn get_ref(&self) -> &S { &self.inner }
+
+    /// Returns mutable reference to the underlying stream. Reading or writing
+    /// it directly will corrupt the encrypted stream.
+    pub fn get_mut(&mut self) -> &mut S { &mut self.inner }
+
+    /// Returns the Noise transport used by the stream.
+    pub fn transport(&self) -> &Transport { &self.transport }
+
+    /// Returns the underlying stream and the Noise transport. Data which were
+    /// buffered and not yet flushed are retained in the transport encryptor
+    /// output buffer.
+    pub fn into_inner(self) -> (S, Transport) { (self.inner, self.transport) }
+
+    /// Writes buffered encrypted data to the underlying stream until the
+    /// buffer is empty or the underlying stream returns an error.
+    fn drain(&mut self) -> io::Result<()> {
+        let encryptor = self.transport.encryptor_mut();
+        while !encryptor.buffered().is_empty() {
+            match self.inner.write(encryptor.buffered()) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(len) => encryptor.consume(len),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn io_error(err: EncryptionError) -> io::Error {
+    match err {
+        EncryptionError::WouldBlock(_) => io::Error::new(io::ErrorKind::WouldBlock, err),
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+impl<S: Read + Write> Write for NoiseStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Frames which were not completely written before must be sent first
+        self.drain()?;
+        let chunk = &buf[..buf.len().min(Self::MAX_CHUNK_LEN)];
+        self.transport.encryptor_mut().encrypt_buffered(chunk).map_err(io_error)?;
+        // The frame is already buffered and its nonce is used, so the data are
+        // accepted even if the underlying stream can't take them right now;
+        // the error will be reported by the next write or flush.
+        let _ = self.drain();
+        Ok(chunk.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.inner.flush()
+    }
+}
+
+impl<S: Read + Write> Read for NoiseStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.read_pending.is_empty() {
+            let decryptor = self.transport.decryptor_mut();
+            if let Some(msg) = decryptor.decrypt_single_message(None).map_err(io_error)? {
+                self.read_pending = msg;
+                continue;
+            }
+            let mut data = [0u8; 4096];
+            let len = self.inner.read(&mut data)?;
+            if len == 0 {
+                return match self.transport.decryptor().read_buffer_len() {
+                    0 => Ok(0),
+                    _ => Err(io::ErrorKind::UnexpectedEof.into()),
+                };
+            }
+            self.transport.decryptor_mut().read_buf(&data[..len]);
+        }
+        let len = buf.len().min(self.read_pending.len());
+        buf[..len].copy_from_slice(&self.read_pending[..len]);
+        self.read_pending.drain(..len);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Writer accepting a single byte per call, with read side returning no
+    /// data.
+    #[derive(Default)]
+    struct ByteWriter {
+        written: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for ByteWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            // Every third call fails as if the socket is not ready
+            if self.calls % 3 == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.written.extend_from_slice(&buf[..buf.len().min(1)]);
+            Ok(buf.len().min(1))
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    impl Read for ByteWriter {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> { Ok(0) }
+    }
+
+    /// Stream with predefined data for reading, discarding written data.
+    struct Reader(Cursor<Vec<u8>>);
+
+    impl Read for Reader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.0.read(buf) }
+    }
+
+    impl Write for Reader {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn short_writes() {
+        let (initiator, responder) = Transport::test_pair();
+        let mut sender = NoiseStream::new(ByteWriter::default(), initiator);
+        let messages: [&[u8]; 3] = [b"first message", b"second", &[0xA5; 300]];
+        for msg in messages {
+            // Data are not accepted while the previous frames can't be sent
+            loop {
+                match sender.write(msg) {
+                    Ok(len) => break assert_eq!(len, msg.len()),
+                    Err(err) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
+                }
+            }
+        }
+        while let Err(err) = sender.flush() {
+            assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        }
+        assert!(sender.transport().encryptor().buffered().is_empty());
+        let (writer, _) = sender.into_inner();
+
+        let mut receiver = NoiseStream::new(Reader(Cursor::new(writer.written)), responder);
+        let mut received = vec![];
+        receiver.read_to_end(&mut received).unwrap();
+        assert_eq!(received, messages.concat());
+    }
+
+    #[test]
+    fn truncated_stream() {
+        let (mut initiator, responder) = Transport::test_pair();
+        let frame = initiator.send(b"message").unwrap();
+        let mut receiver =
+            NoiseStream::new(Reader(Cursor::new(frame[..frame.len() - 1].to_vec())), responder);
+        let mut received = vec![];
+        assert_eq!(
+            receiver.read_to_end(&mut received).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
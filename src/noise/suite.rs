@@ -0,0 +1,160 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Negotiation of the AEAD cipher suite used by the transport, carried in the
+//! handshake act payloads.
+//!
+//! The initiator proposes the list of the supported suites (one byte per
+//! suite, in the order of preference) as the payload of act one; the responder
+//! selects the first proposed suite it supports and confirms it with a
+//! single-byte payload of act two. The payloads are authenticated by the
+//! handshake, so the negotiation can't be downgraded by a network attacker.
+//!
+//! The negotiation is performed by [`NoiseXkState::write_proposal`],
+//! [`NoiseXkState::read_proposal`], [`NoiseXkState::write_confirmation`] and
+//! [`NoiseXkState::read_confirmation`], which record the agreed suite in the
+//! handshake state; the complete handshake is converted into a [`Transport`]
+//! using it with [`NoiseXkState::into_transport`].
+//!
+//! [`NoiseXkState::write_proposal`]: super::xk::NoiseXkState::write_proposal
+//! [`NoiseXkState::read_proposal`]: super::xk::NoiseXkState::read_proposal
+//! [`NoiseXkState::write_confirmation`]: super::xk::NoiseXkState::write_confirmation
+//! [`NoiseXkState::read_confirmation`]: super::xk::NoiseXkState::read_confirmation
+//! [`NoiseXkState::into_transport`]: super::xk::NoiseXkState::into_transport
+//! [`Transport`]: super::Transport
+
+use alloc::vec::Vec;
+
+use super::HandshakeError;
+
+/// AEAD cipher suite used for the transport encryption.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[repr(u8)]
+pub enum CipherSuite {
+    /// ChaCha20Poly1305 AEAD (RFC 8439).
+    #[display("ChaCha20-Poly1305")]
+    ChaCha20Poly1305 = 0x01,
+}
+
+impl CipherSuite {
+    /// All cipher suites supported by the library, in the order of preference.
+    pub const ALL: [CipherSuite; 1] = [CipherSuite::ChaCha20Poly1305];
+
+    /// Returns cipher suite with the given identifier, if known.
+    pub fn from_u8(id: u8) -> Option<Self> {
+        match id {
+            0x01 => Some(CipherSuite::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    /// Returns identifier of the cipher suite used in the handshake payloads.
+    pub const fn to_u8(self) -> u8 { self as u8 }
+
+    /// Constructs act one payload proposing the `suites`.
+    pub fn propose(suites: &[CipherSuite]) -> Vec<u8> {
+        suites.iter().map(|suite| suite.to_u8()).collect()
+    }
+
+    /// Selects the first cipher suite from the `proposal` received in act one
+    /// which is contained in the list of the `supported` suites. Unknown suite
+    /// identifiers are skipped.
+    ///
+    /// # Errors
+    ///
+    /// [`HandshakeError::NoCommonCipherSuite`] if none of the proposed suites is
+    /// supported; the handshake must be aborted.
+    pub fn select(proposal: &[u8], supported: &[CipherSuite]) -> Result<Self, HandshakeError> {
+        proposal
+            .iter()
+            .filter_map(|id| CipherSuite::from_u8(*id))
+            .find(|suite| supported.contains(suite))
+            .ok_or(HandshakeError::NoCommonCipherSuite)
+    }
+
+    /// Constructs act two payload confirming the selected suite.
+    pub fn confirm(self) -> [u8; 1] { [self.to_u8()] }
+
+    /// Checks the `confirmation` received in act two against the `proposed`
+    /// suites, returning the agreed suite.
+    ///
+    /// # Errors
+    ///
+    /// If the confirmation is not a single byte, or the confirmed suite is
+    /// unknown or was not proposed; the handshake must be aborted.
+    pub fn verify(confirmation: &[u8], proposed: &[CipherSuite]) -> Result<Self, HandshakeError> {
+        let [id] = confirmation else {
+            return Err(HandshakeError::InvalidSuiteConfirmation(confirmation.len()));
+        };
+        CipherSuite::from_u8(*id)
+            .filter(|suite| proposed.contains(suite))
+            .ok_or(HandshakeError::UnexpectedCipherSuite(*id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn select() {
+        let proposal = [0x7F, 0x01, 0x02];
+        assert_eq!(
+            CipherSuite::select(&proposal, &CipherSuite::ALL),
+            Ok(CipherSuite::ChaCha20Poly1305)
+        );
+        assert_eq!(
+            CipherSuite::select(&[0x7F], &CipherSuite::ALL),
+            Err(HandshakeError::NoCommonCipherSuite)
+        );
+        assert_eq!(
+            CipherSuite::select(&[], &CipherSuite::ALL),
+            Err(HandshakeError::NoCommonCipherSuite)
+        );
+        assert_eq!(CipherSuite::select(&proposal, &[]), Err(HandshakeError::NoCommonCipherSuite));
+    }
+
+    #[test]
+    fn verify() {
+        let proposed = CipherSuite::ALL;
+        let confirmation = CipherSuite::ChaCha20Poly1305.confirm();
+        assert_eq!(
+            CipherSuite::verify(&confirmation, &proposed),
+            Ok(CipherSuite::ChaCha20Poly1305)
+        );
+        assert_eq!(
+            CipherSuite::verify(&confirmation, &[]),
+            Err(HandshakeError::UnexpectedCipherSuite(0x01))
+        );
+        assert_eq!(
+            CipherSuite::verify(&[0x7F], &proposed),
+            Err(HandshakeError::UnexpectedCipherSuite(0x7F))
+        );
+        assert_eq!(
+            CipherSuite::verify(&[], &proposed),
+            Err(HandshakeError::InvalidSuiteConfirmation(0))
+        );
+        assert_eq!(
+            CipherSuite::verify(&[0x01, 0x01], &proposed),
+            Err(HandshakeError::InvalidSuiteConfirmation(2))
+        );
+    }
+}
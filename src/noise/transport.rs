@@ -0,0 +1,808 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transport phase of the Noise protocol, combining the sending and receiving
+//! cipher states of the local party.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+
+use ed25519::x25519::PublicKey;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::framing::{NoiseDecryptor, NoiseEncryptor, PaddingPolicy};
+use super::hkdf::sha2_256 as hkdf;
+use super::{CipherSuite, EncryptionError, SymmetricKey};
+
+/// Input keying material distinguishing application keys derivation from the
+/// derivation of the transport keys.
+const KEY_DERIVATION_TAG: &[u8] = b"cyphernet noise application key";
+
+/// Role of the local party in the Noise handshake.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum Role {
+    /// The party which has initiated the handshake.
+    Initiator,
+
+    /// The party which has responded to the handshake.
+    Responder,
+}
+
+/// Pair of the sending and receiving cipher states of the local party,
+/// assigned according to its role in the handshake.
+#[derive(Clone, Debug)]
+pub struct Transport {
+    role: Role,
+    cipher_suite: CipherSuite,
+    session_key: SymmetricKey,
+    encryptor: NoiseEncryptor,
+    decryptor: NoiseDecryptor,
+}
+
+/// Raw key material of an established [`Transport`], exported for handing the
+/// session over to an external component (for instance, a transport offload
+/// engine).
+///
+/// # Security
+///
+/// The structure contains secret keys which allow to decrypt and forge all
+/// messages of the session. It must never be logged, persisted or sent over
+/// the network unencrypted, and should be zeroized once not needed. The
+/// exported keys must be used by a single cipher state only: continuing to
+/// use them in two places at once results in nonce reuse, breaking both the
+/// confidentiality and the authenticity of the session.
+///
+/// The keys are zeroized on drop, and are not printed by the [`Debug`]
+/// implementation.
+#[derive(Clone, Eq, PartialEq)]
+pub struct TransportKeys {
+    /// Key used for encrypting messages sent to the remote peer.
+    pub send: SymmetricKey,
+    /// Key used for decrypting messages received from the remote peer.
+    pub recv: SymmetricKey,
+    /// Nonce of the next message encrypted with the `send` key.
+    pub send_nonce: u64,
+    /// Nonce of the next message decrypted with the `recv` key.
+    pub recv_nonce: u64,
+    /// Sequence number of the next frame sent to the remote peer; see
+    /// [`NoiseEncryptor::sequence`].
+    pub send_sequence: u64,
+    /// Sequence number of the next frame received from the remote peer; see
+    /// [`NoiseDecryptor::sequence`].
+    pub recv_sequence: u64,
+    /// Chaining key from which the next `send` key is derived on rotation.
+    pub send_chaining_key: SymmetricKey,
+    /// Chaining key from which the next `recv` key is derived on rotation.
+    pub recv_chaining_key: SymmetricKey,
+    /// Number of nonces after which the keys are rotated.
+    pub key_rotation_period: u32,
+    /// Key from which application keys are derived; see
+    /// [`Transport::derive_key`].
+    pub session_key: SymmetricKey,
+}
+
+impl Debug for TransportKeys {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransportKeys")
+            .field("send", &"<redacted>")
+            .field("recv", &"<redacted>")
+            .field("send_nonce", &self.send_nonce)
+            .field("recv_nonce", &self.recv_nonce)
+            .field("send_sequence", &self.send_sequence)
+            .field("recv_sequence", &self.recv_sequence)
+            .field("send_chaining_key", &"<redacted>")
+            .field("recv_chaining_key", &"<redacted>")
+            .field("key_rotation_period", &self.key_rotation_period)
+            .field("session_key", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Zeroize for TransportKeys {
+    fn zeroize(&mut self) {
+        self.send.zeroize();
+        self.recv.zeroize();
+        self.send_nonce.zeroize();
+        self.recv_nonce.zeroize();
+        self.send_sequence.zeroize();
+        self.recv_sequence.zeroize();
+        self.send_chaining_key.zeroize();
+        self.recv_chaining_key.zeroize();
+        self.key_rotation_period.zeroize();
+        self.session_key.zeroize();
+    }
+}
+
+impl Drop for TransportKeys {
+    fn drop(&mut self) { self.zeroize() }
+}
+
+impl ZeroizeOnDrop for TransportKeys {}
+
+impl Transport {
+    /// Constructs transport from the keys produced by the final split of the
+    /// handshake. `initiator_key` is used for encrypting messages sent by the
+    /// initiator, and `responder_key` for messages sent by the responder;
+    /// which of them is used for sending and which for receiving is
+    /// determined by the local `role`.
+    pub fn with_keys(
+        role: Role,
+        chaining_key: SymmetricKey,
+        initiator_key: SymmetricKey,
+        responder_key: SymmetricKey,
+        remote_pubkey: PublicKey,
+    ) -> Self {
+        let (sending_key, receiving_key) = match role {
+            Role::Initiator => (initiator_key, responder_key),
+            Role::Responder => (responder_key, initiator_key),
+        };
+        Transport {
+            role,
+            cipher_suite: CipherSuite::ChaCha20Poly1305,
+            session_key: chaining_key,
+            encryptor: NoiseEncryptor::new(sending_key, chaining_key, remote_pubkey),
+            decryptor: NoiseDecryptor::new(receiving_key, chaining_key, remote_pubkey),
+        }
+    }
+
+    /// Constructs transport from already assigned encryptor and decryptor.
+    /// The application keys (see [`Transport::derive_key`]) are bound to the
+    /// current chaining key of the encryptor, thus the encryptor must not have
+    /// rotated its keys yet.
+    ///
+    /// # Panics
+    ///
+    /// If the encryptor and decryptor belong to different remote peers.
+    pub fn with_split(role: Role, encryptor: NoiseEncryptor, decryptor: NoiseDecryptor) -> Self {
+        assert_eq!(
+            encryptor.remote_pubkey, decryptor.remote_pubkey,
+            "unrelated Noise encryptor and decryptor objects"
+        );
+        Transport {
+            role,
+            cipher_suite: CipherSuite::ChaCha20Poly1305,
+            session_key: encryptor.sending_chaining_key,
+            encryptor,
+            decryptor,
+        }
+    }
+
+    /// Sets cipher suite agreed during the handshake.
+    pub(crate) fn with_cipher_suite(mut self, suite: CipherSuite) -> Self {
+        self.cipher_suite = suite;
+        self
+    }
+
+    /// Constructs a pair of connected transports of the initiator and the
+    /// responder, sharing fresh random keys, for testing code built on top of
+    /// the transport without performing the handshake. Frames sent by one of
+    /// the transports are received by the other one.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn test_pair() -> (Transport, Transport) {
+        use rand_core::{OsRng, RngCore};
+
+        let mut keys = [[0u8; 32]; 3];
+        for key in &mut keys {
+            OsRng.fill_bytes(key);
+        }
+        let [chaining_key, initiator_key, responder_key] = keys;
+        let (_, initiator_pk) = super::generate_keypair_with(&mut OsRng);
+        let (_, responder_pk) = super::generate_keypair_with(&mut OsRng);
+        let initiator = Transport::with_keys(
+            Role::Initiator,
+            chaining_key,
+            initiator_key,
+            responder_key,
+            responder_pk,
+        );
+        let responder = Transport::with_keys(
+            Role::Responder,
+            chaining_key,
+            initiator_key,
+            responder_key,
+            initiator_pk,
+        );
+        (initiator, responder)
+    }
+
+    /// Re-creates transport from the key material exported with
+    /// [`Transport::export_keys`], continuing the session from the exported
+    /// nonces and frame sequence numbers. Framing options (padding policy, associated data and
+    /// heartbeats) are not part of the key material and have to be configured
+    /// again.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::InvalidCheckpoint`] if any of the nonces doesn't fit
+    /// into the key rotation period (including the case of zero period).
+    pub fn from_checkpoint(
+        role: Role,
+        keys: TransportKeys,
+        remote_pubkey: PublicKey,
+    ) -> Result<Self, EncryptionError> {
+        let period = keys.key_rotation_period;
+        if let Some(nonce) =
+            [keys.send_nonce, keys.recv_nonce].into_iter().find(|nonce| *nonce >= period as u64)
+        {
+            return Err(EncryptionError::InvalidCheckpoint { nonce, period });
+        }
+        let mut encryptor = NoiseEncryptor::new(keys.send, keys.send_chaining_key, remote_pubkey);
+        encryptor.sending_nonce = keys.send_nonce as u32;
+        encryptor.sequence = keys.send_sequence;
+        encryptor.key_rotation_period = period;
+        let mut decryptor = NoiseDecryptor::new(keys.recv, keys.recv_chaining_key, remote_pubkey);
+        decryptor.receiving_nonce = keys.recv_nonce as u32;
+        decryptor.sequence = keys.recv_sequence;
+        decryptor.key_rotation_period = period;
+        Ok(Transport {
+            role,
+            cipher_suite: CipherSuite::ChaCha20Poly1305,
+            session_key: keys.session_key,
+            encryptor,
+            decryptor,
+        })
+    }
+
+    /// Consumes the transport, exporting its raw key material; see
+    /// [`TransportKeys`] for the security considerations. The transport can be
+    /// re-created from the keys with [`Transport::from_checkpoint`].
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::PendingFrame`] if the decryptor holds received data
+    /// of a frame which is not yet decrypted, since the export would lose
+    /// them. The transport is returned back, so the rest of the frame can be
+    /// received before retrying the export.
+    ///
+    /// # Panics
+    ///
+    /// If the sending and receiving keys use different rotation periods.
+    #[allow(clippy::result_large_err)]
+    pub fn export_keys(self) -> Result<TransportKeys, (Self, EncryptionError)> {
+        assert_eq!(
+            self.encryptor.key_rotation_period, self.decryptor.key_rotation_period,
+            "asymmetric key rotation periods can't be exported"
+        );
+        let pending = self.decryptor.read_buffer_len();
+        if pending > 0 || self.decryptor.pending_message_length.is_some() {
+            return Err((self, EncryptionError::PendingFrame(pending)));
+        }
+        Ok(TransportKeys {
+            send: self.encryptor.sending_key,
+            recv: self.decryptor.receiving_key,
+            send_nonce: self.encryptor.sending_nonce as u64,
+            recv_nonce: self.decryptor.receiving_nonce as u64,
+            send_sequence: self.encryptor.sequence,
+            recv_sequence: self.decryptor.sequence,
+            send_chaining_key: self.encryptor.sending_chaining_key,
+            recv_chaining_key: self.decryptor.receiving_chaining_key,
+            key_rotation_period: self.encryptor.key_rotation_period,
+            session_key: self.session_key,
+        })
+    }
+
+    /// Returns role of the local party in the handshake.
+    pub fn role(&self) -> Role { self.role }
+
+    /// Returns AEAD cipher suite used by the transport.
+    pub fn cipher_suite(&self) -> CipherSuite { self.cipher_suite }
+
+    /// Returns static public key of the remote peer.
+    pub fn remote_pubkey(&self) -> PublicKey { self.encryptor.remote_pubkey }
+
+    pub fn encryptor(&self) -> &NoiseEncryptor { &self.encryptor }
+
+    pub fn decryptor(&self) -> &NoiseDecryptor { &self.decryptor }
+
+    pub fn encryptor_mut(&mut self) -> &mut NoiseEncryptor { &mut self.encryptor }
+
+    pub fn decryptor_mut(&mut self) -> &mut NoiseDecryptor { &mut self.decryptor }
+
+    /// Derives application-specific key bound to the session and the `info`
+    /// context. Both parties derive the same key for the same `info`, and the
+    /// derived keys are independent from the keys used by the transport
+    /// itself, including after their rotation.
+    pub fn derive_key(&self, info: &[u8]) -> SymmetricKey {
+        let prk = hkdf::extract(&self.session_key, KEY_DERIVATION_TAG);
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&hkdf::expand(&prk, info, 32));
+        key
+    }
+
+    pub fn into_split(self) -> (NoiseEncryptor, NoiseDecryptor) { (self.encryptor, self.decryptor) }
+
+    /// Encrypts message into a frame to be sent to the remote peer.
+    pub fn send(&mut self, msg: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        self.encryptor.encrypt_buf(msg)
+    }
+
+    /// Encrypts heartbeat frame to be sent to the remote peer; see
+    /// [`NoiseEncryptor::encrypt_heartbeat`].
+    #[cfg(feature = "std")]
+    pub fn send_heartbeat(&mut self) -> Result<Vec<u8>, EncryptionError> {
+        self.encryptor.encrypt_heartbeat()
+    }
+
+    /// Decrypts a frame received from the remote peer. Heartbeat frames are
+    /// dropped.
+    ///
+    /// # Returns
+    ///
+    /// The decrypted message, or `None` if the frame contained only
+    /// heartbeats.
+    ///
+    /// # Errors
+    ///
+    /// If the frame doesn't contain a complete message, the data are kept in
+    /// the internal buffer and [`EncryptionError::IncompleteFrame`] is
+    /// returned; the remaining part of the frame may be provided with the next
+    /// call.
+    pub fn recv(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, EncryptionError> {
+        let heartbeat_count = self.decryptor.heartbeat_count();
+        match self.decryptor.decrypt_single_message(Some(frame))? {
+            Some(msg) => Ok(Some(msg)),
+            None if self.decryptor.read_buffer_len() == 0
+                && self.decryptor.heartbeat_count() > heartbeat_count =>
+            {
+                Ok(None)
+            }
+            None => Err(EncryptionError::IncompleteFrame(frame.len())),
+        }
+    }
+}
+
+/// Builder of a [`Transport`] with non-default options, applied to both the
+/// sending and receiving cipher states. The cipher suite is not configurable:
+/// it is the one agreed during the handshake (see
+/// [`super::xk::NoiseXkState::into_transport`]).
+#[derive(Clone, Debug)]
+pub struct TransportBuilder {
+    transport: Transport,
+    rekey_after: Option<u32>,
+    padding_policy: PaddingPolicy,
+    sequence_aad: bool,
+    aad_prefix: Vec<u8>,
+    heartbeat_len: Option<u16>,
+}
+
+impl TransportBuilder {
+    /// Starts building transport from the keys produced by the final split of
+    /// the handshake; see [`Transport::with_keys`].
+    pub fn with_keys(
+        role: Role,
+        chaining_key: SymmetricKey,
+        initiator_key: SymmetricKey,
+        responder_key: SymmetricKey,
+        remote_pubkey: PublicKey,
+    ) -> Self {
+        Self::with_transport(Transport::with_keys(
+            role,
+            chaining_key,
+            initiator_key,
+            responder_key,
+            remote_pubkey,
+        ))
+    }
+
+    /// Starts building transport from already assigned encryptor and
+    /// decryptor; see [`Transport::with_split`].
+    ///
+    /// # Panics
+    ///
+    /// If the encryptor and decryptor belong to different remote peers.
+    pub fn with_split(role: Role, encryptor: NoiseEncryptor, decryptor: NoiseDecryptor) -> Self {
+        Self::with_transport(Transport::with_split(role, encryptor, decryptor))
+    }
+
+    /// Starts building from an existing transport, like the one produced by
+    /// the handshake, preserving its cipher suite.
+    pub fn with_transport(transport: Transport) -> Self {
+        TransportBuilder {
+            rekey_after: None,
+            padding_policy: transport.encryptor.padding_policy(),
+            sequence_aad: transport.encryptor.sequence_aad(),
+            aad_prefix: transport.encryptor.aad_prefix().to_vec(),
+            heartbeat_len: transport.encryptor.heartbeat_len(),
+            transport,
+        }
+    }
+
+    /// Sets number of messages after which the keys are rotated, instead of
+    /// the default [`super::framing::KEY_ROTATION_PERIOD`] nonces (each
+    /// message uses two nonces).
+    ///
+    /// The period applies to the current keys, so for a transport which has
+    /// already used some nonces it must exceed them; this is checked by
+    /// [`TransportBuilder::build`].
+    ///
+    /// # Panics
+    ///
+    /// If the number of messages is zero or exceeds `u32::MAX / 2`.
+    pub fn rekey_after(mut self, messages: u32) -> Self {
+        assert!(
+            messages > 0 && messages <= u32::MAX / 2,
+            "invalid number of messages for the key rotation"
+        );
+        self.rekey_after = Some(messages);
+        self
+    }
+
+    /// Sets padding policy; see [`PaddingPolicy`].
+    pub fn padding_policy(mut self, policy: PaddingPolicy) -> Self {
+        self.padding_policy = policy;
+        self
+    }
+
+    /// Enables or disables inclusion of the frame sequence number into the
+    /// associated data; see [`NoiseEncryptor::set_sequence_aad`].
+    pub fn sequence_aad(mut self, enabled: bool) -> Self {
+        self.sequence_aad = enabled;
+        self
+    }
+
+    /// Sets domain separation prefix of the associated data; see
+    /// [`NoiseEncryptor::set_aad_prefix`].
+    pub fn aad_prefix(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.aad_prefix = prefix.into();
+        self
+    }
+
+    /// Enables heartbeat frames with `len` bytes of random padding; see
+    /// [`NoiseEncryptor::set_heartbeat_len`].
+    pub fn heartbeats(mut self, len: u16) -> Self {
+        self.heartbeat_len = Some(len);
+        self
+    }
+
+    /// Constructs the configured transport.
+    ///
+    /// # Errors
+    ///
+    /// [`EncryptionError::InvalidRotationPeriod`] if the key rotation period
+    /// set with [`TransportBuilder::rekey_after`] doesn't exceed the nonces
+    /// already used by the sending or receiving key of the transport.
+    pub fn build(self) -> Result<Transport, EncryptionError> {
+        let mut transport = self.transport;
+        if let Some(messages) = self.rekey_after {
+            let period = messages * 2;
+            let nonce = transport.encryptor.sending_nonce.max(transport.decryptor.receiving_nonce);
+            if period <= nonce {
+                return Err(EncryptionError::InvalidRotationPeriod {
+                    nonce: nonce as u64,
+                    period,
+                });
+            }
+            transport.encryptor.set_key_rotation_period(period);
+            transport.decryptor.set_key_rotation_period(period);
+        }
+        transport.encryptor.set_padding_policy(self.padding_policy);
+        transport.decryptor.set_padding_policy(self.padding_policy);
+        transport.encryptor.set_sequence_aad(self.sequence_aad);
+        transport.decryptor.set_sequence_aad(self.sequence_aad);
+        transport.decryptor.set_aad_prefix(self.aad_prefix.clone());
+        transport.encryptor.set_aad_prefix(self.aad_prefix);
+        transport.encryptor.set_heartbeat_len(self.heartbeat_len);
+        transport.decryptor.set_heartbeats(self.heartbeat_len.is_some());
+        Ok(transport)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pair() -> (Transport, Transport) {
+        let initiator_pk = PublicKey::new([1u8; 32]);
+        let responder_pk = PublicKey::new([2u8; 32]);
+        let initiator =
+            Transport::with_keys(Role::Initiator, [0u8; 32], [3u8; 32], [4u8; 32], responder_pk);
+        let responder =
+            Transport::with_keys(Role::Responder, [0u8; 32], [3u8; 32], [4u8; 32], initiator_pk);
+        (initiator, responder)
+    }
+
+    #[test]
+    fn builder() {
+        let build = |transport| {
+            TransportBuilder::with_transport(transport)
+                .rekey_after(3)
+                .padding_policy(PaddingPolicy::FixedBucket(64))
+                .sequence_aad(true)
+                .build()
+                .unwrap()
+        };
+        let (initiator, responder) = pair();
+        let mut initiator = build(initiator);
+        let mut responder = build(responder);
+        assert_eq!(initiator.cipher_suite(), CipherSuite::ChaCha20Poly1305);
+        assert_eq!(initiator.encryptor().padding_policy(), PaddingPolicy::FixedBucket(64));
+        assert!(responder.decryptor().sequence_aad());
+
+        let key = initiator.encryptor().sending_key;
+        for no in 0..7u8 {
+            let frame = initiator.send(&[no]).unwrap();
+            assert_eq!(frame.len(), NoiseEncryptor::TAGGED_MESSAGE_LENGTH_HEADER_SIZE + 64 + 16);
+            assert_eq!(responder.recv(&frame).unwrap().unwrap(), [no]);
+            // Key is rotated after each three messages
+            assert_eq!(initiator.encryptor().sending_key == key, no < 2);
+            if no == 2 {
+                assert_eq!(initiator.encryptor().sending_nonce, 0);
+            }
+        }
+
+        // Peer using default key rotation can't decrypt messages after the
+        // rotation
+        let (mut initiator, _) = pair();
+        let mut initiator_rekey = build(pair().0);
+        initiator_rekey.encryptor_mut().set_padding_policy(PaddingPolicy::None);
+        initiator_rekey.encryptor_mut().set_sequence_aad(false);
+        for _ in 0..3 {
+            assert_eq!(initiator.send(b"msg").unwrap(), initiator_rekey.send(b"msg").unwrap());
+        }
+        assert_ne!(initiator.send(b"msg").unwrap(), initiator_rekey.send(b"msg").unwrap());
+    }
+
+    #[test]
+    fn builder_used_nonces() {
+        let (mut initiator, _) = pair();
+        for _ in 0..2 {
+            initiator.send(b"msg").unwrap();
+        }
+        let builder = TransportBuilder::with_transport(initiator);
+        assert_eq!(
+            builder.clone().rekey_after(2).build().unwrap_err(),
+            EncryptionError::InvalidRotationPeriod {
+                nonce: 4,
+                period: 4
+            }
+        );
+        let initiator = builder.rekey_after(3).build().unwrap();
+        assert_eq!(initiator.encryptor().messages_until_rekey(), 1);
+    }
+
+    #[test]
+    fn messages_until_rekey() {
+        let (initiator, responder) = pair();
+        let mut initiator =
+            TransportBuilder::with_transport(initiator).rekey_after(3).build().unwrap();
+        let mut responder =
+            TransportBuilder::with_transport(responder).rekey_after(3).build().unwrap();
+        for expected in [3, 2, 1, 3, 2, 1, 3] {
+            assert_eq!(initiator.encryptor().messages_until_rekey(), expected);
+            assert_eq!(responder.decryptor().messages_until_rekey(), expected);
+            let frame = initiator.send(b"msg").unwrap();
+            responder.recv(&frame).unwrap();
+        }
+
+        let (initiator, _) = pair();
+        assert_eq!(
+            initiator.encryptor().messages_until_rekey(),
+            crate::noise::framing::KEY_ROTATION_PERIOD as u64 / 2
+        );
+        // Rotation in the middle of a message is counted as the message
+        let mut encryptor = initiator.into_split().0;
+        encryptor.set_key_rotation_period(5);
+        assert_eq!(encryptor.messages_until_rekey(), 3);
+        encryptor.encrypt_buf(b"msg").unwrap();
+        encryptor.encrypt_buf(b"msg").unwrap();
+        assert_eq!(encryptor.messages_until_rekey(), 1);
+        encryptor.encrypt_buf(b"msg").unwrap();
+        assert_eq!(encryptor.messages_until_rekey(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn heartbeats() {
+        let build = |transport| {
+            TransportBuilder::with_transport(transport)
+                .padding_policy(PaddingPolicy::FixedBucket(64))
+                .heartbeats(61)
+                .build()
+                .unwrap()
+        };
+        let (initiator, responder) = pair();
+        let mut initiator = build(initiator);
+        let mut responder = build(responder);
+
+        let heartbeat = initiator.send_heartbeat().unwrap();
+        let frame = initiator.send(b"msg").unwrap();
+        // Heartbeats are indistinguishable from the data frames by their size
+        assert_eq!(heartbeat.len(), frame.len());
+        assert_eq!(responder.recv(&heartbeat), Ok(None));
+        assert_eq!(responder.decryptor().heartbeat_count(), 1);
+        assert_eq!(responder.recv(&frame).unwrap().unwrap(), b"msg");
+
+        // Heartbeat followed by a part of the next frame
+        let mut data = initiator.send_heartbeat().unwrap();
+        let frame = initiator.send(b"next").unwrap();
+        data.extend_from_slice(&frame[..20]);
+        assert_eq!(responder.recv(&data), Err(EncryptionError::IncompleteFrame(data.len())));
+        assert_eq!(responder.decryptor().heartbeat_count(), 2);
+        assert_eq!(responder.recv(&frame[20..]).unwrap().unwrap(), b"next");
+    }
+
+    #[test]
+    fn roles() {
+        let (mut initiator, mut responder) = pair();
+        assert_eq!(initiator.role(), Role::Initiator);
+        assert_eq!(responder.role(), Role::Responder);
+        assert_eq!(initiator.remote_pubkey(), PublicKey::new([2u8; 32]));
+
+        let frame = initiator.send(b"ping").unwrap();
+        assert_eq!(responder.recv(&frame).unwrap().unwrap(), b"ping");
+        let frame = responder.send(b"pong").unwrap();
+        assert_eq!(initiator.recv(&frame).unwrap().unwrap(), b"pong");
+
+        // Messages can't be decrypted by the sender itself
+        let frame = initiator.send(b"ping").unwrap();
+        assert!(initiator.recv(&frame).is_err());
+    }
+
+    #[test]
+    fn derive_key() {
+        let (initiator, mut responder) = pair();
+        let key = initiator.derive_key(b"app/v1");
+        assert_eq!(key, initiator.derive_key(b"app/v1"));
+        assert_eq!(key, responder.derive_key(b"app/v1"));
+        assert_ne!(key, initiator.derive_key(b"app/v2"));
+        assert_ne!(initiator.derive_key(b""), initiator.encryptor().sending_key);
+        assert_ne!(initiator.derive_key(b""), initiator.decryptor().receiving_key);
+
+        // Key rotation doesn't affect application keys
+        let mut initiator =
+            TransportBuilder::with_transport(initiator).rekey_after(1).build().unwrap();
+        let frame = initiator.send(b"ping").unwrap();
+        responder.recv(&frame).unwrap();
+        assert_eq!(initiator.derive_key(b"app/v1"), key);
+
+        // Keys are bound to the session
+        let other = Transport::with_keys(
+            Role::Initiator,
+            [5u8; 32],
+            [3u8; 32],
+            [4u8; 32],
+            PublicKey::new([2u8; 32]),
+        );
+        assert_ne!(other.derive_key(b"app/v1"), key);
+    }
+
+    #[test]
+    fn export_keys() {
+        let (initiator, responder) = pair();
+        let mut initiator =
+            TransportBuilder::with_transport(initiator).rekey_after(2).build().unwrap();
+        let mut responder =
+            TransportBuilder::with_transport(responder).rekey_after(2).build().unwrap();
+        let key = initiator.derive_key(b"app/v1");
+        let frame = initiator.send(b"ping").unwrap();
+        assert_eq!(responder.recv(&frame).unwrap().unwrap(), b"ping");
+        let frame = responder.send(b"pong").unwrap();
+        assert_eq!(initiator.recv(&frame).unwrap().unwrap(), b"pong");
+
+        let keys = initiator.export_keys().unwrap();
+        assert_eq!(keys.send_nonce, 2);
+        assert_eq!(keys.recv_nonce, 2);
+        assert_eq!(keys.key_rotation_period, 4);
+        let pubkey = PublicKey::new([2u8; 32]);
+        let mut initiator = Transport::from_checkpoint(Role::Initiator, keys, pubkey).unwrap();
+        assert_eq!(initiator.derive_key(b"app/v1"), key);
+        assert_eq!(initiator.role(), Role::Initiator);
+        assert_eq!(initiator.remote_pubkey(), pubkey);
+        // Session continues after the import, including through key rotation
+        for no in 0..5u8 {
+            let frame = initiator.send(&[no]).unwrap();
+            assert_eq!(responder.recv(&frame).unwrap().unwrap(), [no]);
+            let frame = responder.send(&[no]).unwrap();
+            assert_eq!(initiator.recv(&frame).unwrap().unwrap(), [no]);
+        }
+    }
+
+    #[test]
+    fn checkpoint_sequence_aad() {
+        let build = |transport| {
+            TransportBuilder::with_transport(transport).sequence_aad(true).build().unwrap()
+        };
+        let (initiator, responder) = pair();
+        let (mut initiator, mut responder) = (build(initiator), build(responder));
+        for msg in [&b"first"[..], b"second"] {
+            let frame = initiator.send(msg).unwrap();
+            assert_eq!(responder.recv(&frame).unwrap().unwrap(), msg);
+        }
+
+        // Export is refused while a frame is partially received
+        let frame = initiator.send(b"third").unwrap();
+        assert_eq!(responder.recv(&frame[..20]), Err(EncryptionError::IncompleteFrame(20)));
+        let (mut responder, err) = responder.export_keys().unwrap_err();
+        assert_eq!(err, EncryptionError::PendingFrame(20));
+        assert_eq!(responder.recv(&frame[20..]).unwrap().unwrap(), b"third");
+
+        let keys = responder.export_keys().unwrap();
+        assert_eq!(keys.recv_sequence, 3);
+        assert_eq!(keys.send_sequence, 0);
+        let pubkey = PublicKey::new([1u8; 32]);
+        let responder = Transport::from_checkpoint(Role::Responder, keys, pubkey).unwrap();
+        let mut responder = build(responder);
+        let frame = initiator.send(b"fourth").unwrap();
+        assert_eq!(responder.recv(&frame).unwrap().unwrap(), b"fourth");
+        assert_eq!(responder.decryptor().sequence(), 4);
+    }
+
+    #[test]
+    fn checkpoint_nonce_overflow() {
+        let (initiator, _) = pair();
+        let mut keys = initiator.export_keys().unwrap();
+        let period = keys.key_rotation_period;
+        keys.recv_nonce = period as u64;
+        assert_eq!(
+            Transport::from_checkpoint(Role::Initiator, keys.clone(), PublicKey::new([2u8; 32]))
+                .unwrap_err(),
+            EncryptionError::InvalidCheckpoint {
+                nonce: period as u64,
+                period
+            }
+        );
+        keys.recv_nonce = 0;
+        keys.key_rotation_period = 0;
+        assert!(
+            Transport::from_checkpoint(Role::Initiator, keys, PublicKey::new([2u8; 32])).is_err()
+        );
+    }
+
+    #[test]
+    fn checkpoint_redacted() {
+        let (initiator, _) = pair();
+        let mut keys = initiator.export_keys().unwrap();
+        let debug = format!("{keys:?}");
+        assert!(debug.contains("send_nonce: 0"));
+        assert!(!debug.contains(&format!("{:?}", keys.send)));
+        keys.zeroize();
+        assert_eq!(keys.send, [0u8; 32]);
+        assert_eq!(keys.session_key, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_pair() {
+        let (mut initiator, mut responder) = Transport::test_pair();
+        assert_eq!(initiator.role(), Role::Initiator);
+        assert_eq!(responder.role(), Role::Responder);
+        assert_ne!(initiator.remote_pubkey(), responder.remote_pubkey());
+        assert_eq!(initiator.derive_key(b"app"), responder.derive_key(b"app"));
+
+        let frame = initiator.send(b"ping").unwrap();
+        assert_eq!(responder.recv(&frame).unwrap().unwrap(), b"ping");
+        let frame = responder.send(b"pong").unwrap();
+        assert_eq!(initiator.recv(&frame).unwrap().unwrap(), b"pong");
+
+        // Each pair uses fresh keys
+        let (mut other, _) = Transport::test_pair();
+        let frame = other.send(b"ping").unwrap();
+        assert!(responder.recv(&frame).is_err());
+    }
+
+    #[test]
+    fn incomplete_frame() {
+        let (mut initiator, mut responder) = pair();
+        let frame = initiator.send(b"ping").unwrap();
+        assert_eq!(responder.recv(&frame[..20]), Err(EncryptionError::IncompleteFrame(20)));
+        assert_eq!(responder.recv(&frame[20..]).unwrap().unwrap(), b"ping");
+    }
+}
@@ -22,7 +22,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{cmp, ops};
+use core::{cmp, ops};
 
 mod _curve25519 {
     pub const PUBKEY_LEN: usize = 32;
@@ -36,8 +36,15 @@ pub use _curve25519::*;
 
 use crate::noise::Handshake;
 
+/// Length of handshake act one without payload: version byte, initiator
+/// ephemeral public key and authentication tag (50 bytes).
 pub const ACT_ONE_LENGTH: usize = 17 + PUBKEY_LEN;
+/// Length of handshake act two without payload: version byte, responder
+/// ephemeral public key and authentication tag (50 bytes).
 pub const ACT_TWO_LENGTH: usize = 17 + PUBKEY_LEN;
+/// Length of handshake act three without payload: version byte, encrypted
+/// initiator static public key with its authentication tag and the final
+/// authentication tag (66 bytes).
 pub const ACT_THREE_LENGTH: usize = 33 + PUBKEY_LEN;
 pub const EMPTY_ACT_ONE: ActOne = [0; ACT_ONE_LENGTH];
 pub const EMPTY_ACT_TWO: ActTwo = [0; ACT_TWO_LENGTH];
@@ -134,6 +141,9 @@ impl ActBuilder {
 
     /// Returns true if the Act is finished building (enough bytes via fill())
     pub fn is_finished(&self) -> bool { self.write_pos == self.partial_act.output_len() }
+
+    /// Returns number of bytes still required to finish the Act
+    pub fn remaining(&self) -> usize { self.partial_act.output_len() - self.write_pos }
 }
 
 #[cfg(test)]
@@ -21,7 +21,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
 use ed25519::x25519::{PublicKey, SecretKey};
+use rand_core::{CryptoRng, RngCore};
 use sha2::{Digest, Sha256};
 
 use super::ceremony::{
@@ -31,7 +35,9 @@ use super::ceremony::{
 use crate::noise::framing::{IncompleteHandshake, NoiseDecryptor, NoiseEncryptor, NoiseState};
 use crate::noise::hkdf::sha2_256 as hkdf;
 use crate::noise::xk::ceremony::PUBKEY_LEN;
-use crate::noise::{chacha, HandshakeError, SymmetricKey};
+use crate::noise::{
+    chacha, dh, generate_keypair_with, CipherSuite, HandshakeError, Role, SymmetricKey, Transport,
+};
 
 // Alias type to help differentiate between temporary key and chaining key when
 // passing bytes around
@@ -48,15 +54,70 @@ macro_rules! sha256 {
 	}}
 }
 
+/// Bounded cache of the ephemeral public keys used by initiators in recent
+/// handshakes, allowing a responder to reject handshakes reusing an ephemeral
+/// key (see [`NoiseXkState::advance_handshake_checked`]). When the cache is
+/// full, the least recently seen key is evicted.
+#[derive(Clone, Debug)]
+pub struct EphemeralCache {
+    capacity: usize,
+    keys: VecDeque<PublicKey>,
+}
+
+impl EphemeralCache {
+    /// Constructs cache remembering up to `capacity` ephemeral keys.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "ephemeral key cache must have non-zero capacity");
+        EphemeralCache {
+            capacity,
+            keys: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns maximal number of keys remembered by the cache.
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// Returns number of keys remembered by the cache.
+    pub fn len(&self) -> usize { self.keys.len() }
+
+    /// Detects whether the cache doesn't contain any keys.
+    pub fn is_empty(&self) -> bool { self.keys.is_empty() }
+
+    /// Detects whether the key was seen recently.
+    pub fn contains(&self, key: &PublicKey) -> bool { self.keys.contains(key) }
+
+    /// Remembers the key, returning `false` if it was already seen recently;
+    /// in the latter case the key becomes the most recently seen one.
+    pub fn insert(&mut self, key: PublicKey) -> bool {
+        if let Some(pos) = self.keys.iter().position(|k| *k == key) {
+            self.keys.remove(pos);
+            self.keys.push_back(key);
+            return false;
+        }
+        if self.keys.len() == self.capacity {
+            self.keys.pop_front();
+        }
+        self.keys.push_back(key);
+        true
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum NoiseXkState {
     InitiatorStarting(InitiatorStartingState),
     ResponderAwaitingActOne(ResponderAwaitingActOneState),
+    ResponderSendingActTwo(ResponderSendingActTwoState),
     InitiatorAwaitingActTwo(InitiatorAwaitingActTwoState),
+    InitiatorSendingActThree(InitiatorSendingActThreeState),
     ResponderAwaitingActThree(ResponderAwaitingActThreeState),
     Complete {
         encryptor: NoiseEncryptor,
         decryptor: NoiseDecryptor,
+        cipher_suite: CipherSuite,
     },
 }
 
@@ -71,6 +132,7 @@ impl NoiseState for NoiseXkState {
         NoiseXkState::Complete {
             encryptor,
             decryptor,
+            cipher_suite: CipherSuite::ChaCha20Poly1305,
         }
     }
 
@@ -78,11 +140,14 @@ impl NoiseState for NoiseXkState {
         match self {
             NoiseXkState::InitiatorStarting(_)
             | NoiseXkState::ResponderAwaitingActOne(_)
+            | NoiseXkState::ResponderSendingActTwo(_)
             | NoiseXkState::InitiatorAwaitingActTwo(_)
+            | NoiseXkState::InitiatorSendingActThree(_)
             | NoiseXkState::ResponderAwaitingActThree(_) => Err(IncompleteHandshake),
             NoiseXkState::Complete {
                 encryptor,
                 decryptor,
+                ..
             } => Ok((encryptor, decryptor)),
         }
     }
@@ -92,11 +157,14 @@ impl NoiseState for NoiseXkState {
         match self {
             NoiseXkState::InitiatorStarting(_)
             | NoiseXkState::ResponderAwaitingActOne(_)
+            | NoiseXkState::ResponderSendingActTwo(_)
             | NoiseXkState::InitiatorAwaitingActTwo(_)
+            | NoiseXkState::InitiatorSendingActThree(_)
             | NoiseXkState::ResponderAwaitingActThree(_) => Err(IncompleteHandshake),
             NoiseXkState::Complete {
                 encryptor,
                 decryptor,
+                ..
             } => Ok((encryptor, decryptor)),
         }
     }
@@ -107,11 +175,14 @@ impl NoiseState for NoiseXkState {
         match self {
             NoiseXkState::InitiatorStarting(_)
             | NoiseXkState::ResponderAwaitingActOne(_)
+            | NoiseXkState::ResponderSendingActTwo(_)
             | NoiseXkState::InitiatorAwaitingActTwo(_)
+            | NoiseXkState::InitiatorSendingActThree(_)
             | NoiseXkState::ResponderAwaitingActThree(_) => Err((self, IncompleteHandshake)),
             NoiseXkState::Complete {
                 encryptor,
                 decryptor,
+                ..
             } => Ok((encryptor, decryptor)),
         }
     }
@@ -121,7 +192,9 @@ impl NoiseState for NoiseXkState {
         let (act, clone) = match self.clone() {
             NoiseXkState::InitiatorStarting(state) => state.next(),
             NoiseXkState::ResponderAwaitingActOne(state) => state.next(input),
+            NoiseXkState::ResponderSendingActTwo(state) => state.next(),
             NoiseXkState::InitiatorAwaitingActTwo(state) => state.next(input),
+            NoiseXkState::InitiatorSendingActThree(state) => state.next(),
             NoiseXkState::ResponderAwaitingActThree(state) => state.next(input),
             NoiseXkState::Complete { .. } => Err(HandshakeError::Complete),
         }?;
@@ -133,13 +206,29 @@ impl NoiseState for NoiseXkState {
         match self {
             NoiseXkState::InitiatorStarting(_) => ACT_ONE_LENGTH,
             NoiseXkState::ResponderAwaitingActOne(_) => ACT_ONE_LENGTH,
+            NoiseXkState::ResponderSendingActTwo(_) => ACT_TWO_LENGTH,
             NoiseXkState::InitiatorAwaitingActTwo(_) => ACT_TWO_LENGTH,
+            NoiseXkState::InitiatorSendingActThree(_) => ACT_THREE_LENGTH,
             NoiseXkState::ResponderAwaitingActThree(_) => ACT_THREE_LENGTH,
             NoiseXkState::Complete { .. } => ACT_THREE_LENGTH,
         }
     }
 
     fn is_handshake_complete(&self) -> bool { matches!(self, NoiseXkState::Complete { .. }) }
+
+    fn next_read_len(&self) -> Option<usize> {
+        match self {
+            NoiseXkState::InitiatorStarting(_)
+            | NoiseXkState::ResponderSendingActTwo(_)
+            | NoiseXkState::InitiatorSendingActThree(_) => Some(0),
+            NoiseXkState::ResponderAwaitingActOne(state) => Some(state.act_one_builder.remaining()),
+            NoiseXkState::InitiatorAwaitingActTwo(state) => Some(state.act_two_builder.remaining()),
+            NoiseXkState::ResponderAwaitingActThree(state) => {
+                Some(state.act_three_builder.remaining())
+            }
+            NoiseXkState::Complete { .. } => None,
+        }
+    }
 }
 
 // Enum dispatch for state machine. Single public interface can statically
@@ -166,6 +255,340 @@ impl NoiseXkState {
             responder_ephemeral_private_key,
         ))
     }
+
+    /// Constructs initiator generating its ephemeral key with the provided
+    /// random number generator. Allows known-answer testing of the handshake
+    /// with a seeded generator.
+    pub fn new_initiator_with_rng(
+        initiator_static_private_key: SecretKey,
+        responder_static_public_key: PublicKey,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let (ephemeral_key, _) = generate_keypair_with(rng);
+        Self::new_initiator(
+            initiator_static_private_key,
+            responder_static_public_key,
+            ephemeral_key,
+        )
+    }
+
+    /// Constructs responder generating its ephemeral key with the provided
+    /// random number generator.
+    pub fn new_responder_with_rng(
+        responder_static_private_key: SecretKey,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let (ephemeral_key, _) = generate_keypair_with(rng);
+        Self::new_responder(responder_static_private_key, ephemeral_key)
+    }
+
+    /// Advances the handshake like [`NoiseState::advance_handshake`], but on
+    /// the responder side additionally rejects act one using an initiator
+    /// ephemeral key remembered by the `cache`, and remembers the key
+    /// otherwise. The key is checked only after act one is authenticated, so
+    /// invalid acts don't pollute the cache.
+    ///
+    /// # Errors
+    ///
+    /// [`HandshakeError::EphemeralReuse`] if the ephemeral key was seen in a
+    /// recent handshake; the state is not changed in this case.
+    pub fn advance_handshake_checked(
+        &mut self,
+        input: &[u8],
+        cache: &mut EphemeralCache,
+    ) -> Result<Option<Act>, HandshakeError> {
+        let NoiseXkState::ResponderAwaitingActOne(state) = self else {
+            return self.advance_handshake(input);
+        };
+        let (act, state) = state.clone().next_checked(input, Some(cache))?;
+        *self = state;
+        Ok(act)
+    }
+
+    /// Writes the next handshake act carrying an application `payload`
+    /// (Noise-style message processing). The payload is encrypted with the
+    /// temporary key of the act; act one payload is not forward-secret and the
+    /// initiator identity is not yet authenticated in act one and two
+    /// payloads.
+    ///
+    /// The length of the produced act is [`super::ACT_ONE_LENGTH`],
+    /// [`super::ACT_TWO_LENGTH`] or [`super::ACT_THREE_LENGTH`] plus the length
+    /// of the payload. Since the acts with payload are not self-delimiting, they
+    /// must be sent over a framed transport, and the received messages must be
+    /// processed with [`NoiseXkState::read_message`].
+    ///
+    /// Initiator writes acts one and three, responder writes act two.
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let (message, state) = match self.clone() {
+            NoiseXkState::InitiatorStarting(state) => state
+                .write_act_one(payload)
+                .map(|(act, state)| (act, NoiseXkState::InitiatorAwaitingActTwo(state))),
+            NoiseXkState::ResponderSendingActTwo(state) => state
+                .write_act_two(payload)
+                .map(|(act, state)| (act, NoiseXkState::ResponderAwaitingActThree(state))),
+            NoiseXkState::InitiatorSendingActThree(state) => {
+                let cipher_suite = state.cipher_suite;
+                state.write_act_three(payload).map(|(act, encryptor, decryptor)| {
+                    (act, NoiseXkState::Complete {
+                        encryptor,
+                        decryptor,
+                        cipher_suite,
+                    })
+                })
+            }
+            NoiseXkState::ResponderAwaitingActOne(_) => Err(HandshakeError::AwaitingRemote(1)),
+            NoiseXkState::InitiatorAwaitingActTwo(_) => Err(HandshakeError::AwaitingRemote(2)),
+            NoiseXkState::ResponderAwaitingActThree(_) => Err(HandshakeError::AwaitingRemote(3)),
+            NoiseXkState::Complete { .. } => Err(HandshakeError::Complete),
+        }?;
+        *self = state;
+        Ok(message)
+    }
+
+    /// Reads complete handshake act written by the remote peer with
+    /// [`NoiseXkState::write_message`], returning the decrypted application
+    /// payload carried by the act.
+    ///
+    /// The `data` must contain exactly one act, which length is
+    /// [`super::ACT_ONE_LENGTH`], [`super::ACT_TWO_LENGTH`] or
+    /// [`super::ACT_THREE_LENGTH`] plus the length of the payload. The method
+    /// must not be mixed with partial act data provided via
+    /// [`NoiseState::advance_handshake`].
+    ///
+    /// Responder reads acts one and three, initiator reads act two.
+    pub fn read_message(&mut self, data: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let (payload, state) = match self.clone() {
+            NoiseXkState::ResponderAwaitingActOne(state) => state
+                .read_act_one(data)
+                .map(|(payload, state)| (payload, NoiseXkState::ResponderSendingActTwo(state))),
+            NoiseXkState::InitiatorAwaitingActTwo(state) => state
+                .read_act_two(data)
+                .map(|(payload, state)| (payload, NoiseXkState::InitiatorSendingActThree(state))),
+            NoiseXkState::ResponderAwaitingActThree(state) => {
+                let cipher_suite = state.cipher_suite;
+                state.read_act_three(data).map(|(payload, encryptor, decryptor)| {
+                    (payload, NoiseXkState::Complete {
+                        encryptor,
+                        decryptor,
+                        cipher_suite,
+                    })
+                })
+            }
+            NoiseXkState::InitiatorStarting(_) => Err(HandshakeError::AwaitingLocal(1)),
+            NoiseXkState::ResponderSendingActTwo(_) => Err(HandshakeError::AwaitingLocal(2)),
+            NoiseXkState::InitiatorSendingActThree(_) => Err(HandshakeError::AwaitingLocal(3)),
+            NoiseXkState::Complete { .. } => Err(HandshakeError::Complete),
+        }?;
+        *self = state;
+        Ok(payload)
+    }
+
+    /// Writes act one proposing the cipher `suites` (in the order of
+    /// preference) as its payload; see [`CipherSuite::propose`]. The proposal
+    /// is remembered to check the suite confirmed by the responder in
+    /// [`NoiseXkState::read_confirmation`].
+    pub fn write_proposal(&mut self, suites: &[CipherSuite]) -> Result<Vec<u8>, HandshakeError> {
+        if !matches!(self, NoiseXkState::InitiatorStarting(_)) {
+            return Err(self.unexpected_act());
+        }
+        let act = self.write_message(&CipherSuite::propose(suites))?;
+        if let NoiseXkState::InitiatorAwaitingActTwo(state) = self {
+            state.proposed_suites = suites.to_vec();
+        }
+        Ok(act)
+    }
+
+    /// Reads act one carrying cipher suite proposal of the initiator, selecting
+    /// the first proposed suite which is `supported` by the responder (see
+    /// [`CipherSuite::select`]). The selected suite is confirmed by
+    /// [`NoiseXkState::write_confirmation`] and is used by the transport once
+    /// the handshake is complete.
+    ///
+    /// # Errors
+    ///
+    /// [`HandshakeError::NoCommonCipherSuite`] if none of the proposed suites
+    /// is supported, in which case the handshake must be aborted. The state is
+    /// not changed on errors.
+    pub fn read_proposal(
+        &mut self,
+        data: &[u8],
+        supported: &[CipherSuite],
+    ) -> Result<CipherSuite, HandshakeError> {
+        if !matches!(self, NoiseXkState::ResponderAwaitingActOne(_)) {
+            return Err(self.unexpected_act());
+        }
+        let mut state = self.clone();
+        let proposal = state.read_message(data)?;
+        let suite = CipherSuite::select(&proposal, supported)?;
+        if let NoiseXkState::ResponderSendingActTwo(ref mut state) = state {
+            state.cipher_suite = suite;
+        }
+        *self = state;
+        Ok(suite)
+    }
+
+    /// Writes act two confirming the cipher suite selected by
+    /// [`NoiseXkState::read_proposal`]; see [`CipherSuite::confirm`].
+    pub fn write_confirmation(&mut self) -> Result<Vec<u8>, HandshakeError> {
+        let NoiseXkState::ResponderSendingActTwo(state) = self else {
+            return Err(self.unexpected_act());
+        };
+        let confirmation = state.cipher_suite.confirm();
+        self.write_message(&confirmation)
+    }
+
+    /// Reads act two carrying the cipher suite confirmed by the responder,
+    /// checking it against the suites proposed by
+    /// [`NoiseXkState::write_proposal`] (see [`CipherSuite::verify`]). The
+    /// agreed suite is used by the transport once the handshake is complete.
+    ///
+    /// # Errors
+    ///
+    /// If the confirmed suite was not proposed or the confirmation is
+    /// malformed, in which case the handshake must be aborted. The state is
+    /// not changed on errors.
+    pub fn read_confirmation(&mut self, data: &[u8]) -> Result<CipherSuite, HandshakeError> {
+        let NoiseXkState::InitiatorAwaitingActTwo(awaiting) = self else {
+            return Err(self.unexpected_act());
+        };
+        let proposed = awaiting.proposed_suites.clone();
+        let mut state = self.clone();
+        let confirmation = state.read_message(data)?;
+        let suite = CipherSuite::verify(&confirmation, &proposed)?;
+        if let NoiseXkState::InitiatorSendingActThree(ref mut state) = state {
+            state.cipher_suite = suite;
+        }
+        *self = state;
+        Ok(suite)
+    }
+
+    /// Returns cipher suite agreed during the handshake, once it is complete.
+    /// Handshakes without cipher suite negotiation use
+    /// [`CipherSuite::ChaCha20Poly1305`].
+    pub fn cipher_suite(&self) -> Option<CipherSuite> {
+        match self {
+            NoiseXkState::Complete { cipher_suite, .. } => Some(*cipher_suite),
+            _ => None,
+        }
+    }
+
+    /// Converts complete handshake into the [`Transport`] of the local party,
+    /// using the cipher suite agreed during the handshake. Incomplete handshake
+    /// state is returned back, like in [`NoiseState::try_into_split`].
+    #[allow(clippy::result_large_err)]
+    pub fn into_transport(self, role: Role) -> Result<Transport, (Self, IncompleteHandshake)> {
+        let cipher_suite = self.cipher_suite();
+        let (encryptor, decryptor) = self.try_into_split()?;
+        Ok(Transport::with_split(role, encryptor, decryptor)
+            .with_cipher_suite(cipher_suite.expect("complete handshake")))
+    }
+
+    /// Error returned by the cipher suite negotiation methods called in a state
+    /// which is expecting a different act.
+    fn unexpected_act(&self) -> HandshakeError {
+        match self {
+            NoiseXkState::InitiatorStarting(_) => HandshakeError::AwaitingLocal(1),
+            NoiseXkState::ResponderAwaitingActOne(_) => HandshakeError::AwaitingRemote(1),
+            NoiseXkState::ResponderSendingActTwo(_) => HandshakeError::AwaitingLocal(2),
+            NoiseXkState::InitiatorAwaitingActTwo(_) => HandshakeError::AwaitingRemote(2),
+            NoiseXkState::InitiatorSendingActThree(_) => HandshakeError::AwaitingLocal(3),
+            NoiseXkState::ResponderAwaitingActThree(_) => HandshakeError::AwaitingRemote(3),
+            NoiseXkState::Complete { .. } => HandshakeError::Complete,
+        }
+    }
+
+    /// Writes the next handshake act with 2-byte big-endian length prefix, for
+    /// the transports which length-prefix all messages.
+    ///
+    /// Initiator writes the first act with this method; other acts sent by the
+    /// local peer are returned by [`NoiseXkState::read_framed_act`].
+    pub fn write_framed_act(&mut self) -> Result<Vec<u8>, HandshakeError> {
+        match self {
+            NoiseXkState::ResponderAwaitingActOne(_) => Err(HandshakeError::AwaitingRemote(1)),
+            NoiseXkState::InitiatorAwaitingActTwo(_) => Err(HandshakeError::AwaitingRemote(2)),
+            NoiseXkState::ResponderAwaitingActThree(_) => Err(HandshakeError::AwaitingRemote(3)),
+            _ => Ok(self.advance_handshake(&[])?.map(|act| frame_act(&act)).unwrap_or_default()),
+        }
+    }
+
+    /// Reads handshake act sent with 2-byte big-endian length prefix, returning
+    /// the next length-prefixed act which has to be sent in response, if any.
+    ///
+    /// The length prefix must be equal to the expected size of the act
+    /// ([`super::ACT_ONE_LENGTH`], [`super::ACT_TWO_LENGTH`] or
+    /// [`super::ACT_THREE_LENGTH`]) and the `frame` must contain exactly the
+    /// prefix and the act. Otherwise, the frame is rejected before any
+    /// cryptographic processing and the state is left unmodified. The method
+    /// must not be mixed with partial act data provided via
+    /// [`NoiseState::advance_handshake`].
+    pub fn read_framed_act(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, HandshakeError> {
+        let act = match self {
+            NoiseXkState::ResponderAwaitingActOne(_) => 1,
+            NoiseXkState::InitiatorAwaitingActTwo(_) => 2,
+            NoiseXkState::ResponderAwaitingActThree(_) => 3,
+            NoiseXkState::InitiatorStarting(_) => return Err(HandshakeError::AwaitingLocal(1)),
+            NoiseXkState::ResponderSendingActTwo(_) => {
+                return Err(HandshakeError::AwaitingLocal(2))
+            }
+            NoiseXkState::InitiatorSendingActThree(_) => {
+                return Err(HandshakeError::AwaitingLocal(3))
+            }
+            NoiseXkState::Complete { .. } => return Err(HandshakeError::Complete),
+        };
+        let expected = self.next_handshake_len();
+        if frame.len() < ACT_LEN_PREFIX_SIZE {
+            return Err(HandshakeError::InvalidActLen {
+                act,
+                expected,
+                found: frame.len(),
+            });
+        }
+        let (prefix, data) = frame.split_at(ACT_LEN_PREFIX_SIZE);
+        let declared = u16::from_be_bytes([prefix[0], prefix[1]]) as usize;
+        for found in [declared, data.len()] {
+            if found != expected {
+                return Err(HandshakeError::InvalidActLen {
+                    act,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(self.advance_handshake(data)?.map(|act| frame_act(&act)))
+    }
+
+    /// Reads handshake act sent with 2-byte big-endian length prefix from the
+    /// beginning of the received `buf`, which may contain data following the
+    /// act (like the first transport message pipelined by the remote peer
+    /// right after act three).
+    ///
+    /// Returns the next length-prefixed act which has to be sent in response,
+    /// if any, and the number of bytes consumed from `buf`. The remaining
+    /// bytes must be passed to the [`Transport`] once the handshake is
+    /// complete. Unlike [`NoiseXkState::read_message`], which can't tell the
+    /// payload of the act from the following data, the act length here is
+    /// known from the length prefix.
+    ///
+    /// [`Transport`]: crate::noise::Transport
+    pub fn read_framed_act_from(
+        &mut self,
+        buf: &[u8],
+    ) -> Result<(Option<Vec<u8>>, usize), HandshakeError> {
+        let consumed = ACT_LEN_PREFIX_SIZE + self.next_handshake_len();
+        let frame = buf.get(..consumed).unwrap_or(buf);
+        self.read_framed_act(frame).map(|response| (response, consumed))
+    }
+}
+
+/// Size of the length prefix used by [`NoiseXkState::write_framed_act`] and
+/// [`NoiseXkState::read_framed_act`].
+pub const ACT_LEN_PREFIX_SIZE: usize = 2;
+
+fn frame_act(act: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(ACT_LEN_PREFIX_SIZE + act.len());
+    frame.extend_from_slice(&(act.len() as u16).to_be_bytes());
+    frame.extend_from_slice(act);
+    frame
 }
 
 // Handshake state of the Initiator prior to generating Act 1
@@ -191,6 +614,18 @@ pub struct ResponderAwaitingActOneState {
     act_one_builder: ActBuilder,
 }
 
+// Handshake state of the Responder after receiving Act 1 prior to generating
+// Act 2
+#[derive(Clone, Debug)]
+pub struct ResponderSendingActTwoState {
+    responder_ephemeral_private_key: SecretKey,
+    responder_ephemeral_public_key: PublicKey,
+    initiator_ephemeral_public_key: PublicKey,
+    chaining_key: ChainingKey,
+    hash: [u8; 32],
+    cipher_suite: CipherSuite,
+}
+
 // Handshake state of the Initiator prior to receiving Act 2
 #[derive(Clone, Debug)]
 pub struct InitiatorAwaitingActTwoState {
@@ -201,6 +636,21 @@ pub struct InitiatorAwaitingActTwoState {
     chaining_key: ChainingKey,
     hash: [u8; 32],
     act_two_builder: ActBuilder,
+    proposed_suites: Vec<CipherSuite>,
+}
+
+// Handshake state of the Initiator after receiving Act 2 prior to generating
+// Act 3
+#[derive(Clone, Debug)]
+pub struct InitiatorSendingActThreeState {
+    initiator_static_private_key: SecretKey,
+    initiator_static_public_key: PublicKey,
+    responder_static_public_key: PublicKey,
+    responder_ephemeral_public_key: PublicKey,
+    chaining_key: ChainingKey,
+    hash: [u8; 32],
+    temporary_key: [u8; 32],
+    cipher_suite: CipherSuite,
 }
 
 // Handshake state of the Responder prior to receiving Act 3
@@ -211,6 +661,7 @@ pub struct ResponderAwaitingActThreeState {
     chaining_key: ChainingKey,
     temporary_key: [u8; 32],
     act_three_builder: ActBuilder,
+    cipher_suite: CipherSuite,
 }
 
 impl InitiatorStartingState {
@@ -246,6 +697,16 @@ impl InitiatorStartingState {
     // IHandshake trait in mother implementation which we are not using
     // anymore. So we can get rid of the the length check.
     pub fn next(self) -> Result<(Option<Act>, NoiseXkState), HandshakeError> {
+        let (act, state) = self.write_act_one(&[])?;
+        let mut act_one = EMPTY_ACT_ONE;
+        act_one.copy_from_slice(&act);
+        Ok((Some(Act::One(act_one)), NoiseXkState::InitiatorAwaitingActTwo(state)))
+    }
+
+    fn write_act_one(
+        self,
+        payload: &[u8],
+    ) -> Result<(Vec<u8>, InitiatorAwaitingActTwoState), HandshakeError> {
         let initiator_static_private_key = self.initiator_static_private_key;
         let initiator_static_public_key = self.initiator_static_public_key;
         let initiator_ephemeral_private_key = self.initiator_ephemeral_private_key;
@@ -255,28 +716,27 @@ impl InitiatorStartingState {
         let hash = self.hash;
 
         // serialize act one
-        let mut act_one = EMPTY_ACT_ONE;
+        let mut act_one = vec![0u8; ACT_ONE_LENGTH + payload.len()];
         let (hash, chaining_key, _) = calculate_act_message(
             &initiator_ephemeral_private_key,
             initiator_ephemeral_public_key,
             responder_static_public_key,
             chaining_key,
             hash,
+            payload,
             &mut act_one,
         )?;
 
-        Ok((
-            Some(Act::One(act_one)),
-            NoiseXkState::InitiatorAwaitingActTwo(InitiatorAwaitingActTwoState {
-                initiator_static_private_key,
-                initiator_static_public_key,
-                initiator_ephemeral_private_key,
-                responder_static_public_key,
-                chaining_key,
-                hash,
-                act_two_builder: ActBuilder::new(Act::Two(EMPTY_ACT_TWO)),
-            }),
-        ))
+        Ok((act_one, InitiatorAwaitingActTwoState {
+            initiator_static_private_key,
+            initiator_static_public_key,
+            initiator_ephemeral_private_key,
+            responder_static_public_key,
+            chaining_key,
+            hash,
+            act_two_builder: ActBuilder::new(Act::Two(EMPTY_ACT_TWO)),
+            proposed_suites: Vec::new(),
+        }))
     }
 }
 
@@ -304,6 +764,14 @@ impl ResponderAwaitingActOneState {
     }
 
     pub fn next(self, input: &[u8]) -> Result<(Option<Act>, NoiseXkState), HandshakeError> {
+        self.next_checked(input, None)
+    }
+
+    fn next_checked(
+        self,
+        input: &[u8],
+        cache: Option<&mut EphemeralCache>,
+    ) -> Result<(Option<Act>, NoiseXkState), HandshakeError> {
         let mut act_one_builder = self.act_one_builder;
         let bytes_read = act_one_builder.fill(input);
 
@@ -335,36 +803,85 @@ impl ResponderAwaitingActOneState {
             ));
         }
 
-        let hash = self.hash;
-        let responder_static_private_key = self.responder_static_private_key;
-        let chaining_key = self.chaining_key;
-        let responder_ephemeral_private_key = self.responder_ephemeral_private_key;
-        let responder_ephemeral_public_key = self.responder_ephemeral_public_key;
         let act_one = Act::from(act_one_builder);
+        let (_, state) = Self {
+            act_one_builder: ActBuilder::new(Act::One(EMPTY_ACT_ONE)),
+            ..self
+        }
+        .read_act_one(&act_one)?;
+        if let Some(cache) = cache {
+            if !cache.insert(state.initiator_ephemeral_public_key) {
+                return Err(HandshakeError::EphemeralReuse);
+            }
+        }
+        state.next()
+    }
 
-        let (initiator_ephemeral_public_key, hash, chaining_key, _) =
-            process_act_message::<1>(&act_one, &responder_static_private_key, chaining_key, hash)?;
+    fn read_act_one(
+        self,
+        act_one: &[u8],
+    ) -> Result<(Vec<u8>, ResponderSendingActTwoState), HandshakeError> {
+        if act_one.len() < ACT_ONE_LENGTH {
+            return Err(HandshakeError::InvalidActLen {
+                act: 1,
+                expected: ACT_ONE_LENGTH,
+                found: act_one.len(),
+            });
+        }
 
-        let mut act_two = EMPTY_ACT_TWO;
-        let (hash, chaining_key, temporary_key) = calculate_act_message(
-            &responder_ephemeral_private_key,
-            responder_ephemeral_public_key,
+        let (initiator_ephemeral_public_key, hash, chaining_key, _, payload) =
+            process_act_message::<1>(
+                act_one,
+                &self.responder_static_private_key,
+                self.chaining_key,
+                self.hash,
+            )?;
+
+        Ok((payload, ResponderSendingActTwoState {
+            responder_ephemeral_private_key: self.responder_ephemeral_private_key,
+            responder_ephemeral_public_key: self.responder_ephemeral_public_key,
             initiator_ephemeral_public_key,
             chaining_key,
             hash,
+            // Replaced with the negotiated suite by `NoiseXkState::read_proposal`
+            cipher_suite: CipherSuite::ChaCha20Poly1305,
+        }))
+    }
+}
+
+impl ResponderSendingActTwoState {
+    pub fn next(self) -> Result<(Option<Act>, NoiseXkState), HandshakeError> {
+        let (act, state) = self.write_act_two(&[])?;
+        let mut act_two = EMPTY_ACT_TWO;
+        act_two.copy_from_slice(&act);
+        Ok((Some(Act::Two(act_two)), NoiseXkState::ResponderAwaitingActThree(state)))
+    }
+
+    fn write_act_two(
+        self,
+        payload: &[u8],
+    ) -> Result<(Vec<u8>, ResponderAwaitingActThreeState), HandshakeError> {
+        let responder_ephemeral_private_key = self.responder_ephemeral_private_key;
+
+        let mut act_two = vec![0u8; ACT_TWO_LENGTH + payload.len()];
+        let (hash, chaining_key, temporary_key) = calculate_act_message(
+            &responder_ephemeral_private_key,
+            self.responder_ephemeral_public_key,
+            self.initiator_ephemeral_public_key,
+            self.chaining_key,
+            self.hash,
+            payload,
             &mut act_two,
         )?;
 
-        Ok((
-            Some(Act::Two(act_two)),
-            NoiseXkState::ResponderAwaitingActThree(ResponderAwaitingActThreeState {
-                hash,
-                responder_ephemeral_private_key,
-                chaining_key,
-                temporary_key,
-                act_three_builder: ActBuilder::new(Act::Three(EMPTY_ACT_THREE)),
-            }),
-        ))
+        Ok((act_two, ResponderAwaitingActThreeState {
+            hash,
+            responder_ephemeral_private_key,
+            chaining_key,
+            temporary_key,
+            act_three_builder: ActBuilder::new(Act::Three(EMPTY_ACT_THREE)),
+            cipher_suite: self.cipher_suite,
+        }))
     }
 }
 
@@ -398,27 +915,80 @@ impl InitiatorAwaitingActTwoState {
                     chaining_key: self.chaining_key,
                     hash: self.hash,
                     act_two_builder,
+                    proposed_suites: self.proposed_suites,
                 }),
             ));
         }
 
-        let initiator_static_private_key = self.initiator_static_private_key;
-        let initiator_static_public_key = self.initiator_static_public_key;
-        let initiator_ephemeral_private_key = &self.initiator_ephemeral_private_key;
-        let responder_static_public_key = self.responder_static_public_key;
-        let hash = self.hash;
-        let chaining_key = self.chaining_key;
         let act_two = Act::from(act_two_builder);
+        let (_, state) = Self {
+            act_two_builder: ActBuilder::new(Act::Two(EMPTY_ACT_TWO)),
+            ..self
+        }
+        .read_act_two(&act_two)?;
+        state.next()
+    }
+
+    fn read_act_two(
+        self,
+        act_two: &[u8],
+    ) -> Result<(Vec<u8>, InitiatorSendingActThreeState), HandshakeError> {
+        if act_two.len() < ACT_TWO_LENGTH {
+            return Err(HandshakeError::InvalidActLen {
+                act: 2,
+                expected: ACT_TWO_LENGTH,
+                found: act_two.len(),
+            });
+        }
 
-        let (responder_ephemeral_public_key, hash, chaining_key, temporary_key) =
+        let (responder_ephemeral_public_key, hash, chaining_key, temporary_key, payload) =
             process_act_message::<2>(
-                &act_two,
-                initiator_ephemeral_private_key,
-                chaining_key,
-                hash,
+                act_two,
+                &self.initiator_ephemeral_private_key,
+                self.chaining_key,
+                self.hash,
             )?;
 
+        Ok((payload, InitiatorSendingActThreeState {
+            initiator_static_private_key: self.initiator_static_private_key,
+            initiator_static_public_key: self.initiator_static_public_key,
+            responder_static_public_key: self.responder_static_public_key,
+            responder_ephemeral_public_key,
+            chaining_key,
+            hash,
+            temporary_key,
+            // Replaced with the negotiated suite by `NoiseXkState::read_confirmation`
+            cipher_suite: CipherSuite::ChaCha20Poly1305,
+        }))
+    }
+}
+
+impl InitiatorSendingActThreeState {
+    pub fn next(self) -> Result<(Option<Act>, NoiseXkState), HandshakeError> {
+        let cipher_suite = self.cipher_suite;
+        let (act, encryptor, decryptor) = self.write_act_three(&[])?;
         let mut act_three = EMPTY_ACT_THREE;
+        act_three.copy_from_slice(&act);
+        Ok((Some(Act::Three(act_three)), NoiseXkState::Complete {
+            encryptor,
+            decryptor,
+            cipher_suite,
+        }))
+    }
+
+    fn write_act_three(
+        self,
+        payload: &[u8],
+    ) -> Result<(Vec<u8>, NoiseEncryptor, NoiseDecryptor), HandshakeError> {
+        let initiator_static_private_key = self.initiator_static_private_key;
+        let initiator_static_public_key = self.initiator_static_public_key;
+        let responder_static_public_key = self.responder_static_public_key;
+        let responder_ephemeral_public_key = self.responder_ephemeral_public_key;
+        let hash = self.hash;
+        let chaining_key = self.chaining_key;
+        let temporary_key = self.temporary_key;
+
+        let mut act_three = vec![0u8; ACT_THREE_LENGTH + payload.len()];
 
         // start serializing act three
         // 1. c = encryptWithAD(temp_k2, 1, h, s.pub.serializeCompressed())
@@ -434,17 +1004,17 @@ impl InitiatorAwaitingActTwoState {
         let hash = sha256!(hash, &act_three[1..(17 + PUBKEY_LEN)]);
 
         // 3. se = ECDH(s.priv, re)
-        let ecdh = ecdh(&initiator_static_private_key, responder_ephemeral_public_key);
+        let ecdh = ecdh(&initiator_static_private_key, responder_ephemeral_public_key)?;
 
         // 4. ck, temp_k3 = HKDF(ck, se)
         let (chaining_key, temporary_key) = hkdf::derive(&chaining_key, &ecdh);
 
-        // 5. t = encryptWithAD(temp_k3, 0, h, zero)
+        // 5. t = encryptWithAD(temp_k3, 0, h, payload)
         chacha::encrypt(
             &temporary_key,
             0,
             &hash,
-            &[0; 0],
+            payload,
             Some(&mut act_three[(17 + PUBKEY_LEN)..]),
         )?;
 
@@ -453,28 +1023,18 @@ impl InitiatorAwaitingActTwoState {
 
         // 7. rn = 0, sn = 0
         // - done by Conduit
-        let encryptor = NoiseEncryptor {
+        let (encryptor, decryptor) = Transport::with_keys(
+            Role::Initiator,
+            chaining_key,
             sending_key,
-            sending_chaining_key: chaining_key,
-            sending_nonce: 0,
-            remote_pubkey: responder_static_public_key,
-        };
-        let decryptor = NoiseDecryptor {
             receiving_key,
-            receiving_chaining_key: chaining_key,
-            receiving_nonce: 0,
-            read_buffer: None,
-            pending_message_length: None,
-            poisoned: false,
-            remote_pubkey: responder_static_public_key,
-        };
+            responder_static_public_key,
+        )
+        .into_split();
 
         // 8. Send m = 0 || c || t
         act_three[0] = 0;
-        Ok((Some(Act::Three(act_three)), NoiseXkState::Complete {
-            encryptor,
-            decryptor,
-        }))
+        Ok((act_three, encryptor, decryptor))
     }
 }
 
@@ -495,19 +1055,49 @@ impl ResponderAwaitingActThreeState {
                     chaining_key: self.chaining_key,
                     temporary_key: self.temporary_key,
                     act_three_builder,
+                    cipher_suite: self.cipher_suite,
                 }),
             ));
         }
 
+        // 1. Read exactly 66 bytes from the network buffer
+        let act_three_bytes = Act::from(act_three_builder);
+        assert_eq!(act_three_bytes.len(), ACT_THREE_LENGTH);
+
+        let cipher_suite = self.cipher_suite;
+        let (_, encryptor, mut decryptor) = Self {
+            act_three_builder: ActBuilder::new(Act::Three(EMPTY_ACT_THREE)),
+            ..self
+        }
+        .read_act_three(&act_three_bytes)?;
+
+        // Bytes following the act belong to the transport messages
+        decryptor.read_buffer = Some(input[bytes_read..].to_vec());
+
+        Ok((None, NoiseXkState::Complete {
+            encryptor,
+            decryptor,
+            cipher_suite,
+        }))
+    }
+
+    fn read_act_three(
+        self,
+        act_three_bytes: &[u8],
+    ) -> Result<(Vec<u8>, NoiseEncryptor, NoiseDecryptor), HandshakeError> {
+        if act_three_bytes.len() < ACT_THREE_LENGTH {
+            return Err(HandshakeError::InvalidActLen {
+                act: 3,
+                expected: ACT_THREE_LENGTH,
+                found: act_three_bytes.len(),
+            });
+        }
+
         let hash = self.hash;
         let temporary_key = self.temporary_key;
         let responder_ephemeral_private_key = &self.responder_ephemeral_private_key;
         let chaining_key = self.chaining_key;
 
-        // 1. Read exactly 66 bytes from the network buffer
-        let act_three_bytes = Act::from(act_three_builder);
-        assert_eq!(act_three_bytes.len(), ACT_THREE_LENGTH);
-
         // 2. Parse the read message (m) into v, c, and t
         let version = act_three_bytes[0];
         let tagged_encrypted_pubkey = &act_three_bytes[1..(17 + PUBKEY_LEN)];
@@ -528,47 +1118,38 @@ impl ResponderAwaitingActThreeState {
             &hash,
             tagged_encrypted_pubkey,
             Some(&mut remote_pubkey),
-        )?;
+        )
+        .map_err(|_| HandshakeError::InvalidActTag { act: 3 })?;
         let initiator_pubkey = PublicKey::new(remote_pubkey);
 
         // 5. h = SHA-256(h || c)
         let hash = sha256!(hash, tagged_encrypted_pubkey);
 
         // 6. se = ECDH(e.priv, rs)
-        let ecdh = ecdh(responder_ephemeral_private_key, initiator_pubkey);
+        let ecdh = ecdh(responder_ephemeral_private_key, initiator_pubkey)?;
 
         // 7. ck, temp_k3 = HKDF(ck, se)
         let (chaining_key, temporary_key) = hkdf::derive(&chaining_key, &ecdh);
 
         // 8. p = decryptWithAD(temp_k3, 0, h, t)
-        chacha::decrypt(&temporary_key, 0, &hash, chacha_tag, Some(&mut [0; 0]))?;
+        let payload = chacha::decrypt(&temporary_key, 0, &hash, chacha_tag, None)
+            .map_err(|_| HandshakeError::InvalidActTag { act: 3 })?;
 
         // 9. rk, sk = HKDF(ck, zero)
         let (receiving_key, sending_key) = hkdf::derive(&chaining_key, &[0; 0]);
 
         // 10. rn = 0, sn = 0
         // - done by Conduit
-        let read_buffer = input[bytes_read..].to_vec();
-        let encryptor = NoiseEncryptor {
-            sending_key,
-            sending_chaining_key: chaining_key,
-            sending_nonce: 0,
-            remote_pubkey: initiator_pubkey,
-        };
-        let decryptor = NoiseDecryptor {
+        let (encryptor, decryptor) = Transport::with_keys(
+            Role::Responder,
+            chaining_key,
             receiving_key,
-            receiving_chaining_key: chaining_key,
-            receiving_nonce: 0,
-            read_buffer: Some(read_buffer),
-            pending_message_length: None,
-            poisoned: false,
-            remote_pubkey: initiator_pubkey,
-        };
+            sending_key,
+            initiator_pubkey,
+        )
+        .into_split();
 
-        Ok((None, NoiseXkState::Complete {
-            encryptor,
-            decryptor,
-        }))
+        Ok((payload, encryptor, decryptor))
     }
 }
 
@@ -600,6 +1181,7 @@ fn calculate_act_message(
     remote_public_key: PublicKey,
     chaining_key: ChainingKey,
     hash: [u8; 32],
+    payload: &[u8],
     act_out: &mut [u8],
 ) -> Result<([u8; 32], SymmetricKey, SymmetricKey), HandshakeError> {
     // 1. e = generateKey() (passed in)
@@ -609,15 +1191,15 @@ fn calculate_act_message(
 
     // 3. ACT1: es = ECDH(e.priv, rs)
     // 3. ACT2: es = ECDH(e.priv, re)
-    let ecdh = ecdh(local_private_ephemeral_key, remote_public_key);
+    let ecdh = ecdh(local_private_ephemeral_key, remote_public_key)?;
 
     // 4. ACT1: ck, temp_k1 = HKDF(ck, es)
     // 4. ACT2: ck, temp_k2 = HKDF(ck, ee)
     let (chaining_key, temporary_key) = hkdf::derive(&chaining_key, &ecdh);
 
-    // 5. ACT1: c = encryptWithAD(temp_k1, 0, h, zero)
-    // 5. ACT2: c = encryptWithAD(temp_k2, 0, h, zero)
-    chacha::encrypt(&temporary_key, 0, &hash, &[0; 0], Some(&mut act_out[(PUBKEY_LEN + 1)..]))?;
+    // 5. ACT1: c = encryptWithAD(temp_k1, 0, h, payload)
+    // 5. ACT2: c = encryptWithAD(temp_k2, 0, h, payload)
+    chacha::encrypt(&temporary_key, 0, &hash, payload, Some(&mut act_out[(PUBKEY_LEN + 1)..]))?;
 
     // 6. h = SHA-256(h || c)
     let hash = sha256!(hash, &act_out[(PUBKEY_LEN + 1)..]);
@@ -629,6 +1211,10 @@ fn calculate_act_message(
     Ok((hash, chaining_key, temporary_key))
 }
 
+// Remote ephemeral public key, hash, chaining key, temporary key and decrypted
+// payload of a processed act
+type ProcessedAct = (PublicKey, [u8; 32], SymmetricKey, SymmetricKey, Vec<u8>);
+
 // Due to the very high similarity of acts 1 and 2, this method is used to
 // process both
 fn process_act_message<const ACT: u8>(
@@ -636,12 +1222,12 @@ fn process_act_message<const ACT: u8>(
     local_private_key: &SecretKey,
     chaining_key: ChainingKey,
     hash: [u8; 32],
-) -> Result<(PublicKey, [u8; 32], SymmetricKey, SymmetricKey), HandshakeError> {
-    // 1. Read exactly 50 bytes from the network buffer
+) -> Result<ProcessedAct, HandshakeError> {
+    // 1. Read exactly 50 bytes (plus the payload) from the network buffer
     // Partial act messages are handled by the callers. By the time it gets
     // here, it must be the correct size.
-    assert_eq!(act_bytes.len(), ACT_ONE_LENGTH);
-    assert_eq!(act_bytes.len(), ACT_TWO_LENGTH);
+    assert!(act_bytes.len() >= ACT_ONE_LENGTH);
+    assert!(act_bytes.len() >= ACT_TWO_LENGTH);
 
     // 2.Parse the read message (m) into v, re, and c
     let version = act_bytes[0];
@@ -667,7 +1253,7 @@ fn process_act_message<const ACT: u8>(
 
     // 5. Act1: es = ECDH(s.priv, re)
     // 5. Act2: ee = ECDH(e.priv, ee)
-    let ecdh = ecdh(local_private_key, ephemeral_public_key);
+    let ecdh = ecdh(local_private_key, ephemeral_public_key)?;
 
     // 6. Act1: ck, temp_k1 = HKDF(ck, es)
     // 6. Act2: ck, temp_k2 = HKDF(ck, ee)
@@ -675,19 +1261,18 @@ fn process_act_message<const ACT: u8>(
 
     // 7. Act1: p = decryptWithAD(temp_k1, 0, h, c)
     // 7. Act2: p = decryptWithAD(temp_k2, 0, h, c)
-    chacha::decrypt(&temporary_key, 0, &hash, chacha_tag, Some(&mut [0; 0]))?;
+    let payload = chacha::decrypt(&temporary_key, 0, &hash, chacha_tag, None)
+        .map_err(|_| HandshakeError::InvalidActTag { act: ACT })?;
 
     // 8. h = SHA-256(h || c)
     let hash = sha256!(hash, chacha_tag);
 
-    Ok((ephemeral_public_key, hash, chaining_key, temporary_key))
+    Ok((ephemeral_public_key, hash, chaining_key, temporary_key, payload))
 }
 
-// TODO: Replace with ECDH from crypto
-fn ecdh(private_key: &SecretKey, public_key: PublicKey) -> SymmetricKey {
-    let pk_object = public_key.dh(private_key).expect("invalid multiplication");
-
-    sha256!(pk_object.as_slice())
+fn ecdh(private_key: &SecretKey, public_key: PublicKey) -> Result<SymmetricKey, HandshakeError> {
+    let shared_secret = dh(private_key, &public_key)?;
+    Ok(sha256!(shared_secret))
 }
 
 #[cfg(test)]
@@ -698,7 +1283,7 @@ mod test {
 
     use super::NoiseXkState::*;
     use super::*;
-    use crate::noise::EncryptionError;
+    use crate::noise::{CipherSuite, Role, Transport};
 
     struct TestCtx {
         initiator: NoiseXkState,
@@ -870,7 +1455,7 @@ mod test {
 
         assert_eq!(
             test_ctx.responder.advance_handshake(&act1).unwrap_err(),
-            HandshakeError::Encryption(chacha20poly1305::aead::Error.into())
+            HandshakeError::InvalidActTag { act: 1 }
         );
     }
 
@@ -905,11 +1490,14 @@ mod test {
         let Complete {
             encryptor,
             decryptor,
-        } = test_ctx.initiator else {
+            cipher_suite,
+        } = test_ctx.initiator
+        else {
             panic!();
         };
 
         assert_eq!(act3.as_ref(), test_ctx.valid_act3.as_slice());
+        assert_eq!(cipher_suite, CipherSuite::ChaCha20Poly1305);
         assert_eq!(encryptor.remote_pubkey, test_ctx.responder_static_public_key);
         assert_eq!(decryptor.remote_pubkey, test_ctx.responder_static_public_key);
     }
@@ -971,7 +1559,7 @@ mod test {
 
         assert_matches!(
             test_ctx.initiator.advance_handshake(&act2),
-            Err(HandshakeError::Encryption(EncryptionError::ChaCha(chacha20poly1305::aead::Error)))
+            Err(HandshakeError::InvalidActTag { act: 2 })
         );
     }
 
@@ -1051,7 +1639,7 @@ mod test {
 
         assert_eq!(
             test_ctx.responder.advance_handshake(&act3).err().unwrap(),
-            HandshakeError::Encryption(chacha20poly1305::aead::Error.into())
+            HandshakeError::InvalidActTag { act: 3 }
         );
     }
 
@@ -1080,7 +1668,7 @@ mod test {
 
         assert_eq!(
             test_ctx.responder.advance_handshake(&act3).err().unwrap(),
-            HandshakeError::Encryption(chacha20poly1305::aead::Error.into())
+            HandshakeError::InvalidActTag { act: 3 }
         );
     }
 
@@ -1129,4 +1717,401 @@ mod test {
         assert_eq!(act3.as_ref().to_hex(),
 				   "00b9e3a702e93e3a9948c2ed6e5fd7590a6e1c3a0344cfc9d5b57357049aa22355361aa02e55a8fc28fef5bd6d71ad0c38228dc68b1c466263b47fdf31e560e139ba");
     }
+
+    /// Generator producing the predefined bytes.
+    struct FixedRng(Vec<u8>);
+
+    impl RngCore for FixedRng {
+        fn next_u32(&mut self) -> u32 { rand_core::impls::next_u32_via_fill(self) }
+
+        fn next_u64(&mut self) -> u64 { rand_core::impls::next_u64_via_fill(self) }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            assert!(dest.len() <= self.0.len(), "fixed generator is exhausted");
+            dest.copy_from_slice(&self.0[..dest.len()]);
+            self.0.drain(..dest.len());
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for FixedRng {}
+
+    #[test]
+    fn seeded_handshake() {
+        let initiator_key = SecretKey::new([0x_11_u8; 32]);
+        let responder_key = SecretKey::new([0x_21_u8; 32]);
+        let responder_pk = responder_key.recover_public_key().unwrap();
+        let mut initiator = NoiseXkState::new_initiator_with_rng(
+            initiator_key,
+            responder_pk,
+            &mut FixedRng(vec![0x_12_u8; 32]),
+        );
+        let mut responder =
+            NoiseXkState::new_responder_with_rng(responder_key, &mut FixedRng(vec![0x_22_u8; 32]));
+
+        let test_ctx = TestCtx::new();
+        let act1 = unwrap!(initiator.advance_handshake(&[]));
+        assert_eq!(act1.as_ref(), test_ctx.valid_act1.as_slice());
+        let act2 = unwrap!(responder.advance_handshake(&act1));
+        assert_eq!(act2.as_ref(), test_ctx.valid_act2.as_slice());
+        let act3 = unwrap!(initiator.advance_handshake(&act2));
+        assert_matches!(responder.advance_handshake(&act3), Ok(None));
+
+        let (encryptor, decryptor) = initiator.try_into_split().unwrap();
+        assert_eq!(
+            encryptor.sending_key.to_hex(),
+            "30e5d12b324387a98b0472d205c9c209f2204d781d2eaa8767fad4a38455eb90"
+        );
+        assert_eq!(
+            decryptor.receiving_key.to_hex(),
+            "1c1c79fd8c53ce3a68cf398f57357bdcd6e955abef9418e0eb7ff0b7ef501427"
+        );
+        let (encryptor, decryptor) = responder.try_into_split().unwrap();
+        assert_eq!(
+            encryptor.sending_key.to_hex(),
+            "1c1c79fd8c53ce3a68cf398f57357bdcd6e955abef9418e0eb7ff0b7ef501427"
+        );
+        assert_eq!(
+            decryptor.receiving_key.to_hex(),
+            "30e5d12b324387a98b0472d205c9c209f2204d781d2eaa8767fad4a38455eb90"
+        );
+    }
+
+    #[test]
+    fn messages_with_payload() {
+        let mut test_ctx = TestCtx::new();
+
+        let act1 = test_ctx.initiator.write_message(b"").unwrap();
+        assert_eq!(act1.len(), ACT_ONE_LENGTH);
+        assert_eq!(
+            test_ctx.initiator.write_message(b"").unwrap_err(),
+            HandshakeError::AwaitingRemote(2)
+        );
+        assert!(test_ctx.responder.read_message(&act1).unwrap().is_empty());
+
+        let act2 = test_ctx.responder.write_message(b"hi").unwrap();
+        assert_eq!(act2.len(), ACT_TWO_LENGTH + 2);
+        assert_eq!(test_ctx.initiator.read_message(&act2).unwrap(), b"hi");
+
+        let act3 = test_ctx.initiator.write_message(b"early data").unwrap();
+        assert_eq!(act3.len(), ACT_THREE_LENGTH + 10);
+        assert_eq!(
+            test_ctx.responder.write_message(b"").unwrap_err(),
+            HandshakeError::AwaitingRemote(3)
+        );
+        assert_eq!(test_ctx.responder.read_message(&act3).unwrap(), b"early data");
+
+        assert!(test_ctx.initiator.is_handshake_complete());
+        assert!(test_ctx.responder.is_handshake_complete());
+        let (encryptor, _) = test_ctx.initiator.try_as_split().unwrap();
+        let (_, decryptor) = test_ctx.responder.try_as_split().unwrap();
+        assert_eq!(encryptor.remote_pubkey, test_ctx.responder_static_public_key);
+        assert_eq!(decryptor.remote_pubkey, test_ctx.initiator_public_key);
+    }
+
+    #[test]
+    fn cipher_suite_negotiation() {
+        let mut test_ctx = TestCtx::new();
+        let proposed = [CipherSuite::ChaCha20Poly1305];
+
+        let act1 = test_ctx.initiator.write_proposal(&proposed).unwrap();
+        let selected = test_ctx.responder.read_proposal(&act1, &CipherSuite::ALL).unwrap();
+        assert_eq!(selected, CipherSuite::ChaCha20Poly1305);
+        let act2 = test_ctx.responder.write_confirmation().unwrap();
+        let agreed = test_ctx.initiator.read_confirmation(&act2).unwrap();
+        assert_eq!(agreed, selected);
+        assert_eq!(test_ctx.initiator.cipher_suite(), None);
+
+        let act3 = test_ctx.initiator.write_message(b"").unwrap();
+        test_ctx.responder.read_message(&act3).unwrap();
+        assert_eq!(test_ctx.initiator.cipher_suite(), Some(agreed));
+        assert_eq!(test_ctx.responder.cipher_suite(), Some(agreed));
+        let mut initiator = test_ctx.initiator.into_transport(Role::Initiator).unwrap();
+        let mut responder = test_ctx.responder.into_transport(Role::Responder).unwrap();
+        assert_eq!(initiator.cipher_suite(), agreed);
+        assert_eq!(responder.cipher_suite(), agreed);
+        let frame = initiator.send(b"message").unwrap();
+        assert_eq!(responder.recv(&frame).unwrap().unwrap(), b"message");
+
+        // Unknown suites proposed by the initiator are skipped
+        let mut test_ctx = TestCtx::new();
+        let act1 = test_ctx.initiator.write_message(&[0x7F, 0x01]).unwrap();
+        assert_eq!(
+            test_ctx.responder.read_proposal(&act1, &CipherSuite::ALL),
+            Ok(CipherSuite::ChaCha20Poly1305)
+        );
+    }
+
+    #[test]
+    fn cipher_suite_no_overlap() {
+        let mut test_ctx = TestCtx::new();
+        let act1 = test_ctx.initiator.write_proposal(&CipherSuite::ALL).unwrap();
+        assert_eq!(
+            test_ctx.responder.read_proposal(&act1, &[]),
+            Err(HandshakeError::NoCommonCipherSuite)
+        );
+        assert_matches!(test_ctx.responder, ResponderAwaitingActOne(_));
+
+        let mut test_ctx = TestCtx::new();
+        let act1 = test_ctx.initiator.write_message(&[0x7F]).unwrap();
+        assert_eq!(
+            test_ctx.responder.read_proposal(&act1, &CipherSuite::ALL),
+            Err(HandshakeError::NoCommonCipherSuite)
+        );
+
+        // Responder confirming a suite which was not proposed
+        let mut test_ctx = TestCtx::new();
+        let act1 = test_ctx.initiator.write_proposal(&[]).unwrap();
+        test_ctx.responder.read_message(&act1).unwrap();
+        let act2 = test_ctx.responder.write_message(&[0x01]).unwrap();
+        assert_eq!(
+            test_ctx.initiator.read_confirmation(&act2),
+            Err(HandshakeError::UnexpectedCipherSuite(0x01))
+        );
+        assert_matches!(test_ctx.initiator, InitiatorAwaitingActTwo(_));
+
+        // Negotiation methods called out of order
+        let mut test_ctx = TestCtx::new();
+        assert_eq!(test_ctx.initiator.write_confirmation(), Err(HandshakeError::AwaitingLocal(1)));
+        assert_eq!(
+            test_ctx.responder.write_proposal(&CipherSuite::ALL),
+            Err(HandshakeError::AwaitingRemote(1))
+        );
+    }
+
+    #[test]
+    fn message_payload_tampered() {
+        let mut test_ctx = TestCtx::new();
+        let mut act1 = test_ctx.initiator.write_message(b"payload").unwrap();
+        act1[ACT_ONE_LENGTH] ^= 1;
+        assert!(test_ctx.responder.read_message(&act1).is_err());
+        assert!(test_ctx.responder.read_message(&act1[..ACT_ONE_LENGTH - 1]).is_err());
+    }
+
+    #[test]
+    fn ephemeral_reuse() {
+        let mut cache = EphemeralCache::new(2);
+        let act1 = TestCtx::new().valid_act1;
+
+        // Invalid acts are not remembered
+        let mut responder = TestCtx::new().responder;
+        let mut invalid = act1.clone();
+        invalid[ACT_ONE_LENGTH - 1] ^= 1;
+        assert!(responder.advance_handshake_checked(&invalid, &mut cache).is_err());
+        assert!(cache.is_empty());
+
+        assert!(responder.advance_handshake_checked(&act1, &mut cache).unwrap().is_some());
+        assert_eq!(cache.len(), 1);
+        let mut responder = TestCtx::new().responder;
+        assert_eq!(
+            responder.advance_handshake_checked(&act1, &mut cache).unwrap_err(),
+            HandshakeError::EphemeralReuse
+        );
+        assert_matches!(responder, ResponderAwaitingActOne(_));
+
+        // Handshake with a fresh ephemeral key succeeds
+        let mut initiator = InitiatorStarting(InitiatorStartingState::new(
+            SecretKey::new([0x11; 32]),
+            SecretKey::new([0x13; 32]),
+            TestCtx::new().responder_static_public_key,
+        ));
+        let act1 = initiator.advance_handshake(&[]).unwrap().unwrap();
+        let act2 = responder.advance_handshake_checked(&act1, &mut cache).unwrap().unwrap();
+        let act3 = initiator.advance_handshake(&act2).unwrap().unwrap();
+        assert!(responder.advance_handshake_checked(&act3, &mut cache).unwrap().is_none());
+        assert!(responder.is_handshake_complete());
+        assert_eq!(cache.len(), 2);
+
+        // Least recently seen key is evicted
+        let ephemeral = SecretKey::new([0x12; 32]).recover_public_key().unwrap();
+        assert!(cache.contains(&ephemeral));
+        assert!(cache.insert(SecretKey::new([0x14; 32]).recover_public_key().unwrap()));
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&ephemeral));
+        assert!(cache.insert(ephemeral));
+    }
+
+    #[test]
+    fn failure_reasons() {
+        fn corrupt(act: &[u8], pos: usize) -> Vec<u8> {
+            let mut act = act.to_vec();
+            act[pos] ^= 1;
+            act
+        }
+
+        let test_ctx = TestCtx::new();
+        let (act1, act2, act3) = (test_ctx.valid_act1, test_ctx.valid_act2, test_ctx.valid_act3);
+        let responder = test_ctx.responder;
+        let mut initiator = test_ctx.initiator;
+        initiator.write_message(b"").unwrap();
+        let mut responder_act3 = responder.clone();
+        responder_act3.read_message(&act1).unwrap();
+        responder_act3.write_message(b"").unwrap();
+
+        for (mut state, act, no) in
+            [(responder, act1, 1u8), (initiator, act2, 2), (responder_act3, act3, 3)]
+        {
+            assert_eq!(
+                state.clone().read_message(&act[..act.len() - 1]).unwrap_err(),
+                HandshakeError::InvalidActLen {
+                    act: no,
+                    expected: act.len(),
+                    found: act.len() - 1
+                }
+            );
+            assert_eq!(
+                state.clone().read_message(&corrupt(&act, 0)).unwrap_err(),
+                HandshakeError::UnexpectedVersion {
+                    version: 1,
+                    act: no
+                }
+            );
+            assert_eq!(
+                state.clone().read_message(&corrupt(&act, act.len() - 1)).unwrap_err(),
+                HandshakeError::InvalidActTag { act: no }
+            );
+            state.read_message(&act).unwrap();
+            assert_ne!(state.read_message(&act).unwrap_err(), HandshakeError::InvalidActTag {
+                act: no
+            });
+        }
+
+        // Initiator using wrong static key of the responder
+        let mut initiator = NoiseXkState::InitiatorStarting(InitiatorStartingState::new(
+            SecretKey::new([0x11; 32]),
+            SecretKey::new([0x12; 32]),
+            SecretKey::new([0x31; 32]).recover_public_key().unwrap(),
+        ));
+        let act1 = initiator.write_message(b"").unwrap();
+        assert_eq!(
+            TestCtx::new().responder.read_message(&act1).unwrap_err(),
+            HandshakeError::InvalidActTag { act: 1 }
+        );
+
+        // Low-order ephemeral key
+        let mut act1 = vec![0u8; ACT_ONE_LENGTH];
+        act1[1] = 1;
+        assert_eq!(
+            TestCtx::new().responder.read_message(&act1).unwrap_err(),
+            HandshakeError::LowOrderPubkey
+        );
+
+        let mut test_ctx = TestCtx::new();
+        assert_eq!(
+            test_ctx.initiator.read_message(&test_ctx.valid_act2).unwrap_err(),
+            HandshakeError::AwaitingLocal(1)
+        );
+    }
+
+    #[test]
+    fn framed_acts() {
+        let mut test_ctx = TestCtx::new();
+
+        let act1 = test_ctx.initiator.write_framed_act().unwrap();
+        assert_eq!(act1[..2], (ACT_ONE_LENGTH as u16).to_be_bytes());
+        assert_eq!(act1[2..], test_ctx.valid_act1);
+        let act2 = test_ctx.responder.read_framed_act(&act1).unwrap().unwrap();
+        assert_eq!(act2[..2], (ACT_TWO_LENGTH as u16).to_be_bytes());
+        assert_eq!(act2[2..], test_ctx.valid_act2);
+        let act3 = test_ctx.initiator.read_framed_act(&act2).unwrap().unwrap();
+        assert_eq!(act3[..2], (ACT_THREE_LENGTH as u16).to_be_bytes());
+        assert_eq!(act3[2..], test_ctx.valid_act3);
+        assert_eq!(test_ctx.responder.read_framed_act(&act3).unwrap(), None);
+
+        assert!(test_ctx.initiator.is_handshake_complete());
+        assert!(test_ctx.responder.is_handshake_complete());
+    }
+
+    #[test]
+    fn framed_act_pipelined() {
+        let mut test_ctx = TestCtx::new();
+        let act1 = test_ctx.initiator.write_framed_act().unwrap();
+        let (act2, consumed) = test_ctx.responder.read_framed_act_from(&act1).unwrap();
+        assert_eq!(consumed, act1.len());
+        let act3 = test_ctx.initiator.read_framed_act(&act2.unwrap()).unwrap().unwrap();
+
+        let (encryptor, decryptor) = test_ctx.initiator.try_into_split().unwrap();
+        let mut initiator = Transport::with_split(Role::Initiator, encryptor, decryptor);
+        let mut buf = act3.clone();
+        buf.extend(initiator.send(b"pipelined").unwrap());
+
+        assert_eq!(
+            test_ctx.responder.clone().read_framed_act_from(&buf[..act3.len() - 1]).unwrap_err(),
+            HandshakeError::InvalidActLen {
+                act: 3,
+                expected: ACT_THREE_LENGTH,
+                found: ACT_THREE_LENGTH - 1
+            }
+        );
+        let (response, consumed) = test_ctx.responder.read_framed_act_from(&buf).unwrap();
+        assert_eq!(response, None);
+        assert_eq!(consumed, act3.len());
+        let (encryptor, decryptor) = test_ctx.responder.try_into_split().unwrap();
+        let mut responder = Transport::with_split(Role::Responder, encryptor, decryptor);
+        assert_eq!(responder.recv(&buf[consumed..]).unwrap().unwrap(), b"pipelined");
+    }
+
+    #[test]
+    fn framed_act_wrong_len() {
+        let mut test_ctx = TestCtx::new();
+        let act1 = test_ctx.initiator.write_framed_act().unwrap();
+
+        // Garbage act data would fail the version check or decryption, thus
+        // the length errors prove nothing was processed
+        let mut frame = vec![0xFFu8; act1.len()];
+        frame[..2].copy_from_slice(&(ACT_THREE_LENGTH as u16).to_be_bytes());
+        assert_eq!(
+            test_ctx.responder.read_framed_act(&frame).unwrap_err(),
+            HandshakeError::InvalidActLen {
+                act: 1,
+                expected: ACT_ONE_LENGTH,
+                found: ACT_THREE_LENGTH
+            }
+        );
+        frame[..2].copy_from_slice(&(ACT_ONE_LENGTH as u16).to_be_bytes());
+        assert_eq!(
+            test_ctx.responder.read_framed_act(&frame[..20]).unwrap_err(),
+            HandshakeError::InvalidActLen {
+                act: 1,
+                expected: ACT_ONE_LENGTH,
+                found: 18
+            }
+        );
+        assert_eq!(
+            test_ctx.responder.read_framed_act(&frame[..1]).unwrap_err(),
+            HandshakeError::InvalidActLen {
+                act: 1,
+                expected: ACT_ONE_LENGTH,
+                found: 1
+            }
+        );
+        assert_eq!(test_ctx.responder.next_read_len(), Some(ACT_ONE_LENGTH));
+
+        assert!(test_ctx.responder.read_framed_act(&act1).unwrap().is_some());
+    }
+
+    #[test]
+    fn next_read_len() {
+        let mut test_ctx = TestCtx::new();
+        assert_eq!(test_ctx.initiator.next_read_len(), Some(0));
+        assert_eq!(test_ctx.responder.next_read_len(), Some(ACT_ONE_LENGTH));
+
+        let act1 = test_ctx.initiator.advance_handshake(&[]).unwrap().unwrap();
+        assert_eq!(test_ctx.responder.next_read_len(), Some(act1.len()));
+        assert!(test_ctx.responder.advance_handshake(&act1[..10]).unwrap().is_none());
+        assert_eq!(test_ctx.responder.next_read_len(), Some(act1.len() - 10));
+        let act2 = test_ctx.responder.advance_handshake(&act1[10..]).unwrap().unwrap();
+
+        assert_eq!(test_ctx.initiator.next_read_len(), Some(act2.len()));
+        let act3 = test_ctx.initiator.advance_handshake(&act2).unwrap().unwrap();
+        assert_eq!(test_ctx.initiator.next_read_len(), None);
+
+        assert_eq!(test_ctx.responder.next_read_len(), Some(act3.len()));
+        assert!(test_ctx.responder.advance_handshake(&act3).unwrap().is_none());
+        assert_eq!(test_ctx.responder.next_read_len(), None);
+    }
 }
@@ -22,24 +22,53 @@
 mod ceremony;
 mod handshake;
 
-pub use handshake::NoiseXkState;
+pub use ceremony::{ACT_ONE_LENGTH, ACT_THREE_LENGTH, ACT_TWO_LENGTH};
+pub use handshake::{EphemeralCache, NoiseXkState, ACT_LEN_PREFIX_SIZE};
 
 mod init {
-    use ed25519::x25519::{KeyPair, PublicKey, SecretKey};
+    use ed25519::x25519::{PublicKey, SecretKey};
+    #[cfg(feature = "std")]
+    use rand_core::OsRng;
+    use rand_core::{CryptoRng, RngCore};
 
     use super::NoiseXkState;
     use crate::noise::framing::NoiseTranscoder;
 
     impl NoiseTranscoder<NoiseXkState> {
+        /// Constructs initiator generating its ephemeral key with the operating
+        /// system random number generator; in `no_std` environments use
+        /// [`Self::with_xk_initiator_rng`].
+        #[cfg(feature = "std")]
         pub fn with_xk_initiator(local_key: SecretKey, remote_key: PublicKey) -> Self {
-            let ephemeral_key = KeyPair::generate().sk;
-            let state = NoiseXkState::new_initiator(local_key, remote_key, ephemeral_key);
-            NoiseTranscoder { state }
+            Self::with_xk_initiator_rng(local_key, remote_key, &mut OsRng)
         }
 
+        /// Constructs responder generating its ephemeral key with the operating
+        /// system random number generator; in `no_std` environments use
+        /// [`Self::with_xk_responder_rng`].
+        #[cfg(feature = "std")]
         pub fn with_xk_responder(local_key: SecretKey) -> Self {
-            let ephemeral_key = KeyPair::generate().sk;
-            let state = NoiseXkState::new_responder(local_key, ephemeral_key);
+            Self::with_xk_responder_rng(local_key, &mut OsRng)
+        }
+
+        /// Constructs initiator generating its ephemeral key with the provided
+        /// random number generator.
+        pub fn with_xk_initiator_rng(
+            local_key: SecretKey,
+            remote_key: PublicKey,
+            rng: &mut (impl RngCore + CryptoRng),
+        ) -> Self {
+            let state = NoiseXkState::new_initiator_with_rng(local_key, remote_key, rng);
+            NoiseTranscoder { state }
+        }
+
+        /// Constructs responder generating its ephemeral key with the provided
+        /// random number generator.
+        pub fn with_xk_responder_rng(
+            local_key: SecretKey,
+            rng: &mut (impl RngCore + CryptoRng),
+        ) -> Self {
+            let state = NoiseXkState::new_responder_with_rng(local_key, rng);
             NoiseTranscoder { state }
         }
     }
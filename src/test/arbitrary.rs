@@ -22,6 +22,10 @@
 use ::ed25519::{KeyPair, Seed};
 use quickcheck::Arbitrary;
 
+#[cfg(feature = "i2p")]
+use crate::addr::i2p::{I2pAddr, I2pAddrKind, I2P_EXTENDED_LEN, I2P_HASH_LEN};
+#[cfg(feature = "tor")]
+use crate::addr::tor::OnionAddrV3;
 use crate::crypto::ed25519::PublicKey;
 
 #[derive(Clone, Debug)]
@@ -39,6 +43,15 @@ impl<const N: usize> Arbitrary for ByteArray<N> {
         }
         Self(bytes)
     }
+
+    /// Shrinks towards arrays with zero trailing bytes, reusing shrinking of
+    /// byte vectors.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.0.to_vec().shrink().map(|mut vec| {
+            vec.resize(N, 0);
+            Self(vec.try_into().expect("vector is resized"))
+        }))
+    }
 }
 
 impl Arbitrary for PublicKey {
@@ -50,3 +63,41 @@ impl Arbitrary for PublicKey {
         PublicKey::from(keypair.pk)
     }
 }
+
+#[cfg(feature = "tor")]
+impl Arbitrary for OnionAddrV3 {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let bytes: ByteArray<32> = Arbitrary::arbitrary(g);
+        Self::from(bytes.into_inner())
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let pk = *self.into_public_key();
+        Box::new(ByteArray(pk).shrink().map(|bytes| Self::from(bytes.into_inner())))
+    }
+}
+
+#[cfg(feature = "i2p")]
+impl Arbitrary for I2pAddr {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        if bool::arbitrary(g) {
+            let bytes: ByteArray<I2P_EXTENDED_LEN> = Arbitrary::arbitrary(g);
+            Self::from(bytes.into_inner())
+        } else {
+            let bytes: ByteArray<I2P_HASH_LEN> = Arbitrary::arbitrary(g);
+            Self::from(bytes.into_inner())
+        }
+    }
+
+    /// Shrinks extended addresses into the hash ones and the address data
+    /// towards zero trailing bytes.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let hash =
+            <[u8; I2P_HASH_LEN]>::try_from(&self.as_bytes()[..I2P_HASH_LEN]).expect("fixed length");
+        let shrunk = ByteArray(hash).shrink().map(|bytes| Self::from(bytes.into_inner()));
+        match self.kind() {
+            I2pAddrKind::Hash => Box::new(shrunk),
+            I2pAddrKind::Extended => Box::new(core::iter::once(Self::from(hash)).chain(shrunk)),
+        }
+    }
+}
@@ -19,8 +19,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "ed25519")]
 mod arbitrary;
 
+#[allow(clippy::module_inception)]
 mod test {
     /*
     use std::net;
@@ -0,0 +1,50 @@
+// Set of libraries for privacy-preserving networking apps
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr. Maxim Orlovsky <orlovsky@cyphernet.org>
+//
+// Copyright 2022-2023 Cyphernet Association, Switzerland
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that the library API is usable from `no_std` code. Run with
+//! `cargo test --no-default-features --features noise` to build the library
+//! itself without `std`.
+
+#![no_std]
+
+extern crate alloc;
+
+use cyphernet::addr::InputSnippet;
+
+#[test]
+fn input_snippet() {
+    let snippet = InputSnippet::new("some-very-long-input-exceeding-snippet-length");
+    assert!(snippet.is_truncated());
+    assert_eq!(snippet.as_str().len(), InputSnippet::MAX_LEN);
+}
+
+#[test]
+#[cfg(feature = "noise")]
+fn chacha_round_trip() {
+    use cyphernet::noise::chacha;
+
+    let key = [7u8; chacha::KEY_SIZE];
+    let ciphertext = chacha::encrypt(&key, 1, b"aad", b"message", None).unwrap();
+    assert_eq!(ciphertext.len(), b"message".len() + chacha::TAG_SIZE);
+    let plaintext = chacha::decrypt(&key, 1, b"aad", &ciphertext, None).unwrap();
+    assert_eq!(plaintext, b"message");
+    assert!(chacha::decrypt(&key, 2, b"aad", &ciphertext, None).is_err());
+}